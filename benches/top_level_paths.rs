@@ -0,0 +1,81 @@
+//! Benchmark of the cleaning of thousands of paths given one by one, with several chunk sizes
+//!
+//! Run with `cargo bench --bench top_level_paths`, the tree is created in the temporary directory
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use notox::{Notox, NotoxArgs, Output};
+
+/// Number of files given one by one
+const FILES: usize = 20_000;
+
+/// Number of directories given with the files, each one with [`DIR_ENTRIES`] entries
+const DIRS: usize = 20;
+
+/// Number of entries of each directory
+const DIR_ENTRIES: usize = 500;
+
+/// Number of runs of each chunk size, the fastest one is kept
+const RUNS: usize = 5;
+
+/// Create the synthetic tree, and return the paths to give to notox
+fn create_tree(root: &Path) -> HashSet<PathBuf> {
+    let _ = fs::remove_dir_all(root);
+    fs::create_dir_all(root).unwrap();
+    let mut paths = HashSet::new();
+    for idx in 0..FILES {
+        let file_path = root.join(format!("file {} é.txt", idx));
+        fs::write(&file_path, "").unwrap();
+        paths.insert(file_path);
+    }
+    // a few large directories make the tasks uneven
+    for idx in 0..DIRS {
+        let dir_path = root.join(format!("dir {}", idx));
+        fs::create_dir(&dir_path).unwrap();
+        for entry_idx in 0..DIR_ENTRIES {
+            fs::write(dir_path.join(format!("entry {}.txt", entry_idx)), "").unwrap();
+        }
+        paths.insert(dir_path);
+    }
+    paths
+}
+
+/// Get the fastest run of the dry-run with this chunk size
+fn measure(paths: &HashSet<PathBuf>, chunk_size: Option<usize>) -> Duration {
+    let options = NotoxArgs {
+        output: Output::Quiet,
+        chunk_size,
+        ..Default::default()
+    };
+    let notox = Notox::new(&options);
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let results = notox.run(paths);
+            let elapsed = start.elapsed();
+            assert_eq!(results.len(), FILES + DIRS * (DIR_ENTRIES + 1));
+            elapsed
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn main() {
+    let root = std::env::temp_dir().join("notox_bench_top_level_paths");
+    let paths = create_tree(&root);
+    for chunk_size in [None, Some(16), Some(64), Some(256), Some(1024)] {
+        let elapsed = measure(&paths, chunk_size);
+        let label = chunk_size.map_or("default".to_string(), |size| size.to_string());
+        println!(
+            "chunk size {:>7}: {:>8.1} ms",
+            label,
+            elapsed.as_secs_f64() * 1000.0
+        );
+    }
+    fs::remove_dir_all(&root).unwrap();
+}
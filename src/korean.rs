@@ -0,0 +1,48 @@
+//! Romanization of the Hangul syllables
+//!
+//! Available with the `korean` feature.
+//! The syllables are romanized one by one with the Revised Romanization of Korean,
+//! without the sound changes between syllables (`한국` is `hanguk`).
+
+/// First Hangul syllable
+const FIRST_SYLLABLE: u32 = 0xAC00;
+
+/// Last Hangul syllable
+const LAST_SYLLABLE: u32 = 0xD7A3;
+
+/// Number of syllables sharing the same initial consonant
+const INITIAL_COUNT: u32 = 21 * 28;
+
+/// Number of syllables sharing the same initial consonant and vowel
+const MEDIAL_COUNT: u32 = 28;
+
+/// Romanization of the initial consonants
+const INITIALS: [&str; 19] = [
+    "g", "kk", "n", "d", "tt", "r", "m", "b", "pp", "s", "ss", "", "j", "jj", "ch", "k", "t", "p",
+    "h",
+];
+
+/// Romanization of the vowels
+const MEDIALS: [&str; 21] = [
+    "a", "ae", "ya", "yae", "eo", "e", "yeo", "ye", "o", "wa", "wae", "oe", "yo", "u", "wo", "we",
+    "wi", "yu", "eu", "ui", "i",
+];
+
+/// Romanization of the final consonants
+const FINALS: [&str; 28] = [
+    "", "k", "k", "k", "n", "n", "n", "t", "l", "k", "m", "l", "l", "l", "p", "l", "m", "p", "p",
+    "t", "t", "ng", "t", "t", "k", "t", "p", "t",
+];
+
+/// Romanize a Hangul syllable, `None` if the char is not a Hangul syllable
+pub fn romanize_syllable(one_char: char) -> Option<String> {
+    let code = one_char as u32;
+    if !(FIRST_SYLLABLE..=LAST_SYLLABLE).contains(&code) {
+        return None;
+    }
+    let index = code - FIRST_SYLLABLE;
+    let initial = INITIALS[(index / INITIAL_COUNT) as usize];
+    let medial = MEDIALS[((index % INITIAL_COUNT) / MEDIAL_COUNT) as usize];
+    let last = FINALS[(index % MEDIAL_COUNT) as usize];
+    Some(format!("{}{}{}", initial, medial, last))
+}
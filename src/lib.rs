@@ -26,7 +26,8 @@
 //!     //    json: JsonOutput::JsonDefault,
 //!     //    pretty: false,
 //!     // },
-//!     output: Output::Quiet
+//!     output: Output::Quiet,
+//!     ..Default::default()
 //! };
 //! // as rust struct (recommended)
 //! let res = Notox::new(&notox_args).run(&paths);
@@ -34,6 +35,19 @@
 //! let res = notox(&notox_args, &paths);
 //! ```
 //!
+//! With the `tokio` feature, `Notox::run_stream` yields the changes as a stream,
+//! the walk runs on a blocking thread so the async workers are not blocked.
+//!
+//! ## Exit codes
+//!
+//! | Code | Meaning                                                  | [`ExitCode`]                                   |
+//! | ---- | -------------------------------------------------------- | ---------------------------------------------- |
+//! | 0    | Success                                                  | `Success`                                      |
+//! | 1    | Help or version displayed, or invalid arguments          | `Usage`                                        |
+//! | 2    | Error before or after the cleaning                       | the errors listed in [`ExitCode`]              |
+//! | 3    | No paths found, there was nothing to process             | `NoPathsFound`                                 |
+//! | 4    | With `--check`, some names would be changed              | `ChangesNeeded`                                |
+//!
 //! Coverage is available at [https://n4n5.dev/notox/coverage/](https://n4n5.dev/notox/coverage/)
 //!
 
@@ -49,23 +63,96 @@
 
 use core::fmt;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     ffi::{OsStr, OsString},
-    path::{Path, PathBuf},
+    io::Write,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex, OnceLock, PoisonError,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 #[cfg(feature = "rayon")]
 use rayon::{iter::Either, prelude::*};
 
+#[cfg(feature = "korean")]
+pub mod korean;
+#[cfg(feature = "watch")]
+pub mod watch;
+
+/// Outcome of the entry points of the binary, converted to the exit code of the process with `i32::from`
+///
+/// The errors `FeatureUnavailable`, `SerializeError`, `OutputError`, `WatchError` and `ReadError` share the code 2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExitCode {
+    /// everything was processed, code 0
+    Success,
+
+    /// the help or the version was displayed, or the arguments are invalid, code 1
+    Usage,
+
+    /// an option needs a feature not compiled in this version, code 2
+    FeatureUnavailable,
+
+    /// the output cannot be serialized, code 2
+    SerializeError,
+
+    /// the output cannot be written, code 2
+    OutputError,
+
+    /// the watcher cannot be started, code 2
+    WatchError,
+
+    /// there was no path to process, code 3
+    NoPathsFound,
+
+    /// the directory of the paths to check cannot be read, code 2
+    ReadError,
+
+    /// with `check`, some names would be changed, code 4
+    ChangesNeeded,
+}
+
+impl From<ExitCode> for i32 {
+    fn from(exit_code: ExitCode) -> i32 {
+        match exit_code {
+            ExitCode::Success => 0,
+            ExitCode::Usage => 1,
+            ExitCode::FeatureUnavailable
+            | ExitCode::SerializeError
+            | ExitCode::OutputError
+            | ExitCode::WatchError
+            | ExitCode::ReadError => 2,
+            ExitCode::NoPathsFound => 3,
+            ExitCode::ChangesNeeded => 4,
+        }
+    }
+}
+
 /// Type of JSON output
 #[cfg(feature = "serde")]
 #[derive(Debug, Clone, PartialEq)]
 pub enum JsonOutput {
-    /// full json output
+    /// full json output, a bare array of the changes (`-j`)
     JsonDefault,
 
-    /// only errors in json output
+    /// only errors in json output (`-e`)
     JsonOnlyError,
+
+    /// json output nested like the directories, see [`PathChangeTree`] (`--json-tree`)
+    JsonTree,
+
+    /// the changes and the summary in a versioned object, see [`JSON_ENVELOPE_VERSION`] (`--json-envelope`)
+    ///
+    /// `{"version": 1, "notox_version": "2.0.0", "transliteration_version": "2", "results": [...], "summary": {...}}`
+    JsonEnvelope,
+
+    /// one json object per line (NDJSON), written as soon as the change is done by the binary,
+    /// see [`Notox::run_streaming_json`]
+    JsonLines,
 }
 
 /// Type of output
@@ -77,6 +164,21 @@ pub enum Output {
     /// quiet output
     Quiet,
 
+    /// number of changed names per category of transformation, see [`CategoryTally`]
+    CategoryReport,
+
+    /// original names rejected by the target filesystems, see [`Filesystem::violations`]
+    CompatReport,
+
+    /// encoding of the original names, see [`NameEncoding`] and [`EncodingTally`]
+    EncodingClass,
+
+    /// one CSV row `status,path,modified,error` per change, see [`PathChange::csv_row`]
+    Csv,
+
+    /// a script doing the renames of the dry-run, see [`write_rename_script`]
+    Script(ScriptDialect),
+
     /// json output type and pretty print flag
     #[cfg(feature = "serde")]
     JsonOutput {
@@ -87,6 +189,17 @@ pub enum Output {
     },
 }
 
+/// Language of the rename script, see [`Output::Script`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScriptDialect {
+    /// POSIX shell, with `mv`
+    #[default]
+    Sh,
+
+    /// PowerShell, with `Rename-Item`
+    PowerShell,
+}
+
 impl Output {
     /// Check if the output is verbose
     pub fn is_verbose(&self) -> bool {
@@ -94,6 +207,276 @@ impl Output {
     }
 }
 
+/// How the invalid UTF-8 bytes of a name are replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NonUtf8Policy {
+    /// a run of invalid bytes is replaced by a single separator
+    #[default]
+    Single,
+
+    /// each invalid byte is replaced by a separator, runs are still limited by `max_consecutive_separators`
+    PerByte,
+}
+
+/// Case given to some letters of the cleaned names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    /// uppercase, like `my_File`
+    Upper,
+
+    /// lowercase, like `My_file`
+    Lower,
+}
+
+/// What to do with an existing file at the target of a rename
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClobberPolicy {
+    /// keep the target, the rename fails
+    #[default]
+    Refuse,
+
+    /// let the rename replace the target (or fail, depending on the OS)
+    Overwrite,
+
+    /// move the target to the trash of the system before the rename
+    #[cfg(feature = "trash")]
+    Trash,
+}
+
+/// What to do when the cleaned name of a path is already taken
+///
+/// A name is taken by an existing path, or by another cleaned path of the same directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    /// the path is not renamed and the collision is an error
+    Error,
+
+    /// a numeric suffix is added to the stem, `cafe_1.txt`
+    Suffix,
+
+    /// the path is not renamed, the collision is not an error
+    Skip,
+}
+
+/// Kinds of paths whose name is cleaned, the directories are always traversed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CleanTargets {
+    /// rename the files (and the symbolic links)
+    pub files: bool,
+    /// rename the directories
+    pub dirs: bool,
+}
+
+impl Default for CleanTargets {
+    fn default() -> Self {
+        CleanTargets {
+            files: true,
+            dirs: true,
+        }
+    }
+}
+
+impl CleanTargets {
+    /// Check if the name of a path is cleaned, the path is only read if one kind is not cleaned
+    fn contains(&self, path: &Path) -> bool {
+        match (self.files, self.dirs) {
+            (true, true) => true,
+            (false, false) => false,
+            (files, dirs) => match path
+                .symlink_metadata()
+                .is_ok_and(|metadata| metadata.is_dir())
+            {
+                true => dirs,
+                false => files,
+            },
+        }
+    }
+}
+
+/// How the non-ASCII chars of a name are cleaned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CleanMode {
+    /// the letters are transliterated to ASCII, the other chars are replaced by a separator, see [`check_similar`]
+    #[default]
+    Transliterate,
+
+    /// the Unicode letters, digits and combining marks are kept as is, `café` and `日本語` are unchanged,
+    /// see [`is_kept_unicode`]
+    ///
+    /// The ASCII chars are cleaned as usual, the other chars (controls, whitespaces, symbols...) are
+    /// replaced by a separator or their ASCII transliteration
+    UnsafeOnly,
+}
+
+/// Check if a non-ASCII char is kept as is by [`CleanMode::UnsafeOnly`]
+///
+/// The letters, digits and combining marks are kept, a decomposed `é` keeps its accent
+pub fn is_kept_unicode(one_char: char) -> bool {
+    !one_char.is_ascii()
+        && (one_char.is_alphanumeric()
+            || matches!(one_char, '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}'))
+}
+
+/// Profile of the place where the files are stored, adding its own rules to the cleaning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Profile {
+    /// only the default cleaning
+    #[default]
+    Default,
+
+    /// SharePoint and OneDrive rules:
+    /// - [`SHAREPOINT_FORBIDDEN_CHARS`] are replaced
+    /// - no leading or trailing spaces and no trailing dots
+    /// - `.lock`, `desktop.ini` and names containing `_vti_` are changed
+    /// - Windows reserved names (`CON`, `PRN`, `AUX`, `NUL`, `COM0`-`COM9`, `LPT0`-`LPT9`) get a `_` after the stem
+    SharePoint,
+
+    /// Windows rules, for FAT32, exFAT and NTFS:
+    /// - [`WINDOWS_FORBIDDEN_CHARS`] and the control characters are replaced
+    /// - no trailing spaces and no trailing dots
+    /// - Windows reserved names (`CON`, `PRN`, `AUX`, `NUL`, `COM0`-`COM9`, `LPT0`-`LPT9`) get a `_` after the stem
+    WindowsSafe,
+
+    /// POSIX portable file names:
+    /// - only ASCII letters, digits, `.`, `_` and `-`, the other characters are replaced
+    /// - no leading `-`, and not empty, `.` or `..` (a `_` is added before)
+    PortablePosix,
+}
+
+/// Characters forbidden in SharePoint and OneDrive names
+pub const SHAREPOINT_FORBIDDEN_CHARS: &[char] = &[
+    '~', '"', '#', '%', '&', '*', ':', '<', '>', '?', '/', '\\', '{', '|', '}',
+];
+
+/// Names reserved by Windows, even with an extension
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM0", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7",
+    "COM8", "COM9", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters forbidden in names by Windows, on FAT32, exFAT and NTFS
+pub const WINDOWS_FORBIDDEN_CHARS: &[char] = &['"', '*', '/', ':', '<', '>', '?', '\\', '|'];
+
+/// Target filesystem of the compatibility report, see [`Output::CompatReport`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Filesystem {
+    /// FAT32 with long file names
+    Fat32,
+    /// exFAT
+    ExFat,
+    /// NTFS
+    Ntfs,
+    /// ext4
+    Ext4,
+    /// APFS
+    Apfs,
+}
+
+/// All the filesystems checked by the compatibility report
+pub const ALL_FILESYSTEMS: [Filesystem; 5] = [
+    Filesystem::Fat32,
+    Filesystem::ExFat,
+    Filesystem::Ntfs,
+    Filesystem::Ext4,
+    Filesystem::Apfs,
+];
+
+impl Filesystem {
+    /// Name of the filesystem
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filesystem::Fat32 => "FAT32",
+            Filesystem::ExFat => "exFAT",
+            Filesystem::Ntfs => "NTFS",
+            Filesystem::Ext4 => "ext4",
+            Filesystem::Apfs => "APFS",
+        }
+    }
+
+    /// Get the reasons why a name would be rejected by the filesystem, empty if it is valid
+    ///
+    /// - FAT32, exFAT and NTFS (as used by Windows): no [`WINDOWS_FORBIDDEN_CHARS`] or control chars,
+    ///   no reserved name (`CON`, `NUL`, `COM1`...), no trailing dot or space, 255 UTF-16 units
+    /// - ext4: no `/` or NUL, 255 bytes
+    /// - APFS: no `/` or NUL, valid UTF-8, 255 chars
+    pub fn violations(&self, name: &OsStr) -> Vec<String> {
+        let mut violations = Vec::new();
+        if *self == Filesystem::Ext4 {
+            if name
+                .as_encoded_bytes()
+                .iter()
+                .any(|byte| matches!(byte, b'/' | 0))
+            {
+                violations.push("forbidden char".to_string());
+            }
+            if name.len() > MAX_NAME_BYTES {
+                violations.push(format!(
+                    "too long ({} bytes, max {})",
+                    name.len(),
+                    MAX_NAME_BYTES
+                ));
+            }
+            return violations;
+        }
+        let name = match name.to_str() {
+            Some(name) => name,
+            None => return vec!["not valid Unicode".to_string()],
+        };
+        let forbidden = name
+            .chars()
+            .filter(|one_char| match self {
+                Filesystem::Apfs => matches!(one_char, '/' | '\0'),
+                _ => WINDOWS_FORBIDDEN_CHARS.contains(one_char) || *one_char < ' ',
+            })
+            .collect::<Vec<char>>();
+        if !forbidden.is_empty() {
+            violations.push(format!(
+                "forbidden chars {:?}",
+                forbidden.iter().collect::<String>()
+            ));
+        }
+        let (len, unit) = match self {
+            Filesystem::Apfs => (name.chars().count(), "chars"),
+            _ => (name.encode_utf16().count(), "UTF-16 units"),
+        };
+        if len > MAX_NAME_BYTES {
+            violations.push(format!(
+                "too long ({} {}, max {})",
+                len, unit, MAX_NAME_BYTES
+            ));
+        }
+        if *self != Filesystem::Apfs {
+            if windows_reserved_stem_len(name).is_some() {
+                violations.push("reserved name".to_string());
+            }
+            if name.ends_with(['.', ' ']) && name != "." && name != ".." {
+                violations.push("ends with a dot or a space".to_string());
+            }
+        }
+        violations
+    }
+}
+
+/// Token replacing the redacted substrings given with `--redact`
+pub const REDACTED_TOKEN: &str = "REDACTED";
+
+/// Substrings to redact from the names before cleaning them
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone)]
+pub struct Redaction {
+    /// Pattern of the substrings to redact
+    pub pattern: regex::Regex,
+    /// Text replacing the substrings, used as is (no capture group expansion)
+    pub replacement: String,
+}
+
+#[cfg(feature = "regex")]
+impl PartialEq for Redaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern.as_str() == other.pattern.as_str() && self.replacement == other.replacement
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 /// Options for the program
 pub struct NotoxArgs {
@@ -102,12 +485,311 @@ pub struct NotoxArgs {
 
     /// which kind of json output to use
     pub output: Output,
+
+    /// names of directories that are never renamed, their content is still cleaned
+    /// unless `skip_no_rename_dir_content`
+    pub no_rename_dirs: Vec<OsString>,
+
+    /// if true, the content of the `no_rename_dirs` is not cleaned either
+    pub skip_no_rename_dir_content: bool,
+
+    /// kinds of paths renamed, the files only or the directories only
+    pub targets: CleanTargets,
+
+    /// patterns of the entries skipped with their content, see [`is_excluded`]
+    pub exclude: Vec<String>,
+
+    /// maximum depth of the cleaned entries, 0 only cleans the given paths, 1 also their entries...
+    pub max_depth: Option<usize>,
+
+    /// number of threads of the traversal with the `rayon` feature, the global pool is used if not set
+    pub threads: Option<usize>,
+
+    /// minimum number of paths to check cleaned by each task of rayon, rayon splits them as it wants if not set
+    ///
+    /// A larger chunk lowers the cost of the tasks when thousands of files are given,
+    /// the paths are sorted so a chunk has neighbouring paths
+    pub chunk_size: Option<usize>,
+
+    /// recurse into the symbolic links to directories, each directory is cleaned once even with cyclic links
+    pub follow_symlinks: bool,
+
+    /// if false, the hidden entries (starting with a dot) found in the directories are skipped with their content,
+    /// the given paths are always cleaned
+    pub clean_hidden: bool,
+
+    /// if set, the output is written to this file instead of stdout
+    pub output_file: Option<PathBuf>,
+
+    /// if true, the output file uses `\r\n` line endings instead of `\n`
+    pub crlf: bool,
+
+    /// if true, the input paths are canonicalized (and deduplicated) before being processed
+    pub canonicalize_input: bool,
+
+    /// if true, an immediately repeated extension is removed, `file.txt.txt` becomes `file.txt`
+    pub dedupe_extension: bool,
+
+    /// if true, the separators around the dots and at both ends of the name are removed,
+    /// `__file_.txt_` becomes `file.txt`, see [`trim_separators`]
+    pub trim: bool,
+
+    /// if true, only the last dot is kept as the extension separator, the other dots of the stem are cleaned like separators
+    pub preserve_extension: bool,
+
+    /// maximum number of consecutive separators kept in a name (default 1), 0 removes them
+    pub max_consecutive_separators: usize,
+
+    /// profile of the place where the files are stored
+    pub profile: Profile,
+
+    /// how the non-ASCII chars are cleaned, transliterated or kept if they are letters
+    pub mode: CleanMode,
+
+    /// categories of transformation applied, the chars of the other categories are kept as is
+    pub categories: Vec<Category>,
+
+    /// how the invalid UTF-8 bytes of a name are replaced
+    pub non_utf8_policy: NonUtf8Policy,
+
+    /// stem used when the cleaned stem has no letter or digit left, the extension is kept
+    pub empty_stem_placeholder: Option<String>,
+
+    /// what to do with an existing file at the target of a rename, it is kept by default
+    pub clobber_policy: ClobberPolicy,
+
+    /// what to do when a cleaned name is already taken, `None` lets the `clobber_policy` handle the existing targets
+    pub on_collision: Option<CollisionPolicy>,
+
+    /// print the unchanged paths and the links in the default output
+    pub show_unchanged: bool,
+
+    /// add the size and the modification time of the files to the JSON output
+    pub include_metadata: bool,
+
+    /// print nothing if no path is changed, would be changed or is in error
+    pub quiet_unless_changes: bool,
+
+    /// do not print the [`Summary`] line at the end of the default output
+    pub no_summary: bool,
+
+    /// print the counts of the entries of each directory in the default output,
+    /// after the changes and in the order the directories are done
+    pub per_dir_summary: bool,
+
+    /// if true, the number of paths processed is printed on stderr every [`PROGRESS_INTERVAL`] paths,
+    /// only with the verbose output
+    pub progress: bool,
+
+    /// extensions of the directories treated as files (like `app`): their name is cleaned but not their content
+    pub treat_as_file_exts: Vec<String>,
+
+    /// characters kept as is in the extension (after the last dot), like `+` for `c++`
+    pub ext_allowed_chars: HashSet<char>,
+
+    /// ASCII punctuation kept as is in the whole name, like `+` for `foo+bar-1.0.tgz`
+    ///
+    /// The separator `_` is always cleaned, so its runs are still collapsed
+    pub allowed_extra: HashSet<char>,
+
+    /// if set, each successful rename is appended to this journal, see [`read_journal`]
+    pub journal: Option<PathBuf>,
+
+    /// paths already renamed by a previous run, they are skipped
+    pub journal_done: HashSet<PathBuf>,
+
+    /// if set, each file is copied in this directory before its rename, see [`backup_path`]
+    ///
+    /// The directory is skipped by the walk, compared by its canonical path, so its copies are never cleaned.
+    /// An existing copy is kept, the new one gets a numeric suffix
+    pub backup_dir: Option<PathBuf>,
+
+    /// make the names safe as shell arguments, see [`is_shell_safe`]
+    pub shell_safe: bool,
+
+    /// maximum length in bytes of a cleaned name, the longer names are not renamed and reported as errors
+    pub reject_over_len: Option<usize>,
+
+    /// maximum length in bytes of a cleaned name, the stem of the longer names is truncated and the extension kept
+    ///
+    /// The names suffixed by the [`CollisionPolicy::Suffix`] also fit, `Some(0)` is no limit
+    pub max_length: Option<usize>,
+
+    /// keep the ASCII spaces instead of replacing them, a run of spaces becomes a single space
+    pub keep_spaces: bool,
+
+    /// replacement of the whitespaces (space, tab, non-breaking space...) instead of the separator,
+    /// `My File.txt` becomes `My-File.txt` with `-`
+    ///
+    /// The replacement and its copies in the name are separators, their runs with the other separators
+    /// are limited by `max_consecutive_separators`, `my - file.txt` becomes `my-file.txt`.
+    /// A run with a whitespace only has the replacement, `r (1) é.txt` becomes `r-1-e.txt`
+    pub space_replacement: Option<char>,
+
+    /// maximum number of renames of a run, the next paths are skipped (the dry-run counts the proposed renames)
+    pub rename_budget: Option<usize>,
+
+    /// only the entries modified after this time are renamed, the others are skipped
+    pub newer_than: Option<SystemTime>,
+
+    /// only the entries modified before this time are renamed, the others are skipped
+    pub older_than: Option<SystemTime>,
+
+    /// lowercase the whole cleaned name, before the `post_separator_case`
+    pub lowercase: bool,
+
+    /// case of the letter right after each separator, unchanged if `None`
+    ///
+    /// The separators are `_`, the `space_replacement` and the spaces kept by `keep_spaces`
+    pub post_separator_case: Option<CaseMode>,
+
+    /// replacements of non-ASCII chars taking precedence over [`TRANSLITERATIONS`], like `ß` to `ss`
+    ///
+    /// The ASCII chars are not looked up
+    pub overrides: HashMap<char, String>,
+
+    /// transliterate `ß` to `ss` and `ẞ` to `SS` instead of a single `s`
+    pub german_sharp_s: bool,
+
+    /// romanize the Hangul syllables instead of replacing them by separators
+    #[cfg(feature = "korean")]
+    pub romanize_hangul: bool,
+
+    /// substrings redacted from the names before cleaning them, only in the names valid in UTF-8
+    #[cfg(feature = "regex")]
+    pub redactions: Vec<Redaction>,
+
+    /// encoding of the names not valid in UTF-8, they are transcoded to UTF-8 before cleaning them
+    #[cfg(feature = "encoding")]
+    pub source_encoding: Option<&'static encoding_rs::Encoding>,
+
+    /// normalize the names to NFC before cleaning them, so the decomposed names (from macOS) give the same result
+    #[cfg(feature = "normalize")]
+    pub normalize_nfc: bool,
+
+    /// if set, this directory is watched and the new files are cleaned
+    #[cfg(feature = "watch")]
+    pub watch: Option<PathBuf>,
+
+    /// if set, these changes of a previous run are undone instead of cleaning the paths, see [`undo`]
+    #[cfg(feature = "serde")]
+    pub undo: Option<Vec<PathChange>>,
+
+    /// if true, the names are read from stdin and cleaned without renaming anything, see [`clean_lines`]
+    pub stdin: bool,
+
+    /// if true, each rename is confirmed on stdin before being done, see [`prompt_decisions`]
+    pub interactive: bool,
+
+    /// if true, the paths are read NUL-separated from stdin, see [`read_null_paths`],
+    /// and the default output is `path\0modified\0` pairs, see [`write_null_pairs`]
+    pub null_separated: bool,
+
+    /// if true, nothing is renamed and [`ExitCode::ChangesNeeded`] is returned if a name would change
+    pub check: bool,
+
+    /// if true, the paths not started yet are not cleaned after the first error, see [`Notox::try_run`]
+    pub stop_at_first_error: bool,
+}
+
+impl Default for NotoxArgs {
+    fn default() -> Self {
+        NotoxArgs {
+            dry_run: true,
+            output: Output::Default,
+            no_rename_dirs: Vec::new(),
+            skip_no_rename_dir_content: false,
+            targets: CleanTargets::default(),
+            exclude: Vec::new(),
+            max_depth: None,
+            threads: None,
+            chunk_size: None,
+            follow_symlinks: false,
+            clean_hidden: true,
+            output_file: None,
+            crlf: false,
+            canonicalize_input: false,
+            dedupe_extension: false,
+            trim: false,
+            preserve_extension: false,
+            max_consecutive_separators: 1,
+            profile: Profile::Default,
+            mode: CleanMode::Transliterate,
+            categories: ALL_CATEGORIES.to_vec(),
+            non_utf8_policy: NonUtf8Policy::Single,
+            empty_stem_placeholder: None,
+            clobber_policy: ClobberPolicy::Refuse,
+            on_collision: None,
+            show_unchanged: false,
+            include_metadata: false,
+            quiet_unless_changes: false,
+            no_summary: false,
+            per_dir_summary: false,
+            progress: false,
+            treat_as_file_exts: Vec::new(),
+            ext_allowed_chars: HashSet::new(),
+            allowed_extra: HashSet::new(),
+            journal: None,
+            journal_done: HashSet::new(),
+            backup_dir: None,
+            shell_safe: false,
+            reject_over_len: None,
+            max_length: None,
+            keep_spaces: false,
+            space_replacement: None,
+            rename_budget: None,
+            newer_than: None,
+            older_than: None,
+            lowercase: false,
+            post_separator_case: None,
+            overrides: HashMap::new(),
+            german_sharp_s: false,
+            #[cfg(feature = "korean")]
+            romanize_hangul: false,
+            #[cfg(feature = "regex")]
+            redactions: Vec::new(),
+            #[cfg(feature = "encoding")]
+            source_encoding: None,
+            #[cfg(feature = "normalize")]
+            normalize_nfc: false,
+            #[cfg(feature = "watch")]
+            watch: None,
+            #[cfg(feature = "serde")]
+            undo: None,
+            stdin: false,
+            interactive: false,
+            null_separated: false,
+            check: false,
+            stop_at_first_error: false,
+        }
+    }
 }
 
 impl NotoxArgs {
+    /// Check if a substring of the name of the path is redacted
+    pub fn is_redacted(&self, path: &Path) -> bool {
+        #[cfg(feature = "regex")]
+        if let Some(name) = path.file_name().and_then(OsStr::to_str) {
+            return self
+                .redactions
+                .iter()
+                .any(|redaction| redaction.pattern.is_match(name));
+        }
+        #[cfg(not(feature = "regex"))]
+        let _ = path;
+        false
+    }
+
     /// Create a new NotoxArgs instance with default values
     pub fn is_vervose(&self) -> bool {
-        self.output.is_verbose()
+        self.output.is_verbose() && !self.quiet_unless_changes && !self.null_separated
+    }
+
+    /// Check if all the categories of transformation are applied
+    pub fn cleans_all_categories(&self) -> bool {
+        ALL_CATEGORIES
+            .iter()
+            .all(|category| self.categories.contains(category))
     }
 }
 
@@ -122,7 +804,8 @@ impl fmt::Display for NotoxArgs {
 }
 
 /// Contains information about a result of a single file
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum PathChange {
     /// The path has not been changed
     Unchanged {
@@ -145,6 +828,41 @@ pub enum PathChange {
         /// The error message
         error: String,
     },
+    /// The path would be changed, the rename was not done because of the dry-run
+    WouldChange {
+        /// The original path
+        path: PathBuf,
+        /// The modified path
+        modified: PathBuf,
+    },
+    /// The path would be changed but the rename is known to fail (dry-run only)
+    WouldFail {
+        /// The original path
+        path: PathBuf,
+        /// The modified path
+        modified: PathBuf,
+        /// The reason of the failure
+        reason: String,
+    },
+    /// The path was moved to the trash to make room for a rename, see [`ClobberPolicy`]
+    Trashed {
+        /// The trashed path
+        path: PathBuf,
+    },
+    /// A symbolic link found in a directory, its own name is cleaned like a file, see `follow_symlinks`
+    Symlink {
+        /// The path of the link
+        path: PathBuf,
+        /// The target of the link, as stored in the link
+        target: PathBuf,
+    },
+    /// The path was not processed because of an option, like the limit of renames
+    Skipped {
+        /// The original path
+        path: PathBuf,
+        /// Why the path was skipped
+        reason: String,
+    },
     /// There was an error while processing the path
     Error {
         /// The original path
@@ -152,489 +870,3563 @@ pub enum PathChange {
         /// The error message
         error: String,
     },
+    /// The path was not renamed because its copy could not be made, see `backup_dir`
+    ErrorBackup {
+        /// The original path
+        path: PathBuf,
+        /// The path of the copy
+        backup: PathBuf,
+        /// The error message
+        error: String,
+    },
 }
 
-#[cfg(feature = "serde")]
-impl<'de> serde::Deserialize<'de> for PathChange {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        #[derive(serde::Deserialize)]
-        struct Helper {
-            /// Path string
-            path: String,
-            /// Modified string
-            modified: Option<String>,
-            /// Error string
-            error: Option<String>,
+impl PathChange {
+    /// Get the original path of the change
+    pub fn path(&self) -> &Path {
+        match self {
+            PathChange::Unchanged { path }
+            | PathChange::Changed { path, .. }
+            | PathChange::ErrorRename { path, .. }
+            | PathChange::WouldChange { path, .. }
+            | PathChange::WouldFail { path, .. }
+            | PathChange::Trashed { path }
+            | PathChange::Symlink { path, .. }
+            | PathChange::Skipped { path, .. }
+            | PathChange::Error { path, .. }
+            | PathChange::ErrorBackup { path, .. } => path,
         }
+    }
 
-        let helper = Helper::deserialize(deserializer)?;
-
-        let path = PathBuf::from(helper.path);
-        match (helper.modified, helper.error) {
-            (None, None) => Ok(PathChange::Unchanged { path }),
-            (Some(modified), None) => Ok(PathChange::Changed {
-                path,
-                modified: PathBuf::from(modified),
-            }),
-            (Some(modified), Some(error)) => Ok(PathChange::ErrorRename {
-                path,
-                modified: PathBuf::from(modified),
-                error,
-            }),
-            (None, Some(error)) => Ok(PathChange::Error { path, error }),
+    /// Get the status of the change, like `changed` or `would-change`, the same as the JSON `status`
+    pub fn status(&self) -> &'static str {
+        match self {
+            PathChange::Unchanged { .. } => "unchanged",
+            PathChange::Changed { .. } => "changed",
+            PathChange::ErrorRename { .. } => "error-rename",
+            PathChange::WouldChange { .. } => "would-change",
+            PathChange::WouldFail { .. } => "would-fail",
+            PathChange::Trashed { .. } => "trashed",
+            PathChange::Symlink { .. } => "symlink",
+            PathChange::Skipped { .. } => "skipped",
+            PathChange::Error { .. } => "error",
+            PathChange::ErrorBackup { .. } => "error-backup",
         }
     }
-}
-
-#[cfg(feature = "serde")]
-impl serde::Serialize for PathChange {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        use serde::ser::SerializeStruct;
 
-        let mut state = serializer.serialize_struct("PathChange", 3)?;
+    /// Get the growth in bytes of the name, `None` if the change has no cleaned path
+    ///
+    /// The names are compared, not the whole paths, see [`name_length_delta`]
+    pub fn length_delta(&self) -> Option<isize> {
         match self {
-            PathChange::Unchanged { path } => {
-                state.serialize_field("path", path)?;
-                state.serialize_field("modified", &Option::<PathBuf>::None)?;
-                state.serialize_field("error", &Option::<String>::None)?;
-            }
-            PathChange::Changed { path, modified } => {
-                state.serialize_field("path", path)?;
-                state.serialize_field("modified", &Some(modified))?;
-                state.serialize_field("error", &Option::<String>::None)?;
+            PathChange::Changed { path, modified }
+            | PathChange::WouldChange { path, modified }
+            | PathChange::ErrorRename { path, modified, .. }
+            | PathChange::WouldFail { path, modified, .. } => Some(
+                name_byte_len(modified.file_name()) as isize
+                    - name_byte_len(path.file_name()) as isize,
+            ),
+            _ => None,
+        }
+    }
+
+    /// Get the CSV row `status,path,modified,error` of the change, without line ending
+    ///
+    /// The target of a symbolic link and the copy of a failed backup are in `modified`, the reason
+    /// of a skipped path or of a rename that would fail is in `error`. The fields with a comma, a quote or a line break are quoted.
+    pub fn csv_row(&self) -> String {
+        let (modified, error): (Option<&Path>, Option<&str>) = match self {
+            PathChange::Unchanged { .. } | PathChange::Trashed { .. } => (None, None),
+            PathChange::Changed { modified, .. } | PathChange::WouldChange { modified, .. } => {
+                (Some(modified), None)
             }
             PathChange::ErrorRename {
-                path,
-                modified,
-                error,
-            } => {
-                state.serialize_field("path", path)?;
-                state.serialize_field("modified", &Some(modified))?;
-                state.serialize_field("error", &Some(error))?;
-            }
-            PathChange::Error { path, error } => {
-                state.serialize_field("path", path)?;
-                state.serialize_field("modified", &Option::<PathBuf>::None)?;
-                state.serialize_field("error", &Some(error))?;
-            }
-        }
-        state.end()
+                modified, error, ..
+            } => (Some(modified), Some(error)),
+            PathChange::WouldFail {
+                modified, reason, ..
+            } => (Some(modified), Some(reason)),
+            PathChange::Symlink { target, .. } => (Some(target), None),
+            PathChange::Skipped { reason, .. } => (None, Some(reason)),
+            PathChange::Error { error, .. } => (None, Some(error)),
+            PathChange::ErrorBackup { backup, error, .. } => (Some(backup), Some(error)),
+        };
+        [
+            self.status().to_string(),
+            self.path().display().to_string(),
+            modified
+                .map(|modified| modified.display().to_string())
+                .unwrap_or_default(),
+            error.unwrap_or_default().to_string(),
+        ]
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<_>>()
+        .join(",")
     }
-}
 
-/// Push a char to a string if a condition is true
-#[inline(always)]
-fn push_underscore_if(stri: &mut String, to_push: char, condition: bool) {
-    if condition {
-        stri.push(to_push);
+    /// Check if the change is a rename not done because of the dry-run
+    pub fn is_dry_run_rename(&self) -> bool {
+        matches!(self, PathChange::WouldChange { .. })
+    }
+
+    /// Check if the change is an error
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            PathChange::Error { .. }
+                | PathChange::ErrorRename { .. }
+                | PathChange::WouldFail { .. }
+                | PathChange::ErrorBackup { .. }
+        )
     }
 }
 
-/// Check if a vector of bytes is similar to a char
-#[inline(always)]
-pub fn check_similar(curr_char: Option<char>, name_acc: &mut String, last_was_under: bool) -> bool {
-    if let Some(one_char) = curr_char {
-        match one_char {
-            'A' | 'Ⓐ' | 'Ａ' | 'À' | 'Á' | 'Â' | 'Ầ' | 'Ấ' | 'Ẫ' | 'Ẩ' | 'Ã' | 'Ā' | 'Ă' | 'Ằ'
-            | 'Ắ' | 'Ẵ' | 'Ẳ' | 'Ȧ' | 'Ǡ' | 'Ä' | 'Ǟ' | 'Ả' | 'Å' | 'Ǻ' | 'Ǎ' | 'Ȁ' | 'Ȃ' | 'Ạ'
-            | 'Ậ' | 'Ặ' | 'Ḁ' | 'Ą' | 'Ⱥ' | 'Ɐ' => name_acc.push('A'),
-            'Ꜳ' => name_acc.push_str("AA"),
-            'Æ' | 'Ǽ' | 'Ǣ' => name_acc.push('A'),
-            'Ꜵ' => name_acc.push_str("AO"),
-            'Ꜷ' => name_acc.push_str("AU"),
-            'Ꜹ' | 'Ꜻ' => name_acc.push_str("AV"),
-            'Ꜽ' => name_acc.push_str("AY"),
-            'B' | 'Ⓑ' | 'Ｂ' | 'Ḃ' | 'Ḅ' | 'Ḇ' | 'Ƀ' | 'Ƃ' | 'Ɓ' => name_acc.push('B'),
-            'C' | 'Ⓒ' | 'Ｃ' | 'Ć' | 'Ĉ' | 'Ċ' | 'Č' | 'Ç' | 'Ḉ' | 'Ƈ' | 'Ȼ' | 'Ꜿ' => {
-                name_acc.push('C')
-            }
-            'D' | 'Ⓓ' | 'Ｄ' | 'Ḋ' | 'Ď' | 'Ḍ' | 'Ḑ' | 'Ḓ' | 'Ḏ' | 'Đ' | 'Ƌ' | 'Ɗ' | 'Ɖ' | 'Ꝺ' => {
-                name_acc.push('D')
-            }
-            'Ǳ' | 'Ǆ' => name_acc.push_str("DZ"),
-            'ǲ' | 'ǅ' => name_acc.push_str("Dz"),
-            'E' | 'Ⓔ' | 'Ｅ' | 'È' | 'É' | 'Ê' | 'Ề' | 'Ế' | 'Ễ' | 'Ể' | 'Ẽ' | 'Ē' | 'Ḕ' | 'Ḗ'
-            | 'Ĕ' | 'Ė' | 'Ë' | 'Ẻ' | 'Ě' | 'Ȅ' | 'Ȇ' | 'Ẹ' | 'Ệ' | 'Ȩ' | 'Ḝ' | 'Ę' | 'Ḙ' | 'Ḛ'
-            | 'Ɛ' | 'Ǝ' => name_acc.push('E'),
-            'F' | 'Ⓕ' | 'Ｆ' | 'Ḟ' | 'Ƒ' | 'Ꝼ' => name_acc.push('F'),
-            'G' | 'Ⓖ' | 'Ｇ' | 'Ǵ' | 'Ĝ' | 'Ḡ' | 'Ğ' | 'Ġ' | 'Ǧ' | 'Ģ' | 'Ǥ' | 'Ɠ' | 'Ꞡ' | 'Ᵹ'
-            | 'Ꝿ' => name_acc.push('G'),
-            'H' | 'Ⓗ' | 'Ｈ' | 'Ĥ' | 'Ḣ' | 'Ḧ' | 'Ȟ' | 'Ḥ' | 'Ḩ' | 'Ḫ' | 'Ħ' | 'Ⱨ' | 'Ⱶ' | 'Ɥ' => {
-                name_acc.push('H')
-            }
-            'I' | 'Ⓘ' | 'Ｉ' | 'Ì' | 'Í' | 'Î' | 'Ĩ' | 'Ī' | 'Ĭ' | 'İ' | 'Ï' | 'Ḯ' | 'Ỉ' | 'Ǐ'
-            | 'Ȉ' | 'Ȋ' | 'Ị' | 'Į' | 'Ḭ' | 'Ɨ' => name_acc.push('I'),
-            'J' | 'Ⓙ' | 'Ｊ' | 'Ĵ' | 'Ɉ' => name_acc.push('J'),
-            'K' | 'Ⓚ' | 'Ｋ' | 'Ḱ' | 'Ǩ' | 'Ḳ' | 'Ķ' | 'Ḵ' | 'Ƙ' | 'Ⱪ' | 'Ꝁ' | 'Ꝃ' | 'Ꝅ' | 'Ꞣ' => {
-                name_acc.push('K')
-            }
-            'L' | 'Ⓛ' | 'Ｌ' | 'Ŀ' | 'Ĺ' | 'Ľ' | 'Ḷ' | 'Ḹ' | 'Ļ' | 'Ḽ' | 'Ḻ' | 'Ł' | 'Ƚ' | 'Ɫ'
-            | 'Ⱡ' | 'Ꝉ' | 'Ꝇ' | 'Ꞁ' => name_acc.push('L'),
-            'Ǉ' => name_acc.push_str("LJ"),
-            'ǈ' => name_acc.push_str("Lj"),
-            'M' | 'Ⓜ' | 'Ｍ' | 'Ḿ' | 'Ṁ' | 'Ṃ' | 'Ɱ' | 'Ɯ' => name_acc.push('M'),
-            'N' | 'Ⓝ' | 'Ｎ' | 'Ǹ' | 'Ń' | 'Ñ' | 'Ṅ' | 'Ň' | 'Ṇ' | 'Ņ' | 'Ṋ' | 'Ṉ' | 'Ƞ' | 'Ɲ'
-            | 'Ꞑ' | 'Ꞥ' => name_acc.push('N'),
-            'Ǌ' => name_acc.push_str("NJ"),
-            'ǋ' => name_acc.push_str("Nj"),
-            'O' | 'Ⓞ' | 'Ｏ' | 'Ò' | 'Ó' | 'Ô' | 'Ồ' | 'Ố' | 'Ỗ' | 'Ổ' | 'Õ' | 'Ṍ' | 'Ȭ' | 'Ṏ'
-            | 'Ō' | 'Ṑ' | 'Ṓ' | 'Ŏ' | 'Ȯ' | 'Ȱ' | 'Ö' | 'Ȫ' | 'Ỏ' | 'Ő' | 'Ǒ' | 'Ȍ' | 'Ȏ' | 'Ơ'
-            | 'Ờ' | 'Ớ' | 'Ỡ' | 'Ở' | 'Ợ' | 'Ọ' | 'Ộ' | 'Ǫ' | 'Ǭ' | 'Ø' | 'Ǿ' | 'Ɔ' | 'Ɵ' | 'Ꝋ'
-            | 'Ꝍ' => name_acc.push('O'),
-            'Ƣ' => name_acc.push_str("OI"),
-            'Ꝏ' => name_acc.push_str("OO"),
-            'Ȣ' => name_acc.push_str("OU"),
-            '\u{008C}' | 'Œ' => name_acc.push_str("OE"),
-            '\u{009C}' | 'œ' => name_acc.push_str("oe"),
-            'P' | 'Ⓟ' | 'Ｐ' | 'Ṕ' | 'Ṗ' | 'Ƥ' | 'Ᵽ' | 'Ꝑ' | 'Ꝓ' | 'Ꝕ' => {
-                name_acc.push('P')
-            }
-            'Q' | 'Ⓠ' | 'Ｑ' | 'Ꝗ' | 'Ꝙ' | 'Ɋ' => name_acc.push('Q'),
-            'R' | 'Ⓡ' | 'Ｒ' | 'Ŕ' | 'Ṙ' | 'Ř' | 'Ȑ' | 'Ȓ' | 'Ṛ' | 'Ṝ' | 'Ŗ' | 'Ṟ' | 'Ɍ' | 'Ɽ'
-            | 'Ꝛ' | 'Ꞧ' | 'Ꞃ' => name_acc.push('R'),
-            'S' | 'Ⓢ' | 'Ｓ' | 'ẞ' | 'Ś' | 'Ṥ' | 'Ŝ' | 'Ṡ' | 'Š' | 'Ṧ' | 'Ṣ' | 'Ṩ' | 'Ș' | 'Ş'
-            | 'Ȿ' | 'Ꞩ' | 'Ꞅ' => name_acc.push('S'),
-            'T' | 'Ⓣ' | 'Ｔ' | 'Ṫ' | 'Ť' | 'Ṭ' | 'Ț' | 'Ţ' | 'Ṱ' | 'Ṯ' | 'Ŧ' | 'Ƭ' | 'Ʈ' | 'Ⱦ'
-            | 'Ꞇ' => name_acc.push('T'),
-            'Ꜩ' => name_acc.push_str("TZ"),
-            'U' | 'Ⓤ' | 'Ｕ' | 'Ù' | 'Ú' | 'Û' | 'Ũ' | 'Ṹ' | 'Ū' | 'Ṻ' | 'Ŭ' | 'Ü' | 'Ǜ' | 'Ǘ'
-            | 'Ǖ' | 'Ǚ' | 'Ủ' | 'Ů' | 'Ű' | 'Ǔ' | 'Ȕ' | 'Ȗ' | 'Ư' | 'Ừ' | 'Ứ' | 'Ữ' | 'Ử' | 'Ự'
-            | 'Ụ' | 'Ṳ' | 'Ų' | 'Ṷ' | 'Ṵ' | 'Ʉ' => name_acc.push('U'),
-            'V' | 'Ⓥ' | 'Ｖ' | 'Ṽ' | 'Ṿ' | 'Ʋ' | 'Ꝟ' | 'Ʌ' => name_acc.push('V'),
-            'Ꝡ' => name_acc.push_str("VY"),
-            'W' | 'Ⓦ' | 'Ｗ' | 'Ẁ' | 'Ẃ' | 'Ŵ' | 'Ẇ' | 'Ẅ' | 'Ẉ' | 'Ⱳ' => {
-                name_acc.push('W')
-            }
-            'X' | 'Ⓧ' | 'Ｘ' | 'Ẋ' | 'Ẍ' => name_acc.push('X'),
-            'Y' | 'Ⓨ' | 'Ｙ' | 'Ỳ' | 'Ý' | 'Ŷ' | 'Ỹ' | 'Ȳ' | 'Ẏ' | 'Ÿ' | 'Ỷ' | 'Ỵ' | 'Ƴ' | 'Ɏ'
-            | 'Ỿ' => name_acc.push('Y'),
-            'Z' | 'Ⓩ' | 'Ｚ' | 'Ź' | 'Ẑ' | 'Ż' | 'Ž' | 'Ẓ' | 'Ẕ' | 'Ƶ' | 'Ȥ' | 'Ɀ' | 'Ⱬ' | 'Ꝣ' => {
-                name_acc.push('Z')
-            }
-            'a' | 'ⓐ' | 'ａ' | 'ẚ' | 'à' | 'á' | 'â' | 'ầ' | 'ấ' | 'ẫ' | 'ẩ' | 'ã' | 'ā' | 'ă'
-            | 'ằ' | 'ắ' | 'ẵ' | 'ẳ' | 'ȧ' | 'ǡ' | 'ä' | 'ǟ' | 'ả' | 'å' | 'ǻ' | 'ǎ' | 'ȁ' | 'ȃ'
-            | 'ạ' | 'ậ' | 'ặ' | 'ḁ' | 'ą' | 'ⱥ' | 'ɐ' => name_acc.push('a'),
-            'ꜳ' => name_acc.push_str("aa"),
-            'æ' | 'ǽ' | 'ǣ' => name_acc.push('a'),
-            'ꜵ' => name_acc.push_str("ao"),
-            'ꜷ' => name_acc.push_str("au"),
-            'ꜹ' | 'ꜻ' => name_acc.push_str("av"),
-            'ꜽ' => name_acc.push_str("ay"),
-            'b' | 'ⓑ' | 'ｂ' | 'ḃ' | 'ḅ' | 'ḇ' | 'ƀ' | 'ƃ' | 'ɓ' | 'þ' => {
-                name_acc.push('b')
-            }
-            'c' | 'ⓒ' | 'ｃ' | 'ć' | 'ĉ' | 'ċ' | 'č' | 'ç' | 'ḉ' | 'ƈ' | 'ȼ' | 'ꜿ' | 'ↄ' => {
-                name_acc.push('c')
-            }
-            'd' | 'ⓓ' | 'ｄ' | 'ḋ' | 'ď' | 'ḍ' | 'ḑ' | 'ḓ' | 'ḏ' | 'đ' | 'ƌ' | 'ɖ' | 'ɗ' | 'ꝺ' => {
-                name_acc.push('d')
-            }
-            'ǳ' | 'ǆ' => name_acc.push_str("dz"),
-            'e' | 'ⓔ' | 'ｅ' | 'è' | 'é' | 'ê' | 'ề' | 'ế' | 'ễ' | 'ể' | 'ẽ' | 'ē' | 'ḕ' | 'ḗ'
-            | 'ĕ' | 'ė' | 'ë' | 'ẻ' | 'ě' | 'ȅ' | 'ȇ' | 'ẹ' | 'ệ' | 'ȩ' | 'ḝ' | 'ę' | 'ḙ' | 'ḛ'
-            | 'ɇ' | 'ɛ' | 'ǝ' => name_acc.push('e'),
-            'f' | 'ⓕ' | 'ｆ' | 'ḟ' | 'ƒ' | 'ꝼ' => name_acc.push('f'),
-            'g' | 'ⓖ' | 'ｇ' | 'ǵ' | 'ĝ' | 'ḡ' | 'ğ' | 'ġ' | 'ǧ' | 'ģ' | 'ǥ' | 'ɠ' | 'ꞡ' | 'ᵹ'
-            | 'ꝿ' => name_acc.push('g'),
-            'h' | 'ⓗ' | 'ｈ' | 'ĥ' | 'ḣ' | 'ḧ' | 'ȟ' | 'ḥ' | 'ḩ' | 'ḫ' | 'ẖ' | 'ħ' | 'ⱨ' | 'ⱶ'
-            | 'ɥ' => name_acc.push('h'),
-            'ƕ' => name_acc.push_str("hv"),
-            'i' | 'ⓘ' | 'ｉ' | 'ì' | 'í' | 'î' | 'ĩ' | 'ī' | 'ĭ' | 'ï' | 'ḯ' | 'ỉ' | 'ǐ' | 'ȉ'
-            | 'ȋ' | 'ị' | 'į' | 'ḭ' | 'ɨ' | 'ı' => name_acc.push('i'),
-            'j' | 'ⓙ' | 'ｊ' | 'ĵ' | 'ǰ' | 'ɉ' => name_acc.push('j'),
-            'k' | 'ⓚ' | 'ｋ' | 'ḱ' | 'ǩ' | 'ḳ' | 'ķ' | 'ḵ' | 'ƙ' | 'ⱪ' | 'ꝁ' | 'ꝃ' | 'ꝅ' | 'ꞣ' => {
-                name_acc.push('k')
-            }
-            'l' | 'ⓛ' | 'ｌ' | 'ŀ' | 'ĺ' | 'ľ' | 'ḷ' | 'ḹ' | 'ļ' | 'ḽ' | 'ḻ' | 'ſ' | 'ł' | 'ƚ'
-            | 'ɫ' | 'ⱡ' | 'ꝉ' | 'ꞁ' | 'ꝇ' => name_acc.push('l'),
-            'ǉ' => name_acc.push_str("lj"),
-            'm' | 'ⓜ' | 'ｍ' | 'ḿ' | 'ṁ' | 'ṃ' | 'ɱ' | 'ɯ' => name_acc.push('m'),
-            'n' | 'ⓝ' | 'ｎ' | 'ǹ' | 'ń' | 'ñ' | 'ṅ' | 'ň' | 'ṇ' | 'ņ' | 'ṋ' | 'ṉ' | 'ƞ' | 'ɲ'
-            | 'ŉ' | 'ꞑ' | 'ꞥ' => name_acc.push('n'),
-            'ǌ' => name_acc.push_str("nj"),
-            'o' | 'ⓞ' | 'ｏ' | 'ò' | 'ó' | 'ô' | 'ồ' | 'ố' | 'ỗ' | 'ổ' | 'õ' | 'ṍ' | 'ȭ' | 'ṏ'
-            | 'ō' | 'ṑ' | 'ṓ' | 'ŏ' | 'ȯ' | 'ȱ' | 'ö' | 'ȫ' | 'ỏ' | 'ő' | 'ǒ' | 'ȍ' | 'ȏ' | 'ơ'
-            | 'ờ' | 'ớ' | 'ỡ' | 'ở' | 'ợ' | 'ọ' | 'ộ' | 'ǫ' | 'ǭ' | 'ø' | 'ǿ' | 'ɔ' | 'ꝋ' | 'ꝍ'
-            | 'ɵ' => name_acc.push('o'),
-            'ƣ' => name_acc.push_str("oi"),
-            'ȣ' => name_acc.push_str("ou"),
-            'ꝏ' => name_acc.push_str("oo"),
-            'p' | 'ⓟ' | 'ｐ' | 'ṕ' | 'ṗ' | 'ƥ' | 'ᵽ' | 'ꝑ' | 'ꝓ' | 'ꝕ' => {
-                name_acc.push('p')
-            }
-            'q' | 'ⓠ' | 'ｑ' | 'ɋ' | 'ꝗ' | 'ꝙ' => name_acc.push('q'),
-            'r' | 'ⓡ' | 'ｒ' | 'ŕ' | 'ṙ' | 'ř' | 'ȑ' | 'ȓ' | 'ṛ' | 'ṝ' | 'ŗ' | 'ṟ' | 'ɍ' | 'ɽ'
-            | 'ꝛ' | 'ꞧ' | 'ꞃ' => name_acc.push('r'),
-            's' | 'ⓢ' | 'ｓ' | 'ß' | 'ś' | 'ṥ' | 'ŝ' | 'ṡ' | 'š' | 'ṧ' | 'ṣ' | 'ṩ' | 'ș' | 'ş'
-            | 'ȿ' | 'ꞩ' | 'ꞅ' | 'ẛ' => name_acc.push('s'),
-            't' | 'ⓣ' | 'ｔ' | 'ṫ' | 'ẗ' | 'ť' | 'ṭ' | 'ț' | 'ţ' | 'ṱ' | 'ṯ' | 'ŧ' | 'ƭ' | 'ʈ'
-            | 'ⱦ' | 'ꞇ' => name_acc.push('t'),
-            'ꜩ' => name_acc.push_str("tz"),
-            'u' | 'ⓤ' | 'ｕ' | 'ù' | 'ú' | 'û' | 'ũ' | 'ṹ' | 'ū' | 'ṻ' | 'ŭ' | 'ü' | 'ǜ' | 'ǘ'
-            | 'ǖ' | 'ǚ' | 'ủ' | 'ů' | 'ű' | 'ǔ' | 'ȕ' | 'ȗ' | 'ư' | 'ừ' | 'ứ' | 'ữ' | 'ử' | 'ự'
-            | 'ụ' | 'ṳ' | 'ų' | 'ṷ' | 'ṵ' | 'ʉ' => name_acc.push('u'),
-            'v' | 'ⓥ' | 'ｖ' | 'ṽ' | 'ṿ' | 'ʋ' | 'ꝟ' | 'ʌ' => name_acc.push('v'),
-            'ꝡ' => name_acc.push_str("vy"),
-            'w' | 'ⓦ' | 'ｗ' | 'ẁ' | 'ẃ' | 'ŵ' | 'ẇ' | 'ẅ' | 'ẘ' | 'ẉ' | 'ⱳ' => {
-                name_acc.push('w')
-            }
-            'x' | 'ⓧ' | 'ｘ' | 'ẋ' | 'ẍ' => name_acc.push('x'),
-            'y' | 'ⓨ' | 'ｙ' | 'ỳ' | 'ý' | 'ŷ' | 'ỹ' | 'ȳ' | 'ẏ' | 'ÿ' | 'ỷ' | 'ẙ' | 'ỵ' | 'ƴ'
-            | 'ɏ' | 'ỿ' => name_acc.push('y'),
-            'z' | 'ⓩ' | 'ｚ' | 'ź' | 'ẑ' | 'ż' | 'ž' | 'ẓ' | 'ẕ' | 'ƶ' | 'ȥ' | 'ɀ' | 'ⱬ' | 'ꝣ' => {
-                name_acc.push('z')
-            }
-            '–' => {
-                name_acc.push('-');
-                return false;
-            }
-            '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' => {}
-            _ => {
-                if !last_was_under {
-                    name_acc.push('_');
-                }
-                return true;
+/// A failure of a run, see [`Notox::try_run`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotoxError {
+    /// a path cannot be read or cleaned
+    Path {
+        /// the path in error
+        path: PathBuf,
+        /// the error message
+        error: String,
+    },
+
+    /// a path cannot be renamed
+    Rename {
+        /// the path to rename
+        path: PathBuf,
+        /// the cleaned path
+        modified: PathBuf,
+        /// the error message
+        error: String,
+    },
+
+    /// with the dry-run, a rename would fail
+    WouldFail {
+        /// the path to rename
+        path: PathBuf,
+        /// the cleaned path
+        modified: PathBuf,
+        /// why the rename would fail
+        reason: String,
+    },
+
+    /// a path was not renamed because its copy could not be made
+    Backup {
+        /// the path to rename
+        path: PathBuf,
+        /// the path of the copy
+        backup: PathBuf,
+        /// the error message
+        error: String,
+    },
+
+    /// all the failures of a run, in the order of the results
+    Multiple(Vec<NotoxError>),
+}
+
+impl NotoxError {
+    /// Get the error of a change, `None` if the change is not an error, see [`PathChange::is_error`]
+    pub fn from_change(one_change: &PathChange) -> Option<NotoxError> {
+        match one_change {
+            PathChange::Error { path, error } => Some(NotoxError::Path {
+                path: path.clone(),
+                error: error.clone(),
+            }),
+            PathChange::ErrorRename {
+                path,
+                modified,
+                error,
+            } => Some(NotoxError::Rename {
+                path: path.clone(),
+                modified: modified.clone(),
+                error: error.clone(),
+            }),
+            PathChange::WouldFail {
+                path,
+                modified,
+                reason,
+            } => Some(NotoxError::WouldFail {
+                path: path.clone(),
+                modified: modified.clone(),
+                reason: reason.clone(),
+            }),
+            PathChange::ErrorBackup {
+                path,
+                backup,
+                error,
+            } => Some(NotoxError::Backup {
+                path: path.clone(),
+                backup: backup.clone(),
+                error: error.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for NotoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotoxError::Path { path, error } => write!(f, "{} : {}", path.display(), error),
+            NotoxError::Rename {
+                path,
+                modified,
+                error,
+            } => write!(
+                f,
+                "{} -> {} : {}",
+                path.display(),
+                modified.display(),
+                error
+            ),
+            NotoxError::WouldFail {
+                path,
+                modified,
+                reason,
+            } => write!(
+                f,
+                "{} -> {} : would fail, {}",
+                path.display(),
+                modified.display(),
+                reason
+            ),
+            NotoxError::Backup {
+                path,
+                backup,
+                error,
+            } => write!(
+                f,
+                "{} : not renamed, cannot copy to {}, {}",
+                path.display(),
+                backup.display(),
+                error
+            ),
+            NotoxError::Multiple(errors) => {
+                write!(f, "{} errors", errors.len())?;
+                for one_error in errors {
+                    write!(f, "\n{}", one_error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for NotoxError {}
+
+/// Header of the CSV output, see [`PathChange::csv_row`]
+pub const CSV_HEADER: &str = "status,path,modified,error";
+
+/// Quote a CSV field if it has a comma, a quote or a line break, the quotes are doubled
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+/// Split results into non-error changes and errors
+pub fn partition(results: Vec<PathChange>) -> (Vec<PathChange>, Vec<PathChange>) {
+    results
+        .into_iter()
+        .partition(|one_change| !one_change.is_error())
+}
+
+/// Remove the [`PathChange::Error`] entries identical to a previous one (same path and message)
+///
+/// A directory reachable in several ways can give the same read error many times,
+/// the first entry is kept and the other results are not changed
+pub fn dedup_errors(results: &mut Vec<PathChange>) {
+    let mut seen = HashSet::new();
+    results.retain(|one_change| is_first_occurrence(&mut seen, one_change));
+}
+
+/// Check if a change is not an error already in `seen`, and add it to `seen`
+fn is_first_occurrence(seen: &mut HashSet<(PathBuf, String)>, one_change: &PathChange) -> bool {
+    match one_change {
+        PathChange::Error { path, error } => seen.insert((path.clone(), error.clone())),
+        _ => true,
+    }
+}
+
+/// Sort results in a safe order to apply them: the parent directories before their entries
+///
+/// The sort is stable, the results at the same depth keep their order
+pub fn sort_for_apply(results: &mut [PathChange]) {
+    results.sort_by_key(|one_change| one_change.path().components().count());
+}
+
+/// Sort results in a safe order to undo them: the entries before their parent directories
+///
+/// The sort is stable, the results at the same depth keep their order
+pub fn sort_for_undo(results: &mut [PathChange]) {
+    results.sort_by_key(|one_change| std::cmp::Reverse(one_change.path().components().count()));
+}
+
+/// Results split into non-error changes and errors, can be collected from an iterator of [`PathChange`]
+#[derive(Debug, Default, PartialEq)]
+pub struct PartitionedChanges {
+    /// The changes that are not errors
+    pub ok: Vec<PathChange>,
+    /// The errors
+    pub errors: Vec<PathChange>,
+}
+
+impl PartitionedChanges {
+    /// Convert to a `Result`, `Err` contains the errors if there is at least one
+    /// # Errors
+    /// Return the errors if there is at least one
+    pub fn into_result(self) -> Result<Vec<PathChange>, Vec<PathChange>> {
+        match self.errors.is_empty() {
+            true => Ok(self.ok),
+            false => Err(self.errors),
+        }
+    }
+}
+
+impl FromIterator<PathChange> for PartitionedChanges {
+    fn from_iter<I: IntoIterator<Item = PathChange>>(iter: I) -> Self {
+        let (ok, errors) = iter
+            .into_iter()
+            .partition(|one_change| !one_change.is_error());
+        PartitionedChanges { ok, errors }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PathChange {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(serde::Deserialize)]
+        struct Helper {
+            /// Path string
+            path: String,
+            /// Modified string
+            modified: Option<String>,
+            /// Error string
+            error: Option<String>,
+            /// Status string, missing in the outputs of the older versions
+            status: Option<String>,
+            /// Reason string, only set for the skipped paths
+            reason: Option<String>,
+            /// Target string, only set for the symbolic links
+            target: Option<String>,
+            /// Backup string, only set for the failed backups
+            backup: Option<String>,
+        }
+
+        let helper = Helper::deserialize(deserializer)?;
+
+        let path = PathBuf::from(helper.path);
+        if helper.status.as_deref() == Some("trashed") {
+            return Ok(PathChange::Trashed { path });
+        }
+        if let (Some("symlink"), Some(target)) = (helper.status.as_deref(), &helper.target) {
+            return Ok(PathChange::Symlink {
+                path,
+                target: PathBuf::from(target),
+            });
+        }
+        if let (Some("skipped"), Some(reason)) = (helper.status.as_deref(), &helper.reason) {
+            return Ok(PathChange::Skipped {
+                path,
+                reason: reason.clone(),
+            });
+        }
+        if let (Some("would-change"), Some(modified)) = (helper.status.as_deref(), &helper.modified)
+        {
+            return Ok(PathChange::WouldChange {
+                path,
+                modified: PathBuf::from(modified),
+            });
+        }
+        if let (Some("error-backup"), Some(backup), Some(error)) =
+            (helper.status.as_deref(), &helper.backup, &helper.error)
+        {
+            return Ok(PathChange::ErrorBackup {
+                path,
+                backup: PathBuf::from(backup),
+                error: error.clone(),
+            });
+        }
+        if let (Some("would-fail"), Some(modified), Some(reason)) =
+            (helper.status.as_deref(), &helper.modified, &helper.error)
+        {
+            return Ok(PathChange::WouldFail {
+                path,
+                modified: PathBuf::from(modified),
+                reason: reason.clone(),
+            });
+        }
+        match (helper.modified, helper.error) {
+            (None, None) => Ok(PathChange::Unchanged { path }),
+            (Some(modified), None) => Ok(PathChange::Changed {
+                path,
+                modified: PathBuf::from(modified),
+            }),
+            // written before the dry-run renames had their own status
+            (Some(modified), Some(error)) if error == DRY_RUN_ERROR => {
+                Ok(PathChange::WouldChange {
+                    path,
+                    modified: PathBuf::from(modified),
+                })
+            }
+            (Some(modified), Some(error)) => Ok(PathChange::ErrorRename {
+                path,
+                modified: PathBuf::from(modified),
+                error,
+            }),
+            (None, Some(error)) => Ok(PathChange::Error { path, error }),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl PathChange {
+    /// Serialize the fields of the change
+    ///
+    /// The `status` is always written, the same `modified` and `error` can be a rename or a dry-run rename
+    fn serialize_fields<S: serde::ser::SerializeStruct>(
+        &self,
+        state: &mut S,
+    ) -> Result<(), S::Error> {
+        let (modified, error): (Option<&Path>, Option<&str>) = match self {
+            PathChange::Unchanged { .. }
+            | PathChange::Trashed { .. }
+            | PathChange::Symlink { .. }
+            | PathChange::Skipped { .. } => (None, None),
+            PathChange::Changed { modified, .. } | PathChange::WouldChange { modified, .. } => {
+                (Some(modified), None)
+            }
+            PathChange::ErrorRename {
+                modified, error, ..
+            } => (Some(modified), Some(error)),
+            PathChange::WouldFail {
+                modified, reason, ..
+            } => (Some(modified), Some(reason)),
+            PathChange::Error { error, .. } | PathChange::ErrorBackup { error, .. } => {
+                (None, Some(error))
+            }
+        };
+        state.serialize_field("path", self.path())?;
+        state.serialize_field("modified", &modified)?;
+        state.serialize_field("error", &error)?;
+        state.serialize_field("status", self.status())?;
+        match self {
+            PathChange::Symlink { target, .. } => state.serialize_field("target", target)?,
+            PathChange::Skipped { reason, .. } => state.serialize_field("reason", reason)?,
+            PathChange::ErrorBackup { backup, .. } => state.serialize_field("backup", backup)?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PathChange {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("PathChange", 4)?;
+        self.serialize_fields(&mut state)?;
+        state.end()
+    }
+}
+
+/// A [`PathChange`] serialized with the size and the modification time of its file
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathChangeWithMetadata<'a> {
+    /// The change
+    pub change: &'a PathChange,
+    /// Size of the file in bytes, `None` if the metadata cannot be read
+    pub size: Option<u64>,
+    /// Modification time of the file in seconds since the Unix epoch, `None` if the metadata cannot be read
+    pub mtime: Option<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a> PathChangeWithMetadata<'a> {
+    /// Read the metadata of the current path of the change (the modified path if it was renamed)
+    pub fn new(change: &'a PathChange) -> PathChangeWithMetadata<'a> {
+        let current_path = match change {
+            PathChange::Changed { modified, .. } => modified.as_path(),
+            _ => change.path(),
+        };
+        let metadata = std::fs::metadata(current_path).ok();
+        PathChangeWithMetadata {
+            change,
+            size: metadata.as_ref().map(|metadata| metadata.len()),
+            mtime: metadata
+                .and_then(|metadata| metadata.modified().ok())
+                .and_then(|mtime| mtime.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PathChangeWithMetadata<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("PathChange", 6)?;
+        self.change.serialize_fields(&mut state)?;
+        if let Some(size) = self.size {
+            state.serialize_field("size", &size)?;
+        }
+        if let Some(mtime) = self.mtime {
+            state.serialize_field("mtime", &mtime)?;
+        }
+        state.end()
+    }
+}
+
+/// A node of the results nested like the directories, see [`JsonOutput::JsonTree`]
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathChangeTree<'a> {
+    /// Last component of the path
+    pub name: String,
+    /// The change of the path, `None` for a parent directory without result
+    pub change: Option<&'a PathChange>,
+    /// The nodes of the entries of the directory
+    pub children: Vec<PathChangeTree<'a>>,
+}
+
+/// A node of [`PathChangeTree`] while it is built, its children are indexes
+#[cfg(feature = "serde")]
+struct TreeNodeBuilder<'a> {
+    /// Last component of the path
+    name: String,
+    /// The change of the path
+    change: Option<&'a PathChange>,
+    /// Indexes of the children nodes
+    children: Vec<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a> PathChangeTree<'a> {
+    /// Nest the results using the components of their paths
+    ///
+    /// The entries of a renamed directory are found under its modified path,
+    /// so the directory must come before its entries like in [`Notox::run`]
+    pub fn from_results(results: &'a [PathChange]) -> Vec<PathChangeTree<'a>> {
+        let mut nodes = Vec::new();
+        let mut roots = Vec::new();
+        let mut indexes = HashMap::new();
+        for one_change in results {
+            let idx = Self::node_index(one_change.path(), &mut nodes, &mut roots, &mut indexes);
+            nodes[idx].change = Some(one_change);
+            if let PathChange::Changed { modified, .. } = one_change {
+                indexes.insert(modified.clone(), idx);
+            }
+        }
+        roots
+            .into_iter()
+            .map(|idx| Self::build(idx, &nodes))
+            .collect()
+    }
+
+    /// Get the index of the node of a path, creating it and its parents if needed
+    fn node_index(
+        path: &Path,
+        nodes: &mut Vec<TreeNodeBuilder<'a>>,
+        roots: &mut Vec<usize>,
+        indexes: &mut HashMap<PathBuf, usize>,
+    ) -> usize {
+        if let Some(idx) = indexes.get(path) {
+            return *idx;
+        }
+        let parent_idx = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map(|parent| Self::node_index(parent, nodes, roots, indexes));
+        let idx = nodes.len();
+        nodes.push(TreeNodeBuilder {
+            name: path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .to_string(),
+            change: None,
+            children: Vec::new(),
+        });
+        match parent_idx {
+            Some(parent_idx) => nodes[parent_idx].children.push(idx),
+            None => roots.push(idx),
+        }
+        indexes.insert(path.to_path_buf(), idx);
+        idx
+    }
+
+    /// Convert the node at `idx` and its children
+    fn build(idx: usize, nodes: &[TreeNodeBuilder<'a>]) -> PathChangeTree<'a> {
+        PathChangeTree {
+            name: nodes[idx].name.clone(),
+            change: nodes[idx].change,
+            children: nodes[idx]
+                .children
+                .iter()
+                .map(|child_idx| Self::build(*child_idx, nodes))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PathChangeTree<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("PathChangeTree", 6)?;
+        state.serialize_field("name", &self.name)?;
+        if let Some(change) = self.change {
+            change.serialize_fields(&mut state)?;
+        }
+        state.serialize_field("children", &self.children)?;
+        state.end()
+    }
+}
+
+/// Version of the transliteration table used by [`check_similar`], and of the decoding of the invalid UTF-8 bytes
+///
+/// It is bumped each time the table or the decoding changes the default output, so cached outputs can be invalidated.
+/// Version 2 rejects the overlong UTF-8 sequences, `\xC1\x81` becomes `_` instead of `A`
+pub const TRANSLITERATION_VERSION: &str = "2";
+
+/// Get the version of the transliteration table, see [`TRANSLITERATION_VERSION`]
+pub fn transliteration_version() -> &'static str {
+    TRANSLITERATION_VERSION
+}
+
+/// Category of transformation applied to a name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// accented or non-latin letters transliterated to ASCII
+    Accents,
+
+    /// symbols replaced by a separator
+    Symbols,
+
+    /// whitespaces replaced by a separator
+    Whitespace,
+
+    /// control characters replaced by a separator
+    Control,
+}
+
+/// All the categories of transformation
+pub const ALL_CATEGORIES: [Category; 4] = [
+    Category::Accents,
+    Category::Symbols,
+    Category::Whitespace,
+    Category::Control,
+];
+
+/// Get the category of transformation of a char, `None` if the char is kept as is
+pub fn char_category(one_char: char) -> Option<Category> {
+    match one_char {
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => None,
+        one_char if one_char.is_control() => Some(Category::Control),
+        one_char if one_char.is_whitespace() => Some(Category::Whitespace),
+        one_char if one_char.is_ascii() => Some(Category::Symbols),
+        one_char => {
+            let mut transliterated = String::new();
+            let is_separator = check_similar(Some(one_char), &mut transliterated, true);
+            match !is_separator && transliterated.chars().all(|c| c.is_ascii_alphanumeric()) {
+                true => Some(Category::Accents),
+                false => Some(Category::Symbols),
+            }
+        }
+    }
+}
+
+/// Get the categories of transformation needed to clean a name
+pub fn name_categories(name: &OsStr) -> Vec<Category> {
+    let mut categories = Vec::new();
+    for category in name.to_string_lossy().chars().filter_map(char_category) {
+        if !categories.contains(&category) {
+            categories.push(category);
+        }
+    }
+    categories
+}
+
+/// A problem of a name found by [`audit`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameIssue {
+    /// a control char (tab, line feed, escape...) at this byte offset
+    ControlChar {
+        /// byte offset of the char in the name
+        offset: usize,
+        /// the control char
+        one_char: char,
+    },
+
+    /// a char without glyph (zero-width space, bidirectional override, soft hyphen...) at this byte offset
+    InvisibleChar {
+        /// byte offset of the char in the name
+        offset: usize,
+        /// the invisible char
+        one_char: char,
+    },
+
+    /// bytes not valid in UTF-8 at this byte offset
+    InvalidUtf8 {
+        /// byte offset of the first invalid byte in the name
+        offset: usize,
+        /// number of invalid bytes
+        len: usize,
+    },
+
+    /// the name starts with a whitespace
+    LeadingWhitespace,
+
+    /// the name ends with a whitespace
+    TrailingWhitespace,
+
+    /// the name ends with a dot, removed by Windows
+    TrailingDot,
+}
+
+impl std::fmt::Display for NameIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameIssue::ControlChar { offset, one_char } => {
+                write!(f, "control char {:?} at byte {}", one_char, offset)
+            }
+            NameIssue::InvisibleChar { offset, one_char } => write!(
+                f,
+                "invisible char U+{:04X} at byte {}",
+                *one_char as u32, offset
+            ),
+            NameIssue::InvalidUtf8 { offset, len } => {
+                write!(f, "{} invalid UTF-8 bytes at byte {}", len, offset)
+            }
+            NameIssue::LeadingWhitespace => write!(f, "leading whitespace"),
+            NameIssue::TrailingWhitespace => write!(f, "trailing whitespace"),
+            NameIssue::TrailingDot => write!(f, "trailing dot"),
+        }
+    }
+}
+
+/// Check if a char has no glyph, it cannot be seen in a name
+fn is_invisible_char(one_char: char) -> bool {
+    matches!(
+        one_char,
+        '\u{00AD}'
+            | '\u{180E}'
+            | '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{FEFF}'
+    )
+}
+
+/// Find the problems of a name, in the order of the name, without renaming anything
+///
+/// The name is audited as is, the options of the cleaning are not used.
+/// A name without problem can still be changed by the cleaning (accents, symbols...)
+pub fn audit(name: &OsStr) -> Vec<NameIssue> {
+    let mut issues = Vec::new();
+    let lossy = name.to_string_lossy();
+    if lossy.starts_with(char::is_whitespace) {
+        issues.push(NameIssue::LeadingWhitespace);
+    }
+    let mut offset = 0;
+    for chunk in name.as_encoded_bytes().utf8_chunks() {
+        for (char_offset, one_char) in chunk.valid().char_indices() {
+            if one_char.is_control() {
+                issues.push(NameIssue::ControlChar {
+                    offset: offset + char_offset,
+                    one_char,
+                });
+            } else if is_invisible_char(one_char) {
+                issues.push(NameIssue::InvisibleChar {
+                    offset: offset + char_offset,
+                    one_char,
+                });
+            }
+        }
+        offset += chunk.valid().len();
+        let invalid_len = chunk.invalid().len();
+        if invalid_len > 0 {
+            // a run of invalid bytes is split in several chunks
+            match issues.last_mut() {
+                Some(NameIssue::InvalidUtf8 {
+                    offset: run_offset,
+                    len,
+                }) if *run_offset + *len == offset => *len += invalid_len,
+                _ => issues.push(NameIssue::InvalidUtf8 {
+                    offset,
+                    len: invalid_len,
+                }),
+            }
+            offset += invalid_len;
+        }
+    }
+    if lossy.ends_with(char::is_whitespace) {
+        issues.push(NameIssue::TrailingWhitespace);
+    } else if lossy.ends_with('.') && lossy != "." && lossy != ".." {
+        issues.push(NameIssue::TrailingDot);
+    }
+    issues
+}
+
+/// Number of changed names per category of transformation
+///
+/// A name is counted in each category it needed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CategoryTally {
+    /// names with accented or non-latin letters
+    pub accents: usize,
+    /// names with symbols
+    pub symbols: usize,
+    /// names with whitespaces
+    pub whitespace: usize,
+    /// names with control characters
+    pub control: usize,
+}
+
+impl CategoryTally {
+    /// Count the categories of the changed (or to be changed) names of the results
+    pub fn from_results(results: &[PathChange]) -> CategoryTally {
+        let mut tally = CategoryTally::default();
+        for one_change in results {
+            tally.add(one_change);
+        }
+        tally
+    }
+
+    /// Count the categories of one more result, if its name is changed (or to be changed)
+    pub fn add(&mut self, one_change: &PathChange) {
+        let path = match one_change {
+            PathChange::Changed { path, .. }
+            | PathChange::ErrorRename { path, .. }
+            | PathChange::WouldChange { path, .. }
+            | PathChange::WouldFail { path, .. }
+            | PathChange::ErrorBackup { path, .. } => path,
+            PathChange::Unchanged { .. }
+            | PathChange::Trashed { .. }
+            | PathChange::Symlink { .. }
+            | PathChange::Skipped { .. }
+            | PathChange::Error { .. } => return,
+        };
+        let Some(file_name) = path.file_name() else {
+            return;
+        };
+        for category in name_categories(file_name) {
+            match category {
+                Category::Accents => self.accents += 1,
+                Category::Symbols => self.symbols += 1,
+                Category::Whitespace => self.whitespace += 1,
+                Category::Control => self.control += 1,
+            }
+        }
+    }
+}
+
+/// Encoding of a name, to choose how to clean an imported tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameEncoding {
+    /// only ASCII bytes
+    Ascii,
+    /// valid UTF-8 with non-ASCII chars
+    Utf8,
+    /// invalid UTF-8, likely a legacy encoding (Latin-1, CP1252, Shift-JIS...)
+    InvalidUtf8,
+}
+
+impl NameEncoding {
+    /// Classify the bytes of a name
+    pub fn of(name: &OsStr) -> NameEncoding {
+        let bytes = name.as_encoded_bytes();
+        if bytes.is_ascii() {
+            NameEncoding::Ascii
+        } else if std::str::from_utf8(bytes).is_ok() {
+            NameEncoding::Utf8
+        } else {
+            NameEncoding::InvalidUtf8
+        }
+    }
+
+    /// Name of the encoding in the report
+    pub fn name(&self) -> &'static str {
+        match self {
+            NameEncoding::Ascii => "ascii",
+            NameEncoding::Utf8 => "utf-8",
+            NameEncoding::InvalidUtf8 => "invalid utf-8",
+        }
+    }
+}
+
+/// Number of original names per [`NameEncoding`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncodingTally {
+    /// names with only ASCII bytes
+    pub ascii: usize,
+    /// names in valid UTF-8 with non-ASCII chars
+    pub utf8: usize,
+    /// names in invalid UTF-8
+    pub invalid_utf8: usize,
+}
+
+impl EncodingTally {
+    /// Count the encodings of the original names of the results
+    pub fn from_results(results: &[PathChange]) -> EncodingTally {
+        let mut tally = EncodingTally::default();
+        for one_change in results {
+            if matches!(
+                one_change,
+                PathChange::Trashed { .. } | PathChange::Symlink { .. } | PathChange::Error { .. }
+            ) {
+                continue;
+            }
+            let Some(file_name) = one_change.path().file_name() else {
+                continue;
+            };
+            match NameEncoding::of(file_name) {
+                NameEncoding::Ascii => tally.ascii += 1,
+                NameEncoding::Utf8 => tally.utf8 += 1,
+                NameEncoding::InvalidUtf8 => tally.invalid_utf8 += 1,
+            }
+        }
+        tally
+    }
+}
+
+/// Counts of the results of a run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Summary {
+    /// paths checked
+    pub checked: usize,
+    /// paths renamed
+    pub changed: usize,
+    /// paths already clean, or whose rename was skipped
+    pub unchanged: usize,
+    /// paths with an error, including the renames of the dry-run known to fail
+    pub errors: usize,
+    /// paths that would be renamed without the dry-run
+    pub would_change: usize,
+    /// renames of the dry-run known to fail, they are also counted in `errors`
+    pub would_fail: usize,
+    /// existing targets moved to the trash, they are not counted as checked
+    pub trashed: usize,
+    /// categories of transformation of the changed (or to be changed) names
+    pub categories: CategoryTally,
+}
+
+impl Summary {
+    /// Count the results of a run
+    pub fn from_results(results: &[PathChange]) -> Summary {
+        let mut summary = Summary::default();
+        for one_change in results {
+            summary.add(one_change);
+        }
+        summary
+    }
+
+    /// Count one more result
+    pub fn add(&mut self, one_change: &PathChange) {
+        self.categories.add(one_change);
+        self.checked += 1;
+        match one_change {
+            PathChange::Trashed { .. } => {
+                self.checked -= 1;
+                self.trashed += 1;
+            }
+            // the name of the link has its own result
+            PathChange::Symlink { .. } => self.checked -= 1,
+            PathChange::Changed { .. } => self.changed += 1,
+            PathChange::Unchanged { .. } | PathChange::Skipped { .. } => self.unchanged += 1,
+            PathChange::WouldChange { .. } => self.would_change += 1,
+            PathChange::WouldFail { .. } => {
+                self.would_fail += 1;
+                self.errors += 1;
+            }
+            PathChange::ErrorRename { .. }
+            | PathChange::Error { .. }
+            | PathChange::ErrorBackup { .. } => self.errors += 1,
+        }
+    }
+
+    /// Add the counts of another summary
+    pub fn merge(&mut self, other: &Summary) {
+        self.checked += other.checked;
+        self.changed += other.changed;
+        self.unchanged += other.unchanged;
+        self.errors += other.errors;
+        self.would_change += other.would_change;
+        self.would_fail += other.would_fail;
+        self.trashed += other.trashed;
+        self.categories.accents += other.categories.accents;
+        self.categories.symbols += other.categories.symbols;
+        self.categories.whitespace += other.categories.whitespace;
+        self.categories.control += other.categories.control;
+    }
+}
+
+impl fmt::Display for Summary {
+    /// Single line tally, like `3 files checked: 1 changed, 2 unchanged, 0 errors`
+    ///
+    /// The renames of the dry-run and the trashed paths are only written if there are some
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.checked {
+            1 => write!(f, "1 file checked: ")?,
+            checked => write!(f, "{} files checked: ", checked)?,
+        }
+        write!(f, "{} changed, ", self.changed)?;
+        if self.would_change > 0 {
+            write!(f, "{} would change, ", self.would_change)?;
+        }
+        write!(f, "{} unchanged, ", self.unchanged)?;
+        match self.errors {
+            1 => write!(f, "1 error")?,
+            errors => write!(f, "{} errors", errors)?,
+        }
+        if self.trashed > 0 {
+            write!(f, ", {} trashed", self.trashed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Push a char to a string if a condition is true
+#[inline(always)]
+fn push_underscore_if(stri: &mut String, to_push: char, condition: bool) {
+    if condition {
+        stri.push(to_push);
+    }
+}
+
+/// Transliteration of the non-ASCII letters, each group of chars is replaced by the same string
+///
+/// See [`transliterate`], a change of this table bumps [`TRANSLITERATION_VERSION`]
+pub const TRANSLITERATIONS: &[(&str, &str)] = &[
+    ("AⒶＡÀÁÂẦẤẪẨÃĀĂẰẮẴẲȦǠÄǞẢÅǺǍȀȂẠẬẶḀĄȺⱯ", "A"),
+    ("Ꜳ", "AA"),
+    ("ÆǼǢ", "A"),
+    ("Ꜵ", "AO"),
+    ("Ꜷ", "AU"),
+    ("ꜸꜺ", "AV"),
+    ("Ꜽ", "AY"),
+    ("BⒷＢḂḄḆɃƂƁ", "B"),
+    ("CⒸＣĆĈĊČÇḈƇȻꜾ", "C"),
+    ("DⒹＤḊĎḌḐḒḎĐƋƊƉꝹ", "D"),
+    ("ǱǄ", "DZ"),
+    ("ǲǅ", "Dz"),
+    ("EⒺＥÈÉÊỀẾỄỂẼĒḔḖĔĖËẺĚȄȆẸỆȨḜĘḘḚƐƎ", "E"),
+    ("FⒻＦḞƑꝻ", "F"),
+    ("GⒼＧǴĜḠĞĠǦĢǤƓꞠꝽꝾ", "G"),
+    ("HⒽＨĤḢḦȞḤḨḪĦⱧⱵꞍ", "H"),
+    ("IⒾＩÌÍÎĨĪĬİÏḮỈǏȈȊỊĮḬƗ", "I"),
+    ("JⒿＪĴɈ", "J"),
+    ("KⓀＫḰǨḲĶḴƘⱩꝀꝂꝄꞢ", "K"),
+    ("LⓁＬĿĹĽḶḸĻḼḺŁȽⱢⱠꝈꝆꞀ", "L"),
+    ("Ǉ", "LJ"),
+    ("ǈ", "Lj"),
+    ("MⓂＭḾṀṂⱮƜ", "M"),
+    ("NⓃＮǸŃÑṄŇṆŅṊṈȠƝꞐꞤ", "N"),
+    ("Ǌ", "NJ"),
+    ("ǋ", "Nj"),
+    ("OⓄＯÒÓÔỒỐỖỔÕṌȬṎŌṐṒŎȮȰÖȪỎŐǑȌȎƠỜỚỠỞỢỌỘǪǬØǾƆƟꝊꝌ", "O"),
+    ("Ƣ", "OI"),
+    ("Ꝏ", "OO"),
+    ("Ȣ", "OU"),
+    ("\u{008C}Œ", "OE"),
+    ("\u{009C}œ", "oe"),
+    ("PⓅＰṔṖƤⱣꝐꝒꝔ", "P"),
+    ("QⓆＱꝖꝘɊ", "Q"),
+    ("RⓇＲŔṘŘȐȒṚṜŖṞɌⱤꝚꞦꞂ", "R"),
+    ("SⓈＳẞŚṤŜṠŠṦṢṨȘŞⱾꞨꞄ", "S"),
+    ("TⓉＴṪŤṬȚŢṰṮŦƬƮȾꞆ", "T"),
+    ("Ꜩ", "TZ"),
+    ("UⓊＵÙÚÛŨṸŪṺŬÜǛǗǕǙỦŮŰǓȔȖƯỪỨỮỬỰỤṲŲṶṴɄ", "U"),
+    ("VⓋＶṼṾƲꝞɅ", "V"),
+    ("Ꝡ", "VY"),
+    ("WⓌＷẀẂŴẆẄẈⱲ", "W"),
+    ("XⓍＸẊẌ", "X"),
+    ("YⓎＹỲÝŶỸȲẎŸỶỴƳɎỾ", "Y"),
+    ("ZⓏＺŹẐŻŽẒẔƵȤⱿⱫꝢ", "Z"),
+    ("aⓐａẚàáâầấẫẩãāăằắẵẳȧǡäǟảåǻǎȁȃạậặḁąⱥɐ", "a"),
+    ("ꜳ", "aa"),
+    ("æǽǣ", "a"),
+    ("ꜵ", "ao"),
+    ("ꜷ", "au"),
+    ("ꜹꜻ", "av"),
+    ("ꜽ", "ay"),
+    ("bⓑｂḃḅḇƀƃɓþ", "b"),
+    ("cⓒｃćĉċčçḉƈȼꜿↄ", "c"),
+    ("dⓓｄḋďḍḑḓḏđƌɖɗꝺ", "d"),
+    ("ǳǆ", "dz"),
+    ("eⓔｅèéêềếễểẽēḕḗĕėëẻěȅȇẹệȩḝęḙḛɇɛǝ", "e"),
+    ("fⓕｆḟƒꝼ", "f"),
+    ("gⓖｇǵĝḡğġǧģǥɠꞡᵹꝿ", "g"),
+    ("hⓗｈĥḣḧȟḥḩḫẖħⱨⱶɥ", "h"),
+    ("ƕ", "hv"),
+    ("iⓘｉìíîĩīĭïḯỉǐȉȋịįḭɨı", "i"),
+    ("jⓙｊĵǰɉ", "j"),
+    ("kⓚｋḱǩḳķḵƙⱪꝁꝃꝅꞣ", "k"),
+    ("lⓛｌŀĺľḷḹļḽḻſłƚɫⱡꝉꞁꝇ", "l"),
+    ("ǉ", "lj"),
+    ("mⓜｍḿṁṃɱɯ", "m"),
+    ("nⓝｎǹńñṅňṇņṋṉƞɲŉꞑꞥ", "n"),
+    ("ǌ", "nj"),
+    ("oⓞｏòóôồốỗổõṍȭṏōṑṓŏȯȱöȫỏőǒȍȏơờớỡởợọộǫǭøǿɔꝋꝍɵ", "o"),
+    ("ƣ", "oi"),
+    ("ȣ", "ou"),
+    ("ꝏ", "oo"),
+    ("pⓟｐṕṗƥᵽꝑꝓꝕ", "p"),
+    ("qⓠｑɋꝗꝙ", "q"),
+    ("rⓡｒŕṙřȑȓṛṝŗṟɍɽꝛꞧꞃ", "r"),
+    ("sⓢｓßśṥŝṡšṧṣṩșşȿꞩꞅẛ", "s"),
+    ("tⓣｔṫẗťṭțţṱṯŧƭʈⱦꞇ", "t"),
+    ("ꜩ", "tz"),
+    ("uⓤｕùúûũṹūṻŭüǜǘǖǚủůűǔȕȗưừứữửựụṳųṷṵʉ", "u"),
+    ("vⓥｖṽṿʋꝟʌ", "v"),
+    ("ꝡ", "vy"),
+    ("wⓦｗẁẃŵẇẅẘẉⱳ", "w"),
+    ("xⓧｘẋẍ", "x"),
+    ("yⓨｙỳýŷỹȳẏÿỷẙỵƴɏỿ", "y"),
+    ("zⓩｚźẑżžẓẕƶȥɀⱬꝣ", "z"),
+    ("–", "-"),
+];
+
+/// Lookup table of [`TRANSLITERATIONS`], built on the first use
+fn transliteration_table() -> &'static HashMap<char, &'static str> {
+    static TABLE: OnceLock<HashMap<char, &'static str>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        TRANSLITERATIONS
+            .iter()
+            .flat_map(|(chars, replacement)| {
+                chars.chars().map(move |one_char| (one_char, *replacement))
+            })
+            .collect()
+    })
+}
+
+/// Get the transliteration of a char in [`TRANSLITERATIONS`], `None` if the char is not in it
+pub fn transliterate(one_char: char) -> Option<&'static str> {
+    transliteration_table().get(&one_char).copied()
+}
+
+/// Check if a vector of bytes is similar to a char
+///
+/// The char is replaced by its transliteration, the combining marks are removed,
+/// and the other chars are replaced by a separator (then `true` is returned)
+#[inline(always)]
+pub fn check_similar(curr_char: Option<char>, name_acc: &mut String, last_was_under: bool) -> bool {
+    let Some(one_char) = curr_char else {
+        return false;
+    };
+    if one_char.is_ascii_alphabetic() {
+        name_acc.push(one_char);
+        return false;
+    }
+    if let Some(replacement) = transliterate(one_char) {
+        name_acc.push_str(replacement);
+        return false;
+    }
+    match one_char {
+        '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' => false,
+        _ => {
+            if !last_was_under {
+                name_acc.push('_');
+            }
+            true
+        }
+    }
+}
+
+/// Convert four bytes to a u32
+#[inline(always)]
+pub fn convert_four_to_u32(
+    first_byte: u8,
+    second_byte: u8,
+    third_byte: u8,
+    fourth_byte: u8,
+) -> u32 {
+    ((first_byte as u32 & 0b0000_0111) << 18)
+        | ((second_byte as u32 & 0b0011_1111) << 12)
+        | ((third_byte as u32 & 0b0011_1111) << 6)
+        | (fourth_byte as u32 & 0b0011_1111)
+}
+
+/// Convert three bytes to a u32
+#[inline(always)]
+pub fn convert_three_to_u32(first_byte: u8, second_byte: u8, third_byte: u8) -> u32 {
+    ((first_byte as u32 & 0b0001_1111) << 12)
+        | ((second_byte as u32 & 0b0011_1111) << 6)
+        | (third_byte as u32 & 0b0011_1111)
+}
+
+/// Convert two bytes to a u32
+#[inline(always)]
+pub fn convert_two_to_u32(first_byte: u8, second_byte: u8) -> u32 {
+    ((first_byte as u32 & 0b0001_1111) << 6) | (second_byte as u32 & 0b0011_1111)
+}
+
+/// Remove an immediately repeated final extension, `file.txt.txt` becomes `file.txt`
+///
+/// Different extensions like `archive.tar.gz` are kept
+fn remove_repeated_extension(name: &mut String) {
+    while let Some(last_dot) = name.rfind('.') {
+        let extension = &name[last_dot + 1..];
+        let previous_dot = match name[..last_dot].rfind('.') {
+            Some(previous_dot) if previous_dot > 0 && !extension.is_empty() => previous_dot,
+            _ => return,
+        };
+        if !name[previous_dot + 1..last_dot].eq_ignore_ascii_case(extension) {
+            return;
+        }
+        name.replace_range(previous_dot..last_dot, "");
+    }
+}
+
+/// Check if a name is safe as a shell argument without quoting
+///
+/// A safe name only has ASCII letters, digits, `.`, `_` and `-`, and does not start with `-`
+/// (it would look like an option). It is not empty, `.` or `..`.
+pub fn is_shell_safe(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.starts_with('-')
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'.' | b'_' | b'-'))
+}
+
+/// Quote a path as a single argument of a POSIX shell
+///
+/// The path is single-quoted, a `'` becomes `'\''` and the newlines are kept as they are.
+/// The bytes not valid in UTF-8 are written with the octal escapes of `printf`
+pub fn sh_quote(path: &Path) -> String {
+    let mut quoted = String::new();
+    for chunk in path.as_os_str().as_encoded_bytes().utf8_chunks() {
+        if !chunk.valid().is_empty() {
+            quoted.push('\'');
+            quoted.push_str(&chunk.valid().replace('\'', "'\\''"));
+            quoted.push('\'');
+        }
+        if !chunk.invalid().is_empty() {
+            quoted.push_str("\"$(printf '");
+            for byte in chunk.invalid() {
+                quoted.push_str(&format!("\\{:03o}", byte));
+            }
+            quoted.push_str("')\"");
+        }
+    }
+    if quoted.is_empty() {
+        quoted.push_str("''");
+    }
+    quoted
+}
+
+/// Quote a text as a single-quoted PowerShell string
+///
+/// PowerShell also ends a string at the typographic single quotes `‘’‚‛`, so they are doubled like `'`
+pub fn powershell_quote(text: &str) -> String {
+    let mut quoted = String::from("'");
+    for one_char in text.chars() {
+        if matches!(
+            one_char,
+            '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}'
+        ) {
+            quoted.push(one_char);
+        }
+        quoted.push(one_char);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Write a script doing the renames of a dry-run, to review it before running it
+///
+/// Only the [`PathChange::WouldChange`] entries are written. The entries of a directory come
+/// before it, so they are renamed while their paths are the ones of the dry-run.
+/// The script stops at the first failed rename
+/// # Errors
+/// Return an error if the writer fails
+pub fn write_rename_script<W: Write>(
+    changes: &[PathChange],
+    dialect: ScriptDialect,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let mut renames: Vec<(&Path, &Path)> = changes
+        .iter()
+        .filter_map(|one_change| match one_change {
+            PathChange::WouldChange { path, modified } => {
+                Some((path.as_path(), modified.as_path()))
+            }
+            _ => None,
+        })
+        .collect();
+    renames.sort_by_key(|(path, _)| std::cmp::Reverse(path.components().count()));
+    match dialect {
+        ScriptDialect::Sh => writeln!(writer, "#!/bin/sh\nset -e")?,
+        ScriptDialect::PowerShell => writeln!(writer, "$ErrorActionPreference = 'Stop'")?,
+    }
+    for (path, modified) in renames {
+        match dialect {
+            ScriptDialect::Sh => {
+                writeln!(writer, "mv -- {} {}", sh_quote(path), sh_quote(modified))?
+            }
+            ScriptDialect::PowerShell => writeln!(
+                writer,
+                "Rename-Item -LiteralPath {} -NewName {}",
+                powershell_quote(&path.to_string_lossy()),
+                powershell_quote(
+                    &modified
+                        .file_name()
+                        .unwrap_or(modified.as_os_str())
+                        .to_string_lossy()
+                )
+            )?,
+        }
+    }
+    Ok(())
+}
+
+/// Make a cleaned name safe as a shell argument, see [`is_shell_safe`]
+///
+/// The other characters (kept by other options) are replaced by `_` and a leading `-` is prefixed with `_`
+fn make_shell_safe(name: &mut String) {
+    let mut safe: String = name
+        .chars()
+        .map(|one_char| {
+            match one_char.is_ascii_alphanumeric() || matches!(one_char, '.' | '_' | '-') {
+                true => one_char,
+                false => '_',
+            }
+        })
+        .collect();
+    if safe.is_empty() || safe.starts_with('-') || safe == "." || safe == ".." {
+        safe.insert(0, '_');
+    }
+    *name = safe;
+}
+
+/// Change the case of the letters right after a separator, see [`NotoxArgs::post_separator_case`]
+fn apply_post_separator_case(name: &mut String, case_mode: CaseMode, options: &NotoxArgs) {
+    let is_separator = |one_char: char| {
+        one_char == '_'
+            || Some(one_char) == options.space_replacement
+            || (one_char == ' ' && options.keep_spaces)
+    };
+    let mut after_separator = false;
+    let mut changed = String::with_capacity(name.len());
+    for one_char in name.chars() {
+        match (after_separator, case_mode) {
+            (true, CaseMode::Upper) => changed.extend(one_char.to_uppercase()),
+            (true, CaseMode::Lower) => changed.extend(one_char.to_lowercase()),
+            (false, _) => changed.push(one_char),
+        }
+        after_separator = is_separator(one_char);
+    }
+    *name = changed;
+}
+
+/// Truncate the stem of a cleaned name to fit in `max_bytes`, keeping the extension
+///
+/// The cut is on a char boundary and the trailing separators of the truncated stem are removed.
+/// If the extension alone does not fit, the whole name is truncated and its trailing dots are also removed.
+/// A `max_bytes` of 0 is no limit, an empty name cannot be renamed to.
+fn truncate_name(name: &mut String, max_bytes: usize) {
+    if max_bytes == 0 || name.len() <= max_bytes {
+        return;
+    }
+    let extension_start = match name.rfind('.') {
+        Some(last_dot) if last_dot > 0 && name.len() - last_dot < max_bytes => last_dot,
+        _ => name.len(),
+    };
+    let mut stem_end = max_bytes - (name.len() - extension_start);
+    while !name.is_char_boundary(stem_end) {
+        stem_end -= 1;
+    }
+    let stem = match extension_start == name.len() {
+        true => name[..stem_end].trim_end_matches(['_', '.']),
+        false => name[..stem_end].trim_end_matches('_'),
+    };
+    // a stem made only of separators is kept to not lose the name
+    let stem_end = match stem.is_empty() {
+        true => stem_end,
+        false => stem.len(),
+    };
+    name.replace_range(stem_end..extension_start, "");
+}
+
+/// Remove the separators at both ends of each part of a name between dots
+///
+/// `__file_.txt_` becomes `file.txt` and the leading dot of a dotfile is kept, `._hidden_` becomes `.hidden`.
+/// A part with only separators is kept as a single separator, so `__.txt` becomes `_.txt` and not a dotfile.
+pub fn trim_separators(name: &str) -> String {
+    let (leading_dot, rest) = match name.strip_prefix('.') {
+        Some(rest) => (".", rest),
+        None => ("", name),
+    };
+    let parts: Vec<&str> = rest
+        .split('.')
+        .map(|part| match part.trim_matches('_') {
+            "" if !part.is_empty() => "_",
+            trimmed => trimmed,
+        })
+        .collect();
+    format!("{}{}", leading_dot, parts.join("."))
+}
+
+/// Replace the stem of a cleaned name by the placeholder if it has no letter or digit left
+///
+/// The extension (after the last dot) is kept, a dotfile has no extension.
+/// A name that was not changed by the cleaning is kept as is.
+fn replace_empty_stem(name: &mut String, original: &OsStr, placeholder: &str) {
+    if name.as_bytes() == original.as_encoded_bytes() {
+        return;
+    }
+    let is_dotfile = original.as_encoded_bytes().starts_with(b".");
+    let stem_len = match name.rfind('.') {
+        Some(last_dot) if last_dot > 0 || !is_dotfile => last_dot,
+        _ => name.len(),
+    };
+    if name[..stem_len]
+        .bytes()
+        .any(|byte| byte.is_ascii_alphanumeric())
+    {
+        return;
+    }
+    name.replace_range(..stem_len, placeholder);
+}
+
+/// Check if the stem of a name (before the first dot) is a Windows reserved name
+#[inline(always)]
+fn windows_reserved_stem_len(name: &str) -> Option<usize> {
+    let stem_len = name.find('.').unwrap_or(name.len());
+    WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(&name[..stem_len]))
+        .then_some(stem_len)
+}
+
+/// Apply the rules of [`Profile::SharePoint`] to a cleaned name
+fn apply_sharepoint_rules(name: &mut String) {
+    let replaced: String = name
+        .chars()
+        .map(
+            |one_char| match SHAREPOINT_FORBIDDEN_CHARS.contains(&one_char) {
+                true => '_',
+                false => one_char,
+            },
+        )
+        .collect();
+    let mut replaced = replaced
+        .trim_start_matches(' ')
+        .trim_end_matches([' ', '.'])
+        .to_string();
+    while replaced.contains("_vti_") {
+        replaced = replaced.replace("_vti_", "vti_");
+    }
+    if replaced.eq_ignore_ascii_case(".lock") || replaced.eq_ignore_ascii_case("desktop.ini") {
+        replaced.insert(0, '_');
+    }
+    if let Some(stem_len) = windows_reserved_stem_len(&replaced) {
+        replaced.insert(stem_len, '_');
+    }
+    if replaced.is_empty() {
+        replaced.push('_');
+    }
+    *name = replaced;
+}
+
+/// Apply the rules of [`Profile::WindowsSafe`] to a cleaned name
+fn apply_windows_rules(name: &mut String) {
+    let replaced: String = name
+        .chars()
+        .map(|one_char| {
+            match WINDOWS_FORBIDDEN_CHARS.contains(&one_char) || one_char.is_control() {
+                true => '_',
+                false => one_char,
+            }
+        })
+        .collect();
+    let mut replaced = replaced.trim_end_matches([' ', '.']).to_string();
+    if let Some(stem_len) = windows_reserved_stem_len(&replaced) {
+        replaced.insert(stem_len, '_');
+    }
+    if replaced.is_empty() {
+        replaced.push('_');
+    }
+    *name = replaced;
+}
+
+/// Push a separator replacing an ASCII byte, or the byte itself if its category is not cleaned
+///
+/// Returns the new length of the run of separators
+#[inline(always)]
+fn push_ascii_separator(
+    byte: u8,
+    new_name: &mut String,
+    separator_run: usize,
+    options: &NotoxArgs,
+) -> usize {
+    if let Some(category) = char_category(byte as char) {
+        if !options.categories.contains(&category) {
+            new_name.push(byte as char);
+            return 0;
+        }
+    }
+    push_underscore_if(
+        new_name,
+        run_separator(new_name, separator_run, options),
+        separator_run < options.max_consecutive_separators,
+    );
+    separator_run.saturating_add(1)
+}
+
+/// Separator of the current run: the `space_replacement` once the run has a whitespace, `_` otherwise
+#[inline(always)]
+fn run_separator(new_name: &str, separator_run: usize, options: &NotoxArgs) -> char {
+    match options.space_replacement {
+        Some(replacement) if separator_run > 0 && new_name.ends_with(replacement) => replacement,
+        _ => '_',
+    }
+}
+
+/// Push the replacement of a whitespace, it is a separator of the run like `_`
+///
+/// The `_` already pushed by the run become the replacement, `a (b` and `a( b` both become `a-b` with `-`.
+/// Returns the new length of the run of separators
+#[inline(always)]
+fn push_space_replacement(
+    new_name: &mut String,
+    separator_run: usize,
+    options: &NotoxArgs,
+) -> usize {
+    if let Some(replacement) = options.space_replacement {
+        let pushed = separator_run.min(options.max_consecutive_separators);
+        let underscores = new_name
+            .chars()
+            .rev()
+            .take(pushed)
+            .take_while(|one_char| *one_char == '_')
+            .count();
+        new_name.truncate(new_name.len() - underscores);
+        new_name.extend(std::iter::repeat_n(replacement, underscores));
+        push_underscore_if(
+            new_name,
+            replacement,
+            separator_run < options.max_consecutive_separators,
+        );
+    }
+    separator_run.saturating_add(1)
+}
+
+/// Push the cleaned version of a non-ASCII char, or the char itself if its category is not cleaned
+///
+/// Returns the new length of the run of separators
+#[inline(always)]
+fn push_cleaned_char(
+    curr_char: Option<char>,
+    new_name: &mut String,
+    separator_run: usize,
+    options: &NotoxArgs,
+) -> usize {
+    if let Some(one_char) = curr_char {
+        if !options.cleans_all_categories() {
+            if let Some(category) = char_category(one_char) {
+                if !options.categories.contains(&category) {
+                    new_name.push(one_char);
+                    return 0;
+                }
+            }
+        }
+    }
+    if let Some(replacement) = curr_char.and_then(|one_char| options.overrides.get(&one_char)) {
+        new_name.push_str(replacement);
+        return 0;
+    }
+    if options.mode == CleanMode::UnsafeOnly {
+        if let Some(one_char) = curr_char.filter(|one_char| is_kept_unicode(*one_char)) {
+            new_name.push(one_char);
+            return 0;
+        }
+    }
+    if options.space_replacement.is_some() && curr_char.is_some_and(char::is_whitespace) {
+        return push_space_replacement(new_name, separator_run, options);
+    }
+    if options.german_sharp_s {
+        match curr_char {
+            Some('ß') => {
+                new_name.push_str("ss");
+                return 0;
+            }
+            Some('ẞ') => {
+                new_name.push_str("SS");
+                return 0;
+            }
+            _ => {}
+        }
+    }
+    #[cfg(feature = "korean")]
+    if options.romanize_hangul {
+        if let Some(romanized) = curr_char.and_then(korean::romanize_syllable) {
+            new_name.push_str(&romanized);
+            return 0;
+        }
+    }
+    let separator = run_separator(new_name, separator_run, options);
+    let pushed_start = new_name.len();
+    let is_separator = check_similar(
+        curr_char,
+        new_name,
+        separator_run >= options.max_consecutive_separators,
+    );
+    if is_separator && separator != '_' && new_name.len() > pushed_start {
+        new_name.truncate(pushed_start);
+        new_name.push(separator);
+    }
+    match is_separator {
+        true => separator_run.saturating_add(1),
+        false => 0,
+    }
+}
+
+/// Length of the UTF-8 sequence starting with this byte, `None` if no sequence can start with it
+#[inline(always)]
+fn utf8_sequence_len(first_byte: u8) -> Option<usize> {
+    match first_byte {
+        0xC2..=0xDF => Some(2),
+        0xE0..=0xEF => Some(3),
+        0xF0..=0xF4 => Some(4),
+        _ => None,
+    }
+}
+
+/// Push the separators replacing a run of invalid UTF-8 bytes, according to the [`NonUtf8Policy`]
+///
+/// Returns the new length of the run of separators
+#[inline(always)]
+fn push_invalid_bytes(
+    invalid_bytes: usize,
+    new_name: &mut String,
+    mut separator_run: usize,
+    options: &NotoxArgs,
+) -> usize {
+    let separators = match options.non_utf8_policy {
+        NonUtf8Policy::Single => invalid_bytes.min(1),
+        NonUtf8Policy::PerByte => invalid_bytes,
+    };
+    for _ in 0..separators {
+        push_underscore_if(
+            new_name,
+            run_separator(new_name, separator_run, options),
+            separator_run < options.max_consecutive_separators,
+        );
+        separator_run = separator_run.saturating_add(1);
+    }
+    separator_run
+}
+
+/// Redact the substrings of a name matching the redactions, `None` if nothing is redacted
+#[cfg(feature = "regex")]
+fn redact_name(name: &OsStr, redactions: &[Redaction]) -> Option<OsString> {
+    let mut redacted = std::borrow::Cow::Borrowed(name.to_str()?);
+    for redaction in redactions {
+        if let std::borrow::Cow::Owned(replaced) = redaction
+            .pattern
+            .replace_all(&redacted, regex::NoExpand(&redaction.replacement))
+        {
+            redacted = std::borrow::Cow::Owned(replaced);
+        }
+    }
+    match redacted {
+        std::borrow::Cow::Owned(redacted) => Some(OsString::from(redacted)),
+        std::borrow::Cow::Borrowed(_) => None,
+    }
+}
+
+/// Transcode a name from the source encoding to UTF-8, `None` if it is already valid in UTF-8
+#[cfg(feature = "encoding")]
+fn transcode_name(name: &OsStr, encoding: &'static encoding_rs::Encoding) -> Option<OsString> {
+    let bytes = name.as_encoded_bytes();
+    if std::str::from_utf8(bytes).is_ok() {
+        return None;
+    }
+    let (decoded, _) = encoding.decode_without_bom_handling(bytes);
+    Some(OsString::from(decoded.into_owned()))
+}
+
+/// Normalize a name to NFC, `None` if it is not valid in UTF-8 or already normalized
+#[cfg(feature = "normalize")]
+fn normalize_name(name: &OsStr) -> Option<OsString> {
+    use unicode_normalization::UnicodeNormalization;
+    let name = name.to_str()?;
+    if unicode_normalization::is_nfc(name) {
+        return None;
+    }
+    Some(OsString::from(name.nfc().collect::<String>()))
+}
+
+/// Clean a file name without touching the filesystem
+///
+/// It gives the same name as the renames of [`Notox::run`], the options about the files
+/// (dry-run, journal, time window...) are not used
+pub fn clean_file_name(name: &OsStr, options: &NotoxArgs) -> OsString {
+    clean_name(name, options)
+}
+
+/// Get the growth in bytes of a name once cleaned, negative if the name is shorter
+///
+/// Some transliterations are longer than the original char (`Œ` gives `OE`, `Ꜳ` gives `AA`),
+/// the path of a deep tree can then exceed the limits of the system. The filesystem is not read.
+pub fn name_length_delta(original: &OsStr, options: &NotoxArgs) -> isize {
+    clean_name(original, options).len() as isize - original.len() as isize
+}
+
+/// Length in bytes of an optional name, 0 without name
+fn name_byte_len(name: Option<&OsStr>) -> usize {
+    name.map_or(0, OsStr::len)
+}
+
+/// Clean a file name given as a string, see [`clean_file_name`]
+pub fn clean_str(name: &str, options: &NotoxArgs) -> String {
+    // the cleaned names are built as strings, they are always valid
+    clean_name(OsStr::new(name), options)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// A substitution done while cleaning a name, see [`clean_name_with_report`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    /// the original char, or `\u{FFFD}` for each byte not valid in UTF-8
+    pub original: String,
+    /// the pushed string, empty if the char is removed (combining mark, repeated separator)
+    pub replacement: String,
+}
+
+impl std::fmt::Display for Substitution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} -> {:?}", self.original, self.replacement)
+    }
+}
+
+/// Clean a file name and report the substitutions of its chars
+///
+/// The substitutions are in the order of the name, the changes of the whole name done after them
+/// (`lowercase`, `trim`, `max_length`...) are not reported
+pub fn clean_name_with_report(name: &OsStr, options: &NotoxArgs) -> (OsString, Vec<Substitution>) {
+    let mut report = Vec::new();
+    let cleaned = clean_name_recorded(name, options, Some(&mut report));
+    (cleaned, report)
+}
+
+/// Add a substitution to the report, if the pushed string is not the original bytes
+#[inline(always)]
+fn record_substitution(report: Option<&mut Vec<Substitution>>, original: &[u8], pushed: &str) {
+    if let Some(report) = report {
+        if original != pushed.as_bytes() {
+            report.push(Substitution {
+                original: String::from_utf8_lossy(original).into_owned(),
+                replacement: pushed.to_string(),
+            });
+        }
+    }
+}
+
+/// Clean a name
+#[inline(always)]
+fn clean_name(path: &OsStr, options: &NotoxArgs) -> OsString {
+    clean_name_recorded(path, options, None)
+}
+
+/// Clean a name, the substitutions of its chars are added to the report if given
+#[inline(always)]
+fn clean_name_recorded(
+    path: &OsStr,
+    options: &NotoxArgs,
+    mut report: Option<&mut Vec<Substitution>>,
+) -> OsString {
+    #[cfg(feature = "encoding")]
+    let transcoded = options
+        .source_encoding
+        .and_then(|encoding| transcode_name(path, encoding));
+    #[cfg(feature = "encoding")]
+    let path = transcoded.as_deref().unwrap_or(path);
+    #[cfg(feature = "normalize")]
+    let normalized = match options.normalize_nfc {
+        true => normalize_name(path),
+        false => None,
+    };
+    #[cfg(feature = "normalize")]
+    let path = normalized.as_deref().unwrap_or(path);
+    #[cfg(feature = "regex")]
+    let redacted = redact_name(path, &options.redactions);
+    #[cfg(feature = "regex")]
+    let path = redacted.as_deref().unwrap_or(path);
+    // for each byte of the path if it's not ascii, replace it with _
+    let mut new_name = String::new();
+    let mut vec_grapheme: [u8; 4] = [0; 4];
+    let mut separator_run: usize = 0;
+    let mut idx_grapheme = 0;
+    let mut invalid_bytes: usize = 0;
+    let name_bytes = path.as_encoded_bytes();
+    // start of the extension, the leading dot of a dotfile is not one
+    let extension_start = match name_bytes.iter().rposition(|byte| *byte == b'.') {
+        Some(last_dot) if last_dot > 0 => last_dot,
+        _ => name_bytes.len(),
+    };
+    for (byte_idx, byte) in name_bytes.iter().enumerate() {
+        if idx_grapheme > 0 && !(0x80..0xC0).contains(byte) {
+            // truncated sequence, the byte is not a continuation byte
+            invalid_bytes += idx_grapheme;
+            idx_grapheme = 0;
+        }
+        if idx_grapheme == 0 && *byte < 128 {
+            let pushed_start = new_name.len();
+            let invalid_start = byte_idx - invalid_bytes;
+            separator_run = push_invalid_bytes(
+                std::mem::take(&mut invalid_bytes),
+                &mut new_name,
+                separator_run,
+                options,
+            );
+            record_substitution(
+                report.as_deref_mut(),
+                &name_bytes[invalid_start..byte_idx],
+                &new_name[pushed_start..],
+            );
+            let pushed_start = new_name.len();
+            match byte {
+                _ if byte_idx > extension_start
+                    && options.ext_allowed_chars.contains(&(*byte as char)) =>
+                {
+                    new_name.push(*byte as char);
+                    separator_run = 0;
+                }
+                // the replacement of the whitespaces is collapsed with them, `a - b` becomes `a-b`
+                _ if options.space_replacement.is_some_and(|replacement| {
+                    byte.is_ascii_whitespace() || *byte as char == replacement
+                }) && char_category(*byte as char)
+                    .is_none_or(|category| options.categories.contains(&category)) =>
+                {
+                    separator_run = push_space_replacement(&mut new_name, separator_run, options);
+                }
+                _ if *byte != b'_' && options.allowed_extra.contains(&(*byte as char)) => {
+                    new_name.push(*byte as char);
+                    separator_run = 0;
+                }
+                b' ' if options.keep_spaces => {
+                    if !new_name.ends_with(' ') {
+                        new_name.push(' ');
+                    }
+                    separator_run = 0;
+                }
+                0..=44 | 47 | 58..=64 | 91..=96 | 123..=127 => {
+                    separator_run =
+                        push_ascii_separator(*byte, &mut new_name, separator_run, options);
+                }
+                // a dot of the stem, the leading dot of a dotfile is kept
+                46 if options.preserve_extension && byte_idx > 0 && byte_idx < extension_start => {
+                    separator_run =
+                        push_ascii_separator(*byte, &mut new_name, separator_run, options);
+                }
+                46 => {
+                    new_name.push('.');
+                    separator_run = 0;
+                }
+                _ => {
+                    new_name.push(*byte as char);
+                    separator_run = 0;
+                }
+            }
+            record_substitution(
+                report.as_deref_mut(),
+                &name_bytes[byte_idx..=byte_idx],
+                &new_name[pushed_start..],
+            );
+        } else if idx_grapheme == 0 && utf8_sequence_len(*byte).is_none() {
+            // continuation byte without a first byte, or byte never used in UTF-8
+            invalid_bytes += 1;
+        } else {
+            vec_grapheme[idx_grapheme] = *byte;
+            idx_grapheme += 1;
+            let first_byte = vec_grapheme[0];
+            if utf8_sequence_len(first_byte) != Some(idx_grapheme) {
+                continue;
+            }
+            let curr_char = std::char::from_u32(match idx_grapheme {
+                // four bytes grapheme
+                4 => convert_four_to_u32(
+                    vec_grapheme[0],
+                    vec_grapheme[1],
+                    vec_grapheme[2],
+                    vec_grapheme[3],
+                ),
+                // three bytes grapheme
+                3 => convert_three_to_u32(vec_grapheme[0], vec_grapheme[1], vec_grapheme[2]),
+                // two bytes grapheme
+                _ => convert_two_to_u32(vec_grapheme[0], vec_grapheme[1]),
+            });
+            match curr_char {
+                // an overlong encoding decodes to a char with a shorter sequence
+                Some(one_char) if one_char.len_utf8() == idx_grapheme => {
+                    let char_start = byte_idx + 1 - idx_grapheme;
+                    let invalid_start = char_start - invalid_bytes;
+                    let pushed_start = new_name.len();
+                    separator_run = push_invalid_bytes(
+                        std::mem::take(&mut invalid_bytes),
+                        &mut new_name,
+                        separator_run,
+                        options,
+                    );
+                    record_substitution(
+                        report.as_deref_mut(),
+                        &name_bytes[invalid_start..char_start],
+                        &new_name[pushed_start..],
+                    );
+                    let pushed_start = new_name.len();
+                    if byte_idx > extension_start && options.ext_allowed_chars.contains(&one_char) {
+                        new_name.push(one_char);
+                        separator_run = 0;
+                    } else {
+                        separator_run =
+                            push_cleaned_char(curr_char, &mut new_name, separator_run, options);
+                    }
+                    record_substitution(
+                        report.as_deref_mut(),
+                        &name_bytes[char_start..=byte_idx],
+                        &new_name[pushed_start..],
+                    );
+                }
+                // surrogates, overlong encodings and values above U+10FFFF
+                _ => invalid_bytes += idx_grapheme,
+            }
+            vec_grapheme = [0; 4];
+            idx_grapheme = 0;
+        }
+    }
+    let pushed_start = new_name.len();
+    let invalid_start = name_bytes.len() - invalid_bytes - idx_grapheme;
+    push_invalid_bytes(
+        invalid_bytes + idx_grapheme,
+        &mut new_name,
+        separator_run,
+        options,
+    );
+    record_substitution(
+        report,
+        &name_bytes[invalid_start..],
+        &new_name[pushed_start..],
+    );
+    if options.dedupe_extension {
+        remove_repeated_extension(&mut new_name);
+    }
+    if options.trim {
+        new_name = trim_separators(&new_name);
+    }
+    if let Some(placeholder) = &options.empty_stem_placeholder {
+        replace_empty_stem(&mut new_name, path, placeholder);
+    }
+    if options.lowercase {
+        new_name = new_name.to_lowercase();
+    }
+    if let Some(case_mode) = options.post_separator_case {
+        apply_post_separator_case(&mut new_name, case_mode, options);
+    }
+    match options.profile {
+        Profile::Default => {}
+        Profile::SharePoint => apply_sharepoint_rules(&mut new_name),
+        Profile::WindowsSafe => apply_windows_rules(&mut new_name),
+        // the portable characters are the ones safe in a shell
+        Profile::PortablePosix => make_shell_safe(&mut new_name),
+    }
+    if options.shell_safe {
+        make_shell_safe(&mut new_name);
+    }
+    if let Some(max_bytes) = options.max_length {
+        truncate_name(&mut new_name, max_bytes);
+    }
+    OsString::from(new_name)
+}
+
+/// Error of the renames not done because of the dry-run, before [`PathChange::WouldChange`]
+const DRY_RUN_ERROR: &str = "dry-run";
+
+/// Maximum length in bytes of a file name on most filesystems
+const MAX_NAME_BYTES: usize = 255;
+
+/// Check if renaming to the cleaned path is known to fail
+///
+/// An existing target is checked like [`rename_path`] does, so the dry-run gives the result of the rename
+#[inline(always)]
+fn would_fail_reason(file_path: &Path, cleaned_path: &Path, options: &NotoxArgs) -> Option<String> {
+    if options.clobber_policy == ClobberPolicy::Refuse && is_other_target(file_path, cleaned_path) {
+        return Some(TARGET_EXISTS_REASON.to_string());
+    }
+    name_too_long_reason(cleaned_path, MAX_NAME_BYTES)
+}
+
+/// Get the reason why a cleaned name is over `max_bytes`, `None` if it is not
+fn name_too_long_reason(cleaned_path: &Path, max_bytes: usize) -> Option<String> {
+    match cleaned_path.file_name() {
+        Some(name) if name.len() > max_bytes => Some(format!(
+            "name too long ({} bytes, max {})",
+            name.len(),
+            max_bytes
+        )),
+        _ => None,
+    }
+}
+
+/// Parse a time limit, relative to `now` (`30s`, `15m`, `12h`, `7d`, `2w`, `1y`) or a date (`2024-01-31`, UTC)
+fn parse_time_limit(value: &str, now: SystemTime) -> Option<SystemTime> {
+    if let Some(days) = parse_date_days(value) {
+        let seconds = u64::try_from(days).ok()?.checked_mul(86400)?;
+        return SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds));
+    }
+    let unit_idx = value.find(|one_char: char| !one_char.is_ascii_digit())?;
+    let count = value[..unit_idx].parse::<u64>().ok()?;
+    let unit_seconds = match &value[unit_idx..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        "y" => 365 * 86400,
+        _ => return None,
+    };
+    now.checked_sub(Duration::from_secs(count.checked_mul(unit_seconds)?))
+}
+
+/// Number of days since the Unix epoch of a `YYYY-MM-DD` date
+fn parse_date_days(value: &str) -> Option<i64> {
+    let mut parts = value.splitn(3, '-');
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let month = parts.next()?.parse::<i64>().ok()?;
+    let day = parts.next()?.parse::<i64>().ok()?;
+    if value.len() != 10 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // days from civil, the year starts in March so the leap day is the last one
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+/// Check if the modification time of a path is outside of `newer_than` and `older_than`
+///
+/// A path whose modification time cannot be read is not outside
+fn is_outside_time_window(file_path: &Path, options: &NotoxArgs) -> bool {
+    if options.newer_than.is_none() && options.older_than.is_none() {
+        return false;
+    }
+    let Ok(mtime) = file_path
+        .symlink_metadata()
+        .and_then(|metadata| metadata.modified())
+    else {
+        return false;
+    };
+    options.newer_than.is_some_and(|limit| mtime < limit)
+        || options.older_than.is_some_and(|limit| mtime > limit)
+}
+
+/// Reason of the paths skipped because of `rename_budget`
+const RENAME_BUDGET_REASON: &str = "rename limit reached";
+
+/// Count a rename in `renames_done`, `false` if the `rename_budget` is already reached
+fn take_rename_slot(options: &NotoxArgs, renames_done: &AtomicUsize) -> bool {
+    match options.rename_budget {
+        Some(max_renames) => renames_done
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |done| {
+                (done < max_renames).then_some(done + 1)
+            })
+            .is_ok(),
+        None => true,
+    }
+}
+
+/// Clean a path
+///
+/// The paths of `journal_done` are skipped and give no change.
+/// The renames are counted in `renames_done` for the `rename_budget`.
+/// The change of the path is the last one, it can be preceded by the change of the existing target
+fn clean_path(
+    file_path: &Path,
+    options: &NotoxArgs,
+    renames_done: &AtomicUsize,
+) -> Vec<PathChange> {
+    clean_planned_path(file_path, options, renames_done, None)
+}
+
+/// Clean a path whose target may have been planned with the other entries of its directory, see [`plan_targets`]
+///
+/// Without a planned target, the collisions are only checked against the existing paths
+fn clean_planned_path(
+    file_path: &Path,
+    options: &NotoxArgs,
+    renames_done: &AtomicUsize,
+    planned: Option<&PlannedTarget>,
+) -> Vec<PathChange> {
+    if options.journal_done.contains(file_path) {
+        return Vec::new();
+    }
+    let file_name = match file_path.file_name() {
+        Some(name) => name,
+        None => {
+            return vec![PathChange::Unchanged {
+                path: file_path.to_path_buf(),
+            }];
+        }
+    };
+    let cleaned_name = clean_name(file_name, options);
+    if cleaned_name == file_name || !options.targets.contains(file_path) {
+        return vec![PathChange::Unchanged {
+            path: file_path.to_path_buf(),
+        }];
+    }
+    if is_outside_time_window(file_path, options) {
+        // the entries of a skipped directory are still cleaned
+        return vec![PathChange::Skipped {
+            path: file_path.to_path_buf(),
+            reason: "modified outside of the time window".to_string(),
+        }];
+    }
+    let mut cleaned_path = file_path.with_file_name(&cleaned_name);
+    if let Some(PlannedTarget::CaseCollision(other_name)) = planned {
+        let reason = format!(
+            "{} {}",
+            CASE_COLLISION_REASON,
+            Path::new(other_name).display()
+        );
+        return collision_failure(file_path, cleaned_path, reason, options);
+    }
+    let target = match (planned, options.on_collision) {
+        (Some(target), _) => Some(target.clone()),
+        (None, Some(policy)) => Some(resolve_collision(
+            &cleaned_name,
+            policy,
+            options.max_length,
+            |candidate| {
+                file_path
+                    .with_file_name(candidate)
+                    .symlink_metadata()
+                    .is_ok()
+            },
+        )),
+        (None, None) => None,
+    };
+    match (target, options.on_collision) {
+        (None | Some(PlannedTarget::Free | PlannedTarget::CaseCollision(_)), _) => {}
+        (Some(PlannedTarget::Suffixed(suffixed_name)), _) => {
+            cleaned_path = file_path.with_file_name(suffixed_name);
+        }
+        (Some(PlannedTarget::Taken), Some(CollisionPolicy::Skip)) => {
+            return vec![PathChange::Skipped {
+                path: file_path.to_path_buf(),
+                reason: COLLISION_REASON.to_string(),
+            }];
+        }
+        (Some(PlannedTarget::Taken), _) => {
+            return collision_failure(
+                file_path,
+                cleaned_path,
+                COLLISION_REASON.to_string(),
+                options,
+            );
+        }
+    }
+    if let Some(reason) = options
+        .reject_over_len
+        .and_then(|max_bytes| name_too_long_reason(&cleaned_path, max_bytes))
+    {
+        return match options.dry_run {
+            true => vec![PathChange::WouldFail {
+                path: file_path.to_path_buf(),
+                modified: cleaned_path,
+                reason,
+            }],
+            false => vec![PathChange::ErrorRename {
+                path: file_path.to_path_buf(),
+                modified: cleaned_path,
+                error: reason,
+            }],
+        };
+    }
+    let skipped = || {
+        vec![PathChange::Skipped {
+            path: file_path.to_path_buf(),
+            reason: RENAME_BUDGET_REASON.to_string(),
+        }]
+    };
+    if options.dry_run {
+        let change = dry_run_change(file_path, cleaned_path, options);
+        if change.is_dry_run_rename() && !take_rename_slot(options, renames_done) {
+            return skipped();
+        }
+        return vec![change];
+    }
+    if !take_rename_slot(options, renames_done) {
+        return skipped();
+    }
+    let changes = rename_path(file_path, cleaned_path, options);
+    if options.rename_budget.is_some()
+        && !matches!(changes.last(), Some(PathChange::Changed { .. }))
+    {
+        // the failed renames are not counted
+        renames_done.fetch_sub(1, Ordering::SeqCst);
+    }
+    changes
+}
+
+/// Reason of the renames not done because the target exists, with [`ClobberPolicy::Refuse`]
+const TARGET_EXISTS_REASON: &str = "target already exists";
+
+/// Reason of the renames not done because the cleaned name is already taken
+const COLLISION_REASON: &str = "name already taken";
+
+/// Reason of the renames not done because the cleaned name differs only by case from another name
+const CASE_COLLISION_REASON: &str = "name differs only by case from";
+
+/// Get the change of a rename not done because of a collision, a failure with the dry-run
+fn collision_failure(
+    file_path: &Path,
+    cleaned_path: PathBuf,
+    reason: String,
+    options: &NotoxArgs,
+) -> Vec<PathChange> {
+    match options.dry_run {
+        true => vec![PathChange::WouldFail {
+            path: file_path.to_path_buf(),
+            modified: cleaned_path,
+            reason,
+        }],
+        false => vec![PathChange::ErrorRename {
+            path: file_path.to_path_buf(),
+            modified: cleaned_path,
+            error: reason,
+        }],
+    }
+}
+
+/// Target of a cleaned name, according to the `on_collision` policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PlannedTarget {
+    /// the cleaned name is not taken
+    Free,
+
+    /// the cleaned name is taken, this name with a suffix is not
+    Suffixed(OsString),
+
+    /// the cleaned name is taken, the path is not renamed
+    Taken,
+
+    /// the cleaned name differs only by case from this name of the directory, the path is not renamed
+    CaseCollision(OsString),
+}
+
+/// Find the target of a cleaned name, `is_taken` tells if a name is already used
+///
+/// A suffixed name still fits in `max_length`
+fn resolve_collision(
+    cleaned_name: &OsStr,
+    policy: CollisionPolicy,
+    max_length: Option<usize>,
+    is_taken: impl Fn(&OsStr) -> bool,
+) -> PlannedTarget {
+    if !is_taken(cleaned_name) {
+        return PlannedTarget::Free;
+    }
+    if policy != CollisionPolicy::Suffix {
+        return PlannedTarget::Taken;
+    }
+    let mut suffix: usize = 1;
+    loop {
+        let candidate = suffixed_name(cleaned_name, suffix, max_length);
+        if !is_taken(&candidate) {
+            return PlannedTarget::Suffixed(candidate);
+        }
+        suffix += 1;
+    }
+}
+
+/// Add a numeric suffix to the stem of a name, before its extension (`photo.jpg` gives `photo_1.jpg`)
+///
+/// With `max_length`, the stem is truncated again to keep the suffix and the extension,
+/// like [`truncate_name`]
+fn suffixed_name(name: &OsStr, suffix: usize, max_length: Option<usize>) -> OsString {
+    let name = name.to_string_lossy();
+    let (mut stem, extension) = match name.rfind('.') {
+        Some(last_dot) if last_dot > 0 => name.split_at(last_dot),
+        _ => (name.as_ref(), ""),
+    };
+    let suffix = format!("_{}", suffix);
+    if let Some(max_bytes) = max_length.filter(|max_bytes| *max_bytes > 0) {
+        let mut stem_end = max_bytes.saturating_sub(suffix.len() + extension.len());
+        if stem_end < stem.len() {
+            while !stem.is_char_boundary(stem_end) {
+                stem_end -= 1;
+            }
+            stem = stem[..stem_end].trim_end_matches('_');
+        }
+    }
+    OsString::from(format!("{}{}{}", stem, suffix, extension))
+}
+
+/// Get the name used to compare the names on the case-insensitive filesystems
+fn case_fold(name: &OsStr) -> String {
+    name.to_string_lossy().to_lowercase()
+}
+
+/// Plan the targets of the entries of a directory
+///
+/// With an `on_collision` policy, the names of all the entries are taken, then the cleaned names are given
+/// in the order of the original names, so the same tree always gives the same suffixes.
+/// Whatever the policy, a target already given to an earlier entry is [`PlannedTarget::Taken`],
+/// the existing targets are left to the [`ClobberPolicy`].
+/// A target differing only by case from the name of another entry, or from an earlier target,
+/// is a [`PlannedTarget::CaseCollision`] whatever the filesystem, so the results are the same
+/// on the case-insensitive ones. Only the entries whose name changes are planned.
+fn plan_targets(entry_paths: &[PathBuf], options: &NotoxArgs) -> HashMap<PathBuf, PlannedTarget> {
+    let mut planned = HashMap::new();
+    let mut taken: HashSet<OsString> = entry_paths
+        .iter()
+        .filter_map(|entry_path| entry_path.file_name())
+        .map(OsStr::to_os_string)
+        .collect();
+    let mut target_names = HashSet::new();
+    let mut sorted_paths: Vec<&PathBuf> = entry_paths.iter().collect();
+    sorted_paths.sort();
+    let mut renamed = Vec::new();
+    // the names kept as they are, by their case-folded name
+    let mut folded_names: HashMap<String, Vec<OsString>> = HashMap::new();
+    for entry_path in sorted_paths {
+        let Some(name) = entry_path.file_name() else {
+            continue;
+        };
+        let cleaned_name = clean_name(name, options);
+        match cleaned_name == name
+            || skip_reason(entry_path, options).is_some()
+            || !options.targets.contains(entry_path)
+        {
+            true => folded_names
+                .entry(case_fold(name))
+                .or_default()
+                .push(name.to_os_string()),
+            false => renamed.push((entry_path, cleaned_name)),
+        }
+    }
+    for (entry_path, cleaned_name) in renamed {
+        let target = match options.on_collision {
+            Some(policy) => {
+                resolve_collision(&cleaned_name, policy, options.max_length, |candidate| {
+                    taken.contains(candidate)
+                })
+            }
+            None => PlannedTarget::Free,
+        };
+        let target_name = match &target {
+            PlannedTarget::Free => cleaned_name,
+            PlannedTarget::Suffixed(suffixed_name) => suffixed_name.clone(),
+            PlannedTarget::Taken | PlannedTarget::CaseCollision(_) => {
+                planned.insert(entry_path.clone(), target);
+                continue;
+            }
+        };
+        // a rename would replace the path renamed by another one
+        if target_names.contains(&target_name) {
+            planned.insert(entry_path.clone(), PlannedTarget::Taken);
+            continue;
+        }
+        let same_folded_names = folded_names.entry(case_fold(&target_name)).or_default();
+        if let Some(other_name) = same_folded_names.iter().find(|name| **name != target_name) {
+            planned.insert(
+                entry_path.clone(),
+                PlannedTarget::CaseCollision(other_name.clone()),
+            );
+            continue;
+        }
+        same_folded_names.push(target_name.clone());
+        target_names.insert(target_name.clone());
+        taken.insert(target_name);
+        planned.insert(entry_path.clone(), target);
+    }
+    planned
+}
+
+/// Get the change of a rename not done because of the dry-run
+fn dry_run_change(file_path: &Path, cleaned_path: PathBuf, options: &NotoxArgs) -> PathChange {
+    match would_fail_reason(file_path, &cleaned_path, options) {
+        Some(reason) => PathChange::WouldFail {
+            path: file_path.to_path_buf(),
+            modified: cleaned_path,
+            reason,
+        },
+        None => PathChange::WouldChange {
+            path: file_path.to_path_buf(),
+            modified: cleaned_path,
+        },
+    }
+}
+
+/// Get a path from the bytes of a journal record, of a NUL-separated input or of a line
+fn journal_path(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).as_ref())
+    }
+}
+
+/// Read the paths already renamed from a journal
+///
+/// A journal is a list of `path\0modified\0` records, one per successful rename
+/// # Errors
+/// Return an error if the journal cannot be read
+pub fn read_journal(journal: &Path) -> std::io::Result<HashSet<PathBuf>> {
+    let content = std::fs::read(journal)?;
+    let mut records = content.split(|byte| *byte == 0);
+    let mut done = HashSet::new();
+    while let (Some(path), Some(_modified)) = (records.next(), records.next()) {
+        done.insert(journal_path(path));
+    }
+    Ok(done)
+}
+
+/// Read NUL-separated paths, like the output of `find -print0`
+///
+/// Unlike the lines, the names containing a newline are kept as they are. The empty records are skipped
+/// # Errors
+/// Return an error if the reader fails
+pub fn read_null_paths<R: std::io::Read>(mut reader: R) -> std::io::Result<Vec<PathBuf>> {
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+    Ok(content
+        .split(|byte| *byte == 0)
+        .filter(|record| !record.is_empty())
+        .map(journal_path)
+        .collect())
+}
+
+/// Write the renames as NUL-separated `path\0modified\0` pairs, like the records of a journal
+///
+/// Only the renames done or that would be done are written, without the summary
+/// # Errors
+/// Return an error if the writer fails
+pub fn write_null_pairs<W: Write>(changes: &[PathChange], writer: &mut W) -> std::io::Result<()> {
+    for one_change in changes {
+        if let PathChange::Changed { path, modified } | PathChange::WouldChange { path, modified } =
+            one_change
+        {
+            writer.write_all(path.as_os_str().as_encoded_bytes())?;
+            writer.write_all(b"\0")?;
+            writer.write_all(modified.as_os_str().as_encoded_bytes())?;
+            writer.write_all(b"\0")?;
+        }
+    }
+    Ok(())
+}
+
+/// Append a successful rename to the journal
+///
+/// The record is written with a single write on a file opened in append mode,
+/// so the renames of parallel threads are not mixed
+fn append_journal(journal: &Path, path: &Path, modified: &Path) -> std::io::Result<()> {
+    let mut record = Vec::new();
+    record.extend_from_slice(path.as_os_str().as_encoded_bytes());
+    record.push(0);
+    record.extend_from_slice(modified.as_os_str().as_encoded_bytes());
+    record.push(0);
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(journal)?
+        .write_all(&record)
+}
+
+/// Check if the target of a rename is an existing path other than the renamed one
+///
+/// On the case-insensitive filesystems, the target of a change of case is the renamed path itself
+fn is_other_target(file_path: &Path, cleaned_path: &Path) -> bool {
+    let Ok(target_metadata) = cleaned_path.symlink_metadata() else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        file_path.symlink_metadata().map_or(true, |metadata| {
+            (metadata.dev(), metadata.ino()) != (target_metadata.dev(), target_metadata.ino())
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = target_metadata;
+        file_path.file_name().map(case_fold) != cleaned_path.file_name().map(case_fold)
+    }
+}
+
+/// Move away the existing target of a rename according to the [`ClobberPolicy`]
+///
+/// Returns the change of the target, if any
+/// # Errors
+/// Return the error message if the target is refused or cannot be moved away
+#[cfg_attr(not(feature = "trash"), allow(unused_variables))]
+fn clobber_target(target: &Path, policy: ClobberPolicy) -> Result<Option<PathChange>, String> {
+    match policy {
+        ClobberPolicy::Refuse => Err(TARGET_EXISTS_REASON.to_string()),
+        ClobberPolicy::Overwrite => Ok(None),
+        #[cfg(feature = "trash")]
+        ClobberPolicy::Trash => match trash::delete(target) {
+            Ok(_) => Ok(Some(PathChange::Trashed {
+                path: target.to_path_buf(),
+            })),
+            Err(e) => Err(format!("cannot move the target to the trash: {}", e)),
+        },
+    }
+}
+
+/// Get the path of the copy of a file in the backup directory, with the same relative structure
+///
+/// The root and the `..` of the path are dropped, so the copy is always inside the backup directory.
+/// The files are copied when they are renamed, after the rename of their parent directories
+pub fn backup_path(backup_dir: &Path, file_path: &Path) -> PathBuf {
+    file_path
+        .components()
+        .fold(backup_dir.to_path_buf(), |acc, component| match component {
+            Component::Normal(name) => acc.join(name),
+            _ => acc,
+        })
+}
+
+/// Copy a regular file to its backup path, creating the missing directories
+///
+/// An existing backup is never replaced, the copy then gets a numeric suffix
+/// (`<dir>/photos/a b_1.jpg`), so a second run keeps the first copy.
+/// Returns the path of the copy
+fn backup_file(file_path: &Path, backup: &Path) -> std::io::Result<PathBuf> {
+    if let Some(parent) = backup.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut source = std::fs::File::open(file_path)?;
+    let mut candidate = backup.to_path_buf();
+    let mut suffix: usize = 0;
+    // create_new claims the copy, two renames of the same run cannot share it
+    let mut copy = loop {
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(copy) => break copy,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                suffix += 1;
+                candidate = backup.with_file_name(suffixed_name(
+                    backup.file_name().unwrap_or_default(),
+                    suffix,
+                    None,
+                ));
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    let copied = std::io::copy(&mut source, &mut copy)
+        .and_then(|_| copy.set_permissions(source.metadata()?.permissions()));
+    match copied {
+        Ok(_) => Ok(candidate),
+        Err(e) => {
+            let _ = std::fs::remove_file(&candidate);
+            Err(e)
+        }
+    }
+}
+
+/// Rename a path
+///
+/// The change of the path is the last one, it can be preceded by the change of the existing target.
+/// With `backup_dir`, a regular file is copied once the target is cleared, the directories and the links
+/// are not copied and a file that cannot be copied is not renamed. The copy is removed if the rename fails
+fn rename_path(file_path: &Path, cleaned_path: PathBuf, options: &NotoxArgs) -> Vec<PathChange> {
+    let mut changes = Vec::new();
+    if is_other_target(file_path, &cleaned_path) {
+        match clobber_target(&cleaned_path, options.clobber_policy) {
+            Ok(target_change) => changes.extend(target_change),
+            Err(error) => {
+                changes.push(PathChange::ErrorRename {
+                    path: file_path.to_path_buf(),
+                    modified: cleaned_path,
+                    error,
+                });
+                return changes;
+            }
+        }
+    }
+    let mut copy = None;
+    if let Some(backup_dir) = &options.backup_dir {
+        let is_file = file_path
+            .symlink_metadata()
+            .is_ok_and(|metadata| metadata.is_file());
+        if is_file {
+            let backup = backup_path(backup_dir, file_path);
+            match backup_file(file_path, &backup) {
+                Ok(copied) => copy = Some(copied),
+                Err(e) => {
+                    changes.push(PathChange::ErrorBackup {
+                        path: file_path.to_path_buf(),
+                        backup,
+                        error: e.to_string(),
+                    });
+                    return changes;
+                }
+            }
+        }
+    }
+    match std::fs::rename(file_path, &cleaned_path) {
+        Ok(_) => {
+            if let Some(journal) = &options.journal {
+                if let Err(e) = append_journal(journal, file_path, &cleaned_path) {
+                    changes.push(PathChange::Error {
+                        path: journal.clone(),
+                        error: format!("Cannot write journal: {}", e),
+                    });
+                }
+            }
+            changes.push(PathChange::Changed {
+                path: file_path.to_path_buf(),
+                modified: cleaned_path,
+            });
+        }
+        Err(rename_error) => {
+            if let Some(copy) = copy {
+                let _ = std::fs::remove_file(copy);
+            }
+            changes.push(PathChange::ErrorRename {
+                path: file_path.to_path_buf(),
+                modified: cleaned_path,
+                error: rename_error.to_string(),
+            })
+        }
+    }
+    changes
+}
+
+/// Check if the name of a directory is in the list of directories to not rename
+#[inline(always)]
+fn is_no_rename_dir(dir_path: &Path, options: &NotoxArgs) -> bool {
+    match dir_path.file_name() {
+        Some(dir_name) => options.no_rename_dirs.iter().any(|name| name == dir_name),
+        None => false,
+    }
+}
+
+/// Check if a directory has an extension of the directories treated as files,
+/// or is one of the `no_rename_dirs` with `skip_no_rename_dir_content`
+#[inline(always)]
+fn is_opaque_dir(dir_path: &Path, options: &NotoxArgs) -> bool {
+    if options.skip_no_rename_dir_content && is_no_rename_dir(dir_path, options) {
+        return true;
+    }
+    match dir_path.extension() {
+        Some(extension) => options
+            .treat_as_file_exts
+            .iter()
+            .any(|ext| extension.eq_ignore_ascii_case(ext)),
+        None => false,
+    }
+}
+
+/// Number of paths between two progress lines, see [`NotoxArgs::progress`]
+pub const PROGRESS_INTERVAL: usize = 1000;
+
+/// State shared by all the directories of a run
+struct WalkState {
+    /// canonical paths of the directories already visited, with `follow_symlinks`
+    visited: Mutex<HashSet<PathBuf>>,
+    /// number of paths cleaned, for the progress
+    processed: AtomicUsize,
+    /// start of the run, `None` without progress
+    progress_start: Option<Instant>,
+    /// if true, the walk stops at the first error
+    stop_at_first_error: bool,
+    /// set at the first error with `stop_at_first_error`, or once the sink is closed
+    stopped: AtomicBool,
+    /// the counts of the directories done, in the order they are finished, for `per_dir_summary`
+    dir_summaries: Mutex<Vec<(PathBuf, Summary)>>,
+}
+
+impl WalkState {
+    /// Create the state of a new run, the progress is only printed with the verbose output
+    fn new(options: &NotoxArgs) -> WalkState {
+        WalkState {
+            visited: Mutex::new(HashSet::new()),
+            processed: AtomicUsize::new(0),
+            progress_start: (options.progress && options.is_vervose()).then(Instant::now),
+            stop_at_first_error: options.stop_at_first_error,
+            stopped: AtomicBool::new(false),
+            dir_summaries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Send a change to the sink, or keep it in the results if there is no sink
+    ///
+    /// An error stops the walk with `stop_at_first_error`, a closed sink always stops it
+    fn emit(
+        &self,
+        result_vec: &mut Vec<(usize, PathChange)>,
+        sink: ChangeSink<'_>,
+        depth: usize,
+        one_change: PathChange,
+    ) {
+        if self.stop_at_first_error && one_change.is_error() {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+        match sink {
+            Some(sink) => {
+                if !sink(depth, one_change) {
+                    self.stopped.store(true, Ordering::Relaxed);
+                }
             }
+            None => result_vec.push((depth, one_change)),
+        }
+    }
+
+    /// Check if the walk is stopped by an error or a closed sink, the paths not started yet are not cleaned
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Count a cleaned path, the progress is printed every [`PROGRESS_INTERVAL`] paths
+    fn tick(&self) {
+        let Some(start) = self.progress_start else {
+            return;
         };
-        return false;
+        // the counter is shared by the threads of rayon, a single thread prints each line
+        let processed = self.processed.fetch_add(1, Ordering::Relaxed) + 1;
+        if processed.is_multiple_of(PROGRESS_INTERVAL) {
+            eprintln!("{}", progress_line(processed, start.elapsed()));
+        }
+    }
+
+    /// Print the final count of the progress
+    fn finish(&self) {
+        if let Some(start) = self.progress_start {
+            let processed = self.processed.load(Ordering::Relaxed);
+            eprintln!("{}, done", progress_line(processed, start.elapsed()));
+        }
     }
-    false
 }
 
-/// Convert four bytes to a u32
-#[inline(always)]
-pub fn convert_four_to_u32(
-    first_byte: u8,
-    second_byte: u8,
-    third_byte: u8,
-    fourth_byte: u8,
-) -> u32 {
-    ((first_byte as u32 & 0b0000_0111) << 18)
-        | ((second_byte as u32 & 0b0011_1111) << 12)
-        | ((third_byte as u32 & 0b0011_1111) << 6)
-        | (fourth_byte as u32 & 0b0011_1111)
+/// Get the progress line of the paths processed since the start of the run
+fn progress_line(processed: usize, elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs_f64();
+    let rate = match seconds > 0.0 {
+        true => processed as f64 / seconds,
+        false => 0.0,
+    };
+    format!(
+        "{} paths processed in {:.1}s ({:.0} paths/s)",
+        processed, seconds, rate
+    )
 }
 
-/// Convert three bytes to a u32
-#[inline(always)]
-pub fn convert_three_to_u32(first_byte: u8, second_byte: u8, third_byte: u8) -> u32 {
-    ((first_byte as u32 & 0b0001_1111) << 12)
-        | ((second_byte as u32 & 0b0011_1111) << 6)
-        | (third_byte as u32 & 0b0011_1111)
+/// Receiver of the changes as soon as they are done, with their depth, see [`Notox::run_with_callback`]
+///
+/// It returns false once it is closed, the walk is then stopped
+type ChangeSink<'a> = Option<&'a (dyn Fn(usize, PathChange) -> bool + Sync)>;
+
+/// A directory whose entries are waiting to be cleaned, see [`clean_directory`]
+struct PendingDir {
+    /// path of the directory, already renamed
+    path: PathBuf,
+    /// depth of the directory
+    depth: usize,
+    /// index of the slot of the changes of its entries
+    slot: usize,
 }
 
-/// Convert two bytes to a u32
-#[inline(always)]
-pub fn convert_two_to_u32(first_byte: u8, second_byte: u8) -> u32 {
-    ((first_byte as u32 & 0b0001_1111) << 6) | (second_byte as u32 & 0b0011_1111)
+/// A result of the cleaning of the entries of a directory, in the order of the output
+enum DirItem {
+    /// a change with its depth
+    Change(usize, PathChange),
+    /// a directory entry (already renamed) whose own entries come at this place
+    Dir(PathBuf),
 }
 
-/// Clean a name
-#[inline(always)]
-fn clean_name(path: &OsStr, _options: &NotoxArgs) -> OsString {
-    // for each byte of the path if it's not ascii, replace it with _
-    let mut new_name = String::new();
-    let mut vec_grapheme: [u8; 4] = [0; 4];
-    let mut last_was_underscore = false;
-    let mut idx_grapheme = 0;
-    for byte in path.as_encoded_bytes().iter() {
-        if idx_grapheme == 0 && *byte < 128 {
-            match byte {
-                0..=44 => {
-                    push_underscore_if(&mut new_name, '_', !last_was_underscore);
-                    last_was_underscore = true;
-                }
-                46 => {
-                    new_name.push('.');
-                    last_was_underscore = false;
-                }
-                47 => {
-                    push_underscore_if(&mut new_name, '_', !last_was_underscore);
-                    last_was_underscore = true;
-                }
-                58..=64 => {
-                    push_underscore_if(&mut new_name, '_', !last_was_underscore);
-                    last_was_underscore = true;
+/// Changes of the entries of a directory, with the places of the changes of its subdirectories
+struct DirSlot {
+    /// the changes of the entries, in the order of the output
+    changes: Vec<Slot>,
+    /// index of the slot of the parent directory
+    parent: Option<usize>,
+    /// number of subdirectories whose content is not cleaned yet
+    remaining_subdirs: usize,
+    /// the counts of the changes of the entries, for `per_dir_summary`
+    summary: Option<Summary>,
+}
+
+/// A change or the place of the changes of a subdirectory
+enum Slot {
+    /// a change with its depth
+    Change(usize, PathChange),
+    /// the index of the slot of a subdirectory
+    Child(usize),
+}
+
+/// Clean a directory and its content
+///
+/// The change of the directory always comes before the changes of its entries, even with rayon,
+/// and the entries are cleaned in the order of their paths.
+/// The directories with an extension of `treat_as_file_exts` are cleaned like files, without their content
+///
+/// The tree is traversed with a queue of the directories instead of recursive calls,
+/// so a deep tree cannot overflow the stack. A directory is renamed before its entries are cleaned,
+/// with rayon all the directories waiting in the queue are cleaned in parallel.
+fn clean_directory(
+    dir_path: &Path,
+    options: &NotoxArgs,
+    renames_done: &AtomicUsize,
+    sink: ChangeSink<'_>,
+    walk: &WalkState,
+) -> Vec<(usize, PathChange)> {
+    let (dir_changes, dir_path, _) =
+        clean_directory_name(dir_path, 0, options, renames_done, sink, None, walk);
+    let mut slots = vec![DirSlot {
+        changes: dir_changes
+            .into_iter()
+            .map(|(depth, one_change)| Slot::Change(depth, one_change))
+            .collect(),
+        parent: None,
+        remaining_subdirs: 0,
+        summary: None,
+    }];
+    let mut pending = vec![PendingDir {
+        path: dir_path,
+        depth: 0,
+        slot: 0,
+    }];
+    while !pending.is_empty() && !walk.is_stopped() {
+        #[cfg(feature = "rayon")]
+        let cleaned = std::mem::take(&mut pending)
+            .into_par_iter()
+            .map(|dir| {
+                let (items, summary) =
+                    clean_directory_entries(&dir, options, renames_done, sink, walk);
+                (dir, items, summary)
+            })
+            .collect::<Vec<(PendingDir, Vec<DirItem>, Option<Summary>)>>();
+        // one directory at a time, depth-first like the recursive calls
+        #[cfg(not(feature = "rayon"))]
+        let cleaned = pending
+            .pop()
+            .map(|dir| {
+                let (items, summary) =
+                    clean_directory_entries(&dir, options, renames_done, sink, walk);
+                (dir, items, summary)
+            })
+            .into_iter()
+            .collect::<Vec<(PendingDir, Vec<DirItem>, Option<Summary>)>>();
+        for (dir, items, summary) in cleaned {
+            let mut subdirs = Vec::new();
+            for item in items {
+                let slot = match item {
+                    DirItem::Change(depth, one_change) => Slot::Change(depth, one_change),
+                    DirItem::Dir(path) => {
+                        let child = slots.len() + subdirs.len();
+                        subdirs.push(PendingDir {
+                            path,
+                            depth: dir.depth + 1,
+                            slot: child,
+                        });
+                        Slot::Child(child)
+                    }
+                };
+                slots[dir.slot].changes.push(slot);
+            }
+            slots[dir.slot].remaining_subdirs = subdirs.len();
+            slots[dir.slot].summary = summary;
+            slots.extend(subdirs.iter().map(|_| DirSlot {
+                changes: Vec::new(),
+                parent: Some(dir.slot),
+                remaining_subdirs: 0,
+                summary: None,
+            }));
+            if options.per_dir_summary && options.is_vervose() {
+                finish_summaries(&mut slots, dir.slot, &dir.path, walk);
+            }
+            pending.extend(subdirs.into_iter().rev());
+        }
+    }
+    flatten_slots(slots)
+}
+
+/// Keep the `per_dir_summary` of a directory whose content is cleaned, then the ones of its parents done with it
+///
+/// A directory is summarized after its subdirectories, like with the recursive calls
+fn finish_summaries(slots: &mut [DirSlot], slot: usize, dir_path: &Path, walk: &WalkState) {
+    let mut finished = Some((slot, dir_path.to_path_buf()));
+    while let Some((slot, dir_path)) = finished.take() {
+        if slots[slot].remaining_subdirs > 0 {
+            break;
+        }
+        if let Some(summary) = slots[slot].summary.take() {
+            walk.dir_summaries
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((dir_path.clone(), summary));
+        }
+        if let Some(parent) = slots[slot].parent {
+            slots[parent].remaining_subdirs -= 1;
+            finished = dir_path.parent().map(|path| (parent, path.to_path_buf()));
+        }
+    }
+}
+
+/// Put the changes of the subdirectories at their place, the first slot is the one of the cleaned directory
+fn flatten_slots(mut slots: Vec<DirSlot>) -> Vec<(usize, PathChange)> {
+    let mut result_vec = Vec::new();
+    let mut stack = vec![std::mem::take(&mut slots[0].changes).into_iter()];
+    while let Some(slot_iter) = stack.last_mut() {
+        match slot_iter.next() {
+            Some(Slot::Change(depth, one_change)) => result_vec.push((depth, one_change)),
+            Some(Slot::Child(child)) => {
+                stack.push(std::mem::take(&mut slots[child].changes).into_iter())
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+    result_vec
+}
+
+/// Clean the name of a directory, before its entries
+///
+/// Returns the changes, the path of the directory after them,
+/// and the counts of the changes for the `per_dir_summary` of its parent
+fn clean_directory_name(
+    dir_path: &Path,
+    depth: usize,
+    options: &NotoxArgs,
+    renames_done: &AtomicUsize,
+    sink: ChangeSink<'_>,
+    planned: Option<&PlannedTarget>,
+    walk: &WalkState,
+) -> (Vec<(usize, PathChange)>, PathBuf, Summary) {
+    walk.tick();
+    let mut dir_path = dir_path.to_path_buf();
+    let mut result_vec = Vec::new();
+    let res_dir = match is_no_rename_dir(&dir_path, options) {
+        true => vec![PathChange::Unchanged {
+            path: dir_path.clone(),
+        }],
+        false => clean_planned_path(&dir_path, options, renames_done, planned),
+    };
+    if let Some(PathChange::Changed { modified, .. }) = res_dir.last() {
+        dir_path = modified.clone();
+    }
+    let dir_summary = Summary::from_results(&res_dir);
+    for one_change in res_dir {
+        walk.emit(&mut result_vec, sink, depth, one_change);
+    }
+    (result_vec, dir_path, dir_summary)
+}
+
+/// Clean the entries of a directory, the subdirectories are renamed and returned to be cleaned later
+///
+/// Returns the counts of the changes of the entries when the directory could be read, for `per_dir_summary`
+fn clean_directory_entries(
+    dir: &PendingDir,
+    options: &NotoxArgs,
+    renames_done: &AtomicUsize,
+    sink: ChangeSink<'_>,
+    walk: &WalkState,
+) -> (Vec<DirItem>, Option<Summary>) {
+    let (dir_path, depth) = (&dir.path, dir.depth);
+    let mut result_vec = Vec::new();
+    // the directory at the maximum depth is still cleaned, not its entries
+    if is_opaque_dir(dir_path, options)
+        || options
+            .max_depth
+            .is_some_and(|max_depth| depth >= max_depth)
+        || (options.follow_symlinks && !is_first_visit(dir_path, &walk.visited))
+    {
+        return (Vec::new(), None);
+    }
+    if let Ok(entries) = std::fs::read_dir(dir_path) {
+        let ok_entries = {
+            #[cfg(feature = "rayon")]
+            {
+                use std::fs::DirEntry;
+                let (ok_entries, error_entries): (Vec<_>, Vec<_>) = entries
+                    .collect::<Vec<Result<DirEntry, std::io::Error>>>()
+                    .into_par_iter()
+                    .partition_map(|x| match x {
+                        Ok(entry) => Either::Left(entry),
+                        Err(e) => Either::Right(e),
+                    });
+                error_entries.into_iter().for_each(|e| {
+                    walk.emit(
+                        &mut result_vec,
+                        sink,
+                        depth,
+                        PathChange::Error {
+                            path: dir_path.clone(),
+                            error: format!("Error reading dir entry of directory {}", e),
+                        },
+                    )
+                });
+                ok_entries
+            }
+            #[cfg(not(feature = "rayon"))]
+            {
+                let mut ok_entries = Vec::new();
+                for entry in entries {
+                    match entry {
+                        Ok(e) => ok_entries.push(e),
+                        Err(e) => walk.emit(
+                            &mut result_vec,
+                            sink,
+                            depth,
+                            PathChange::Error {
+                                path: dir_path.clone(),
+                                error: format!("Error reading dir entry of directory {}", e),
+                            },
+                        ),
+                    }
                 }
-                91..=96 => {
-                    push_underscore_if(&mut new_name, '_', !last_was_underscore);
-                    last_was_underscore = true;
+                ok_entries
+            }
+        };
+        let mut ok_entries = ok_entries;
+        ok_entries.sort_by_key(std::fs::DirEntry::path);
+        let planned_targets = plan_targets(
+            &ok_entries
+                .iter()
+                .map(|entry| entry.path())
+                .collect::<Vec<_>>(),
+            options,
+        );
+        #[cfg(feature = "rayon")]
+        let iter = ok_entries.par_iter();
+        #[cfg(not(feature = "rayon"))]
+        let iter = ok_entries.iter();
+        let mapped = iter
+            .map(|entry| {
+                if walk.is_stopped() {
+                    return (Vec::new(), None, Summary::default());
                 }
-                123..=127 => {
-                    push_underscore_if(&mut new_name, '_', !last_was_underscore);
-                    last_was_underscore = true;
+                let file_path = entry.path();
+                let file_type = entry.file_type().ok();
+                let is_symlink = file_type.is_some_and(|file_type| file_type.is_symlink());
+                let is_entry_directory = match file_type {
+                    Some(_) if is_symlink => options.follow_symlinks && file_path.is_dir(),
+                    Some(file_type) => file_type.is_dir(),
+                    None => false,
+                };
+                if let Some(reason) = skip_reason(&file_path, options) {
+                    walk.tick();
+                    let skipped = PathChange::Skipped {
+                        path: file_path,
+                        reason: reason.to_string(),
+                    };
+                    let skipped_summary = Summary::from_results(std::slice::from_ref(&skipped));
+                    let mut skipped_changes = Vec::new();
+                    walk.emit(&mut skipped_changes, sink, depth + 1, skipped);
+                    return (skipped_changes, None, skipped_summary);
                 }
-                _ => {
-                    new_name.push(*byte as char);
-                    last_was_underscore = false;
+                let mut entry_changes = Vec::new();
+                if let (true, Ok(target)) = (is_symlink, std::fs::read_link(&file_path)) {
+                    let symlink = PathChange::Symlink {
+                        path: file_path.clone(),
+                        target,
+                    };
+                    walk.emit(&mut entry_changes, sink, depth + 1, symlink);
                 }
+                let planned = planned_targets.get(&file_path);
+                let (changes, subdir, entry_summary) = if is_entry_directory {
+                    let (changes, subdir, entry_summary) = clean_directory_name(
+                        &file_path,
+                        depth + 1,
+                        options,
+                        renames_done,
+                        sink,
+                        planned,
+                        walk,
+                    );
+                    (changes, Some(subdir), entry_summary)
+                } else {
+                    walk.tick();
+                    let res_file = clean_planned_path(&file_path, options, renames_done, planned);
+                    let file_summary = Summary::from_results(&res_file);
+                    let mut file_changes = Vec::new();
+                    for one_change in res_file {
+                        walk.emit(&mut file_changes, sink, depth + 1, one_change);
+                    }
+                    (file_changes, None, file_summary)
+                };
+                entry_changes.extend(changes);
+                (entry_changes, subdir, entry_summary)
+            })
+            .collect::<Vec<(Vec<(usize, PathChange)>, Option<PathBuf>, Summary)>>();
+        let mut entries_summary = Summary::default();
+        let mut items: Vec<DirItem> = result_vec
+            .into_iter()
+            .map(|(depth, one_change)| DirItem::Change(depth, one_change))
+            .collect();
+        for (entry_changes, subdir, entry_summary) in mapped {
+            items.extend(
+                entry_changes
+                    .into_iter()
+                    .map(|(depth, one_change)| DirItem::Change(depth, one_change)),
+            );
+            items.extend(subdir.map(DirItem::Dir));
+            entries_summary.merge(&entry_summary);
+        }
+        (items, Some(entries_summary))
+    } else {
+        walk.emit(
+            &mut result_vec,
+            sink,
+            depth,
+            PathChange::Error {
+                path: dir_path.clone(),
+                error: "Error while reading directory".to_string(),
+            },
+        );
+        let items = result_vec
+            .into_iter()
+            .map(|(depth, one_change)| DirItem::Change(depth, one_change))
+            .collect();
+        (items, None)
+    }
+}
+
+/// Check if a directory is visited for the first time, with `follow_symlinks`
+///
+/// The directories are compared by their canonical path, a directory that cannot be canonicalized is always visited
+fn is_first_visit(dir_path: &Path, visited: &Mutex<HashSet<PathBuf>>) -> bool {
+    match std::fs::canonicalize(dir_path) {
+        Ok(canonical_path) => visited
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(canonical_path),
+        Err(_) => true,
+    }
+}
+
+/// Line of the `per_dir_summary`, like `dir/: 3 changed, 10 unchanged`
+fn dir_summary_line(dir_path: &Path, summary: &Summary) -> String {
+    let mut line = format!(
+        "{}{}: {} changed, {} unchanged",
+        dir_path.display(),
+        std::path::MAIN_SEPARATOR,
+        summary.changed,
+        summary.unchanged
+    );
+    if summary.would_change > 0 {
+        line.push_str(&format!(", {} would change", summary.would_change));
+    }
+    if summary.errors > 0 {
+        line.push_str(&format!(", {} errors", summary.errors));
+    }
+    line
+}
+
+/// Canonicalize paths, removing the duplicates
+///
+/// Paths that cannot be canonicalized are returned as errors
+fn canonicalize_paths(paths: &HashSet<PathBuf>) -> (HashSet<PathBuf>, Vec<PathChange>) {
+    let mut canonicalized = HashSet::new();
+    let mut errors = Vec::new();
+    for one_path in paths {
+        match std::fs::canonicalize(one_path) {
+            Ok(canonical_path) => {
+                canonicalized.insert(canonical_path);
             }
-            idx_grapheme = 0;
-        } else {
-            vec_grapheme[idx_grapheme] = *byte;
-            idx_grapheme += 1;
-            let first_byte = vec_grapheme[0];
-            if first_byte >= 240 && idx_grapheme == 4 {
-                // four bytes grapheme
-                let curr_char = std::char::from_u32(convert_four_to_u32(
-                    vec_grapheme[0],
-                    vec_grapheme[1],
-                    vec_grapheme[2],
-                    vec_grapheme[3],
-                ));
-                last_was_underscore = check_similar(curr_char, &mut new_name, last_was_underscore);
-                vec_grapheme = [0; 4];
-                idx_grapheme = 0;
-            } else if (224..240).contains(&first_byte) && idx_grapheme == 3 {
-                // three bytes grapheme
-                let curr_char = std::char::from_u32(convert_three_to_u32(
-                    vec_grapheme[0],
-                    vec_grapheme[1],
-                    vec_grapheme[2],
-                ));
-                last_was_underscore = check_similar(curr_char, &mut new_name, last_was_underscore);
-                vec_grapheme = [0; 4];
-                idx_grapheme = 0;
-            } else if (128..224).contains(&first_byte) && idx_grapheme == 2 {
-                // two bytes grapheme
-                let curr_char =
-                    std::char::from_u32(convert_two_to_u32(vec_grapheme[0], vec_grapheme[1]));
-                last_was_underscore = check_similar(curr_char, &mut new_name, last_was_underscore);
-                vec_grapheme = [0; 4];
-                idx_grapheme = 0;
+            Err(e) => errors.push(PathChange::Error {
+                path: one_path.clone(),
+                error: format!("Cannot canonicalize path: {}", e),
+            }),
+        }
+    }
+    errors.sort_by(|first, second| first.path().cmp(second.path()));
+    (canonicalized, errors)
+}
+
+/// Check if the walk of a directory reaches a path inside it
+///
+/// The walk does not go below `max_depth`, and does not enter the skipped entries,
+/// the directories treated as files and the links (without `follow_symlinks`)
+fn is_reached_by_walk(dir_path: &Path, path: &Path, options: &NotoxArgs) -> bool {
+    let Ok(relative) = path.strip_prefix(dir_path) else {
+        return false;
+    };
+    if is_opaque_dir(dir_path, options)
+        || options
+            .max_depth
+            .is_some_and(|max_depth| relative.components().count() > max_depth)
+    {
+        return false;
+    }
+    let mut current = dir_path.to_path_buf();
+    for component in relative.components() {
+        current.push(component);
+        if skip_reason(&current, options).is_some() {
+            return false;
+        }
+        let is_link = current
+            .symlink_metadata()
+            .is_ok_and(|metadata| metadata.file_type().is_symlink());
+        if current != path
+            && (is_opaque_dir(&current, options) || (is_link && !options.follow_symlinks))
+        {
+            return false;
+        }
+    }
+    true
+}
+
+/// Get the absolute path without the `.` and `..` components, the links are not resolved
+fn normalized_path(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
             }
+            other => normalized.push(other),
         }
     }
-    OsString::from(new_name)
+    normalized
 }
 
-/// Clean a path
-fn clean_path(file_path: &Path, options: &NotoxArgs) -> PathChange {
-    let file_name = match file_path.file_name() {
-        Some(name) => name,
-        None => {
-            return PathChange::Unchanged {
-                path: file_path.to_path_buf(),
-            };
+/// Remove the paths reached by the walk of another directory to check
+///
+/// They are cleaned with their parent directory, after it has been renamed,
+/// so processing them concurrently would use a path that does not exist anymore.
+/// The paths are compared in their normalized form, so `./dir` contains `dir/child`.
+/// The nested paths not reached (below `max_depth`, in a skipped entry...) are returned first,
+/// the deepest first: they are cleaned before the other paths, whose renames would change them.
+/// The remaining paths are sorted
+fn remove_nested_paths<'a>(
+    paths: &'a HashSet<PathBuf>,
+    options: &NotoxArgs,
+) -> (Vec<&'a PathBuf>, Vec<&'a PathBuf>) {
+    let mut dirs_to_check: HashMap<PathBuf, &PathBuf> = HashMap::new();
+    for one_path in paths.iter().filter(|one_path| one_path.is_dir()) {
+        dirs_to_check
+            .entry(normalized_path(one_path))
+            .and_modify(|kept| *kept = (*kept).min(one_path))
+            .or_insert(one_path);
+    }
+    let mut nested = Vec::new();
+    let mut remaining = Vec::new();
+    for one_path in paths {
+        let normalized = normalized_path(one_path);
+        let mut dirs = normalized
+            .ancestors()
+            .skip(1)
+            .filter_map(|ancestor| {
+                let dir_path = dirs_to_check.get(ancestor)?;
+                // the path as spelled by the walk of the directory
+                let walked = dir_path.join(normalized.strip_prefix(ancestor).ok()?);
+                Some((*dir_path, walked))
+            })
+            .peekable();
+        if dirs.peek().is_none() {
+            remaining.push(one_path);
+        } else if !dirs.any(|(dir_path, walked)| is_reached_by_walk(dir_path, &walked, options)) {
+            nested.push(one_path);
         }
-    };
-    let cleaned_name = clean_name(file_name, options);
-    if cleaned_name == file_name {
-        return PathChange::Unchanged {
-            path: file_path.to_path_buf(),
-        };
     }
-    let cleaned_path = file_path.with_file_name(cleaned_name);
-    if options.dry_run {
-        return PathChange::ErrorRename {
-            path: file_path.to_path_buf(),
-            modified: cleaned_path,
-            error: "dry-run".to_string(),
-        };
+    nested.sort_by(|first, second| {
+        second
+            .components()
+            .count()
+            .cmp(&first.components().count())
+            .then(first.cmp(second))
+    });
+    remaining.sort();
+    (nested, remaining)
+}
+
+/// Get the paths of the entries of a directory
+/// # Errors
+/// Return the error if the directory cannot be read, an unreadable directory is not an empty one
+#[inline(always)]
+fn get_path_of_dir(dir_path: &Path) -> Result<HashSet<PathBuf>, std::io::Error> {
+    Ok(std::fs::read_dir(dir_path)?
+        .filter_map(Result::ok)
+        .map(|e| e.path().to_path_buf())
+        .collect())
+}
+
+/// Get the paths of the entries of a directory to check, the error is printed if it cannot be read
+fn read_paths_to_check(dir_path: &Path) -> Result<HashSet<PathBuf>, ExitCode> {
+    get_path_of_dir(dir_path).map_err(|e| {
+        match dir_path == Path::new(".") {
+            true => println!("Cannot read the current directory: {}", e),
+            false => println!("Cannot read the directory {}: {}", dir_path.display(), e),
+        }
+        ExitCode::ReadError
+    })
+}
+
+/// Check if a name matches a pattern with `*` (any chars) and `?` (one char)
+///
+/// Like the shells, the wildcards do not match the leading dot of a hidden name
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    if name.first() == Some(&'.') && pattern.first() != Some(&'.') {
+        return false;
     }
-    match std::fs::rename(file_path, &cleaned_path) {
-        Ok(_) => PathChange::Changed {
-            path: file_path.to_path_buf(),
-            modified: cleaned_path,
-        },
-        Err(rename_error) => PathChange::ErrorRename {
-            path: file_path.to_path_buf(),
-            modified: cleaned_path,
-            error: rename_error.to_string(),
-        },
+    let (mut pattern_idx, mut name_idx) = (0, 0);
+    // position after the last `*` and the name position it is matched up to
+    let mut backtrack = None;
+    while name_idx < name.len() {
+        match pattern.get(pattern_idx) {
+            Some('*') => {
+                pattern_idx += 1;
+                backtrack = Some((pattern_idx, name_idx));
+            }
+            Some(one_char) if *one_char == '?' || *one_char == name[name_idx] => {
+                pattern_idx += 1;
+                name_idx += 1;
+            }
+            _ => match backtrack {
+                Some((star_pattern_idx, star_name_idx)) => {
+                    pattern_idx = star_pattern_idx;
+                    name_idx = star_name_idx + 1;
+                    backtrack = Some((star_pattern_idx, star_name_idx + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[pattern_idx..]
+        .iter()
+        .all(|one_char| *one_char == '*')
+}
+
+/// Reason of the entries skipped because of the `exclude` patterns
+const EXCLUDED_REASON: &str = "excluded";
+
+/// Reason of the hidden entries skipped because `clean_hidden` is false
+const HIDDEN_REASON: &str = "hidden";
+
+/// Reason of the backup directory, its copies keep their original names
+const BACKUP_REASON: &str = "backup directory";
+
+/// Get the reason to skip an entry found in a directory, `None` if it is cleaned
+fn skip_reason(path: &Path, options: &NotoxArgs) -> Option<&'static str> {
+    if is_excluded(path, &options.exclude) {
+        Some(EXCLUDED_REASON)
+    } else if is_backup_dir(path, options) {
+        Some(BACKUP_REASON)
+    } else if !options.clean_hidden
+        && path
+            .file_name()
+            .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+    {
+        Some(HIDDEN_REASON)
+    } else {
+        None
+    }
+}
+
+/// Check if an entry is the `backup_dir`, compared by their canonical paths
+///
+/// Only the entries with the name of the backup directory are canonicalized
+fn is_backup_dir(path: &Path, options: &NotoxArgs) -> bool {
+    options.backup_dir.as_ref().is_some_and(|backup_dir| {
+        path.file_name() == backup_dir.file_name()
+            && path.canonicalize().is_ok_and(|canonical_path| {
+                backup_dir
+                    .canonicalize()
+                    .is_ok_and(|canonical_backup| canonical_path == canonical_backup)
+            })
+    })
+}
+
+/// Get the absolute path of the backup directory, canonicalized if it already exists
+///
+/// The name of a relative path like `.` or `..` is only known once absolute
+fn absolute_backup_dir(backup_dir: &Path) -> PathBuf {
+    backup_dir
+        .canonicalize()
+        .or_else(|_| std::path::absolute(backup_dir))
+        .unwrap_or_else(|_| backup_dir.to_path_buf())
+}
+
+/// Check if a path matches one of the patterns, with `*` and `?` wildcards
+///
+/// A pattern without `/` is matched against the name (`node_modules`, `*.lock`),
+/// a pattern with `/` against the last components of the path (`docs/*.md`).
+pub fn is_excluded(path: &Path, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| {
+        let pattern_components: Vec<Vec<char>> = pattern
+            .split('/')
+            .filter(|component| !component.is_empty())
+            .map(|component| component.chars().collect())
+            .collect();
+        let path_components: Vec<Vec<char>> = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().chars().collect())
+            .collect();
+        path_components.len() >= pattern_components.len()
+            && !pattern_components.is_empty()
+            && pattern_components
+                .iter()
+                .rev()
+                .zip(path_components.iter().rev())
+                .all(|(pattern_component, path_component)| {
+                    wildcard_match(pattern_component, path_component)
+                })
+    })
+}
+
+/// Get the directories matching a glob pattern like `packages/*/src`
+///
+/// The wildcards are only expanded in the components that contain them
+fn expand_glob(pattern: &str) -> Vec<PathBuf> {
+    let pattern_path = Path::new(pattern);
+    let mut matches = vec![PathBuf::new()];
+    for component in pattern_path.components() {
+        let component = component.as_os_str();
+        let component_str = component.to_string_lossy();
+        if !component_str.contains(['*', '?']) {
+            matches = matches
+                .into_iter()
+                .map(|one_match| one_match.join(component))
+                .collect();
+            continue;
+        }
+        let component_chars: Vec<char> = component_str.chars().collect();
+        let mut next_matches = Vec::new();
+        for one_match in matches {
+            let dir = match one_match.as_os_str().is_empty() {
+                true => Path::new("."),
+                false => one_match.as_path(),
+            };
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            let mut names: Vec<OsString> = entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.file_name())
+                .filter(|name| {
+                    wildcard_match(
+                        &component_chars,
+                        &name.to_string_lossy().chars().collect::<Vec<char>>(),
+                    )
+                })
+                .collect();
+            names.sort();
+            next_matches.extend(names.into_iter().map(|name| one_match.join(name)));
+        }
+        matches = next_matches;
+    }
+    matches
+        .into_iter()
+        .filter(|one_match| one_match.is_dir())
+        .collect()
+}
+
+/// Version of the format of [`JsonOutput::JsonEnvelope`], bumped when a field changes
+#[cfg(feature = "serde")]
+pub const JSON_ENVELOPE_VERSION: u32 = 1;
+
+/// The results of a run with the versions needed to read them, see [`JsonOutput::JsonEnvelope`]
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonEnvelope<'a, T: serde::Serialize> {
+    /// see [`JSON_ENVELOPE_VERSION`]
+    version: u32,
+    /// version of notox
+    notox_version: &'static str,
+    /// see [`TRANSLITERATION_VERSION`]
+    transliteration_version: &'static str,
+    /// the changes
+    results: T,
+    /// the counts of the changes
+    summary: &'a Summary,
+}
+
+#[cfg(feature = "serde")]
+impl<'a, T: serde::Serialize> JsonEnvelope<'a, T> {
+    /// Wrap the results of a run
+    fn new(results: T, summary: &'a Summary) -> Self {
+        JsonEnvelope {
+            version: JSON_ENVELOPE_VERSION,
+            notox_version: env!("CARGO_PKG_VERSION"),
+            transliteration_version: transliteration_version(),
+            results,
+            summary,
+        }
     }
 }
 
-/// Clean a directory
-fn clean_directory(dir_path: &Path, options: &NotoxArgs) -> Vec<PathChange> {
-    let mut dir_path = dir_path.to_path_buf();
-    let mut result_vec = Vec::new();
-    let res_dir = clean_path(&dir_path, options);
-    if let PathChange::Changed { modified, .. } = &res_dir {
-        dir_path = modified.clone();
+/// Serialize a value to JSON, prettified or not
+#[cfg(feature = "serde")]
+fn to_json_string<T: serde::Serialize>(value: &T, pretty: bool) -> serde_json::Result<String> {
+    match pretty {
+        true => serde_json::to_string_pretty(value),
+        false => serde_json::to_string(value),
     }
-    result_vec.push(res_dir);
-    if let Ok(entries) = std::fs::read_dir(&dir_path) {
-        let ok_entries = {
-            #[cfg(feature = "rayon")]
-            {
-                use std::fs::DirEntry;
-                let (ok_entries, error_entries): (Vec<_>, Vec<_>) = entries
-                    .collect::<Vec<Result<DirEntry, std::io::Error>>>()
-                    .into_par_iter()
-                    .partition_map(|x| match x {
-                        Ok(entry) => Either::Left(entry),
-                        Err(e) => Either::Right(e),
-                    });
-                error_entries.into_iter().for_each(|e| {
-                    result_vec.push(PathChange::Error {
-                        path: dir_path.clone(),
-                        error: format!("Error reading dir entry of directory {}", e),
-                    })
-                });
-                ok_entries
-            }
-            #[cfg(not(feature = "rayon"))]
-            {
-                let mut ok_entries = Vec::new();
-                for entry in entries {
-                    match entry {
-                        Ok(e) => ok_entries.push(e),
-                        Err(e) => result_vec.push(PathChange::Error {
-                            path: dir_path.clone(),
-                            error: format!("Error reading dir entry of directory {}", e),
-                        }),
-                    }
+}
+
+/// Report an error while writing the output
+fn output_error(error: std::io::Error) -> ExitCode {
+    eprintln!("Cannot write output: {}", error);
+    ExitCode::OutputError
+}
+
+/// Writer converting `\n` line endings to `\r\n`
+struct CrlfWriter<W: Write> {
+    /// The wrapped writer
+    inner: W,
+}
+
+impl<W: Write> Write for CrlfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for line in buf.split_inclusive(|byte| *byte == b'\n') {
+            match line.strip_suffix(b"\n") {
+                Some(content) => {
+                    self.inner.write_all(content)?;
+                    self.inner.write_all(b"\r\n")?;
                 }
-                ok_entries
+                None => self.inner.write_all(line)?,
             }
-        };
-        #[cfg(feature = "rayon")]
-        let iter = ok_entries.par_iter();
-        #[cfg(not(feature = "rayon"))]
-        let iter = ok_entries.iter();
-        let mapped = iter
-            .map(|entry| {
-                let file_path = entry.path();
-                let is_entry_directory = match entry.file_type() {
-                    Ok(file_type) => file_type.is_dir(),
-                    Err(_) => false,
-                };
-                if is_entry_directory {
-                    clean_directory(&file_path, options)
-                } else {
-                    let res = clean_path(&file_path, options);
-                    vec![res]
-                }
-            })
-            .flatten()
-            .collect::<Vec<PathChange>>();
-        result_vec.extend(mapped);
-    } else {
-        result_vec.push(PathChange::Error {
-            path: dir_path,
-            error: "Error while reading directory".to_string(),
-        });
+        }
+        Ok(buf.len())
     }
-    result_vec
-}
 
-/// Get the path of a directory
-#[inline(always)]
-fn get_path_of_dir(dir_path: &str) -> HashSet<PathBuf> {
-    match std::fs::read_dir(dir_path) {
-        Ok(dir_entries) => dir_entries
-            .into_iter()
-            .filter_map(Result::ok)
-            .map(|e| e.path().to_path_buf())
-            .collect(),
-        Err(_) => HashSet::new(),
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
     }
 }
 
@@ -648,39 +4440,377 @@ fn show_version() {
     println!("notox {} by {}", &VERSION, &AUTHORS)
 }
 
+/// Get the version and the enabled features as JSON
+///
+/// `{"version":"1.0.0","features":["serde","rayon"]}`
+pub fn version_json() -> String {
+    let features = [
+        ("serde", cfg!(feature = "serde")),
+        ("rayon", cfg!(feature = "rayon")),
+        ("watch", cfg!(feature = "watch")),
+        ("trash", cfg!(feature = "trash")),
+        ("korean", cfg!(feature = "korean")),
+        ("regex", cfg!(feature = "regex")),
+        ("encoding", cfg!(feature = "encoding")),
+        ("normalize", cfg!(feature = "normalize")),
+        ("tokio", cfg!(feature = "tokio")),
+    ]
+    .iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| format!(r#""{}""#, name))
+    .collect::<Vec<String>>()
+    .join(",");
+    format!(
+        r#"{{"version":"{}","features":[{}]}}"#,
+        env!("CARGO_PKG_VERSION"),
+        features
+    )
+}
+
+/// Name of the config file read in the current directory by the binary when `--config` is not given, see [`Config`]
+#[cfg(feature = "serde")]
+pub const CONFIG_FILE_NAME: &str = ".notox.json";
+
+/// Default options read from a JSON config file, the flags of the command line override them
+///
+/// `{"dry_run": false, "output": "quiet", "exclude": ["node_modules", "*.lock"]}`
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// see [`NotoxArgs::dry_run`]
+    pub dry_run: Option<bool>,
+    /// see [`ConfigOutput`]
+    pub output: Option<ConfigOutput>,
+    /// added to the patterns of `--exclude`
+    pub exclude: Vec<String>,
+    /// added to the names of `--no-rename-dir`
+    pub no_rename_dirs: Vec<String>,
+    /// see [`NotoxArgs::max_depth`]
+    pub max_depth: Option<usize>,
+    /// see [`NotoxArgs::follow_symlinks`]
+    pub follow_symlinks: Option<bool>,
+    /// see [`NotoxArgs::clean_hidden`]
+    pub clean_hidden: Option<bool>,
+    /// see [`NotoxArgs::max_consecutive_separators`]
+    pub max_consecutive_separators: Option<usize>,
+    /// see [`NotoxArgs::dedupe_extension`]
+    pub dedupe_extension: Option<bool>,
+    /// see [`NotoxArgs::trim`]
+    pub trim: Option<bool>,
+    /// see [`NotoxArgs::keep_spaces`]
+    pub keep_spaces: Option<bool>,
+    /// see [`NotoxArgs::lowercase`]
+    pub lowercase: Option<bool>,
+}
+
+/// Output of a config file, written in kebab-case (`"json-pretty"`)
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigOutput {
+    /// [`Output::Default`]
+    Default,
+    /// [`Output::Quiet`]
+    Quiet,
+    /// [`Output::Csv`]
+    Csv,
+    /// [`JsonOutput::JsonDefault`]
+    Json,
+    /// [`JsonOutput::JsonDefault`], pretty printed
+    JsonPretty,
+    /// [`JsonOutput::JsonLines`]
+    JsonLines,
+}
+
+#[cfg(feature = "serde")]
+impl From<ConfigOutput> for Output {
+    fn from(config_output: ConfigOutput) -> Output {
+        let json_output = |json, pretty| Output::JsonOutput { json, pretty };
+        match config_output {
+            ConfigOutput::Default => Output::Default,
+            ConfigOutput::Quiet => Output::Quiet,
+            ConfigOutput::Csv => Output::Csv,
+            ConfigOutput::Json => json_output(JsonOutput::JsonDefault, false),
+            ConfigOutput::JsonPretty => json_output(JsonOutput::JsonDefault, true),
+            ConfigOutput::JsonLines => json_output(JsonOutput::JsonLines, false),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Config {
+    /// Read a config file
+    /// # Errors
+    /// Return the error message if the file cannot be read or is not a valid config
+    pub fn from_file(path: &Path) -> Result<Config, String> {
+        std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+            .map_err(|e| format!("Cannot read the config {}: {}", path.display(), e))
+    }
+
+    /// Read the config given with `--config`, or the default config if it exists
+    ///
+    /// The default config cannot disable the dry-run, only a config given with `--config` can
+    fn read(config_path: Option<&Path>, default_config: Option<&Path>) -> Result<Config, String> {
+        if let Some(config_path) = config_path {
+            return Config::from_file(config_path);
+        }
+        let Some(default_path) = default_config.filter(|default_path| default_path.is_file())
+        else {
+            return Ok(Config::default());
+        };
+        let config = Config::from_file(default_path)?;
+        if config.dry_run == Some(false) {
+            return Err(format!(
+                "The config {} cannot disable the dry-run, use -d or --config {}",
+                default_path.display(),
+                default_path.display()
+            ));
+        }
+        Ok(config)
+    }
+}
+
 /// Parse the arguments and return the options and the paths to check
+///
+/// Only the config file given with `--config` is read, see [`parse_args_with_config`]
 /// # Errors
 /// Return an error if the path is not found
-pub fn parse_args(args: &[String]) -> Result<(NotoxArgs, HashSet<PathBuf>), i32> {
-    let mut dry_run = true;
-    let mut output = Output::Default;
+pub fn parse_args(args: &[String]) -> Result<(NotoxArgs, HashSet<PathBuf>), ExitCode> {
+    parse_args_with_config(args, None)
+}
+
+/// Parse the arguments and return the options and the paths to check, with a default config file
+///
+/// The options of the config file are used as defaults, see [`Config`]. The `default_config` is read
+/// if it exists and `--config` is not given, it cannot disable the dry-run. No config is read with `--no-config`
+/// # Errors
+/// Return an error if the path is not found or the config cannot be read
+pub fn parse_args_with_config(
+    args: &[String],
+    default_config: Option<&Path>,
+) -> Result<(NotoxArgs, HashSet<PathBuf>), ExitCode> {
+    // the options of the config are only set if their flags are not given
+    let mut dry_run = None;
+    let mut output = None;
+    let mut no_rename_dirs: Vec<OsString> = Vec::new();
+    let mut skip_no_rename_dir_content = false;
+    let mut targets = CleanTargets::default();
+    let mut exclude: Vec<String> = Vec::new();
+    let mut max_depth = None;
+    let mut threads = None;
+    let mut chunk_size = None;
+    let mut follow_symlinks = None;
+    let mut clean_hidden = None;
+    let mut output_file = None;
+    let mut crlf = false;
+    let mut canonicalize_input = false;
+    let mut dedupe_extension = None;
+    let mut trim = None;
+    let mut preserve_extension = false;
+    let mut max_consecutive_separators = None;
+    let mut profile = Profile::Default;
+    let mut mode = CleanMode::Transliterate;
+    let mut categories = ALL_CATEGORIES.to_vec();
+    let mut non_utf8_policy = NonUtf8Policy::Single;
+    let mut empty_stem_placeholder = None;
+    let mut clobber_policy = ClobberPolicy::Refuse;
+    let mut on_collision = None;
+    let mut show_unchanged = false;
+    let mut include_metadata = false;
+    let mut quiet_unless_changes = false;
+    let mut no_summary = false;
+    let mut per_dir_summary = false;
+    let mut progress = false;
+    let mut treat_as_file_exts: Vec<String> = Vec::new();
+    let mut ext_allowed_chars: HashSet<char> = HashSet::new();
+    let mut allowed_extra: HashSet<char> = HashSet::new();
+    let mut journal = None;
+    let mut journal_done = HashSet::new();
+    let mut backup_dir = None;
+    let mut shell_safe = false;
+    let mut reject_over_len = None;
+    let mut max_length = None;
+    let mut keep_spaces = None;
+    let mut space_replacement = None;
+    let mut rename_budget = None;
+    let mut newer_than = None;
+    let mut older_than = None;
+    let mut lowercase = None;
+    let mut german_sharp_s = false;
+    let mut post_separator_case = None;
+    #[cfg(feature = "korean")]
+    let mut romanize_hangul = false;
+    #[cfg(feature = "regex")]
+    let mut redactions = Vec::new();
+    #[cfg(feature = "encoding")]
+    let mut source_encoding = None;
+    #[cfg(feature = "normalize")]
+    let mut normalize_nfc = false;
+    #[cfg(feature = "watch")]
+    let mut watch = None;
+    #[cfg(feature = "serde")]
+    let mut undo_changes = None;
+    let mut stdin = false;
+    let mut interactive = false;
+    let mut null_separated = false;
+    let mut check = false;
+    let mut stop_at_first_error = false;
+    // a path argument was given, even if it was not found
+    let mut path_args_given = false;
+    // a path argument was found
+    let mut path_args_found = false;
+    // the path arguments not found, reported once the output is known
+    let mut missing_paths = Vec::new();
+    #[cfg(feature = "serde")]
+    let mut config_path = None;
+    let mut no_config = false;
     let mut path_to_check: HashSet<PathBuf> = HashSet::new();
-    for one_arg in &args[1..] {
+    let mut args_iter = args[1..].iter();
+    while let Some(one_arg) = args_iter.next() {
         if one_arg == "-d" || one_arg == "--do" {
-            dry_run = false;
+            dry_run = Some(false);
+        } else if one_arg == "--dry-run" {
+            dry_run = Some(true);
+        } else if one_arg == "--no-config" {
+            no_config = true;
         } else if one_arg == "-h" || one_arg == "--help" {
             println!("Usage: notox [options] [path]");
             show_version();
             println!("Options:");
             println!("  -d, --do          Do the renaming");
+            println!("  --dry-run         Only print the renames, even if the config disables the dry-run");
             println!("  -h, --help        Show this help message");
             println!("  -v, --version     Show the version");
+            println!("  --version-json    Show the version and the enabled features in JSON");
             println!("  -p, --json-pretty Print the result in JSON format (pretty)");
             println!("  -e, --json-error  Print only the errors in JSON format");
             println!("  -j, --json        Print the result in JSON format");
+            println!(
+                "  --json-tree       Print the result in JSON format, nested like the directories"
+            );
+            println!("  --json-lines      Print each result as a JSON line as soon as it is done");
+            println!(
+                "  --json-envelope   Print the result and the summary in a versioned JSON object"
+            );
             println!("  -q, --quiet       Do not print anything");
-            return Err(1);
+            println!("  -i, --interactive Ask before each rename (y/N/a/q), with -d");
+            println!("  --check           Rename nothing, exit with code 4 if a name would change");
+            println!("  --fail-fast       Stop cleaning new paths after the first error");
+            println!("  --config <file>   Read the default options from a JSON file (default: .notox.json)");
+            println!("  --no-config       Do not read any config file");
+            println!("  --quiet-unless-changes  Do not print anything if nothing changed");
+            println!("  --no-summary      Do not print the summary line of the default output");
+            println!("  --per-dir-summary Print the counts of each directory before the total");
+            println!("  --progress        Print the number of paths processed on stderr");
+            println!("  --metadata        Add the size and mtime of the files to the JSON output");
+            println!("  --category-report Print the number of names per kind of transformation");
+            println!(
+                "  --csv             Print the result in CSV format (status,path,modified,error)"
+            );
+            println!(
+                "  --emit-script [sh|ps1]  Print a script doing the renames instead of renaming (default: sh)"
+            );
+            println!("  --compat-report   Print the original names rejected by FAT32, exFAT, NTFS, ext4 or APFS");
+            println!(
+                "  --report-encoding Print the encoding of the names (ascii, utf-8, invalid utf-8)"
+            );
+            println!("  --show-unchanged        Also print the unchanged paths and the links");
+            println!("  --preview-only-changed  Only print the changed paths (default)");
+            println!("  --no-rename-dir <name>  Do not rename directories with this name");
+            println!("  --skip-no-rename-dir-content Do not clean the content of these directories either");
+            println!("  --exclude <pattern>     Skip the entries matching the pattern (node_modules, *.lock)");
+            println!("  --max-depth <n>         Do not clean the entries deeper than n (0: only the given paths)");
+            println!("  --threads <n>           Number of threads used to clean the directories");
+            println!("  --chunk-size <n>        Minimum number of given paths cleaned by each thread task");
+            println!(
+                "  --follow-symlinks       Clean the content of the symbolic links to directories"
+            );
+            println!("  --files-only            Only rename the files, the directories are still traversed");
+            println!("  --dirs-only             Only rename the directories");
+            println!("  --skip-hidden           Skip the hidden entries (.git, .config) of the directories");
+            println!(
+                "  --include-hidden        Clean the hidden entries of the directories (default)"
+            );
+            println!(
+                "  --opaque-ext <exts>     Do not descend into directories with these extensions"
+            );
+            println!("  --output-file <path>    Write the output to a file instead of stdout");
+            println!("  --crlf                  Use CRLF line endings in the output file");
+            println!("  --resume <journal>      Record the renames and skip the ones already done");
+            println!(
+                "  --backup <dir>          Copy each file in this directory before renaming it"
+            );
+            println!("  --canonicalize          Canonicalize the paths before processing them");
+            println!("  --dedupe-extension      Remove a repeated extension (file.txt.txt)");
+            println!(
+                "  --trim                  Remove the separators around the dots and at both ends"
+            );
+            println!("  --preserve-extension    Clean the dots of the stem, keeping only the extension (my.file.txt)");
+            println!("  --ext-allowed-chars <chars>  Keep these chars in the extension (+#)");
+            println!(
+                "  --allow <chars>         Keep this ASCII punctuation in the whole name (+-)"
+            );
+            println!(
+                "  --max-separators <n>    Maximum number of consecutive separators (default 1)"
+            );
+            println!("  --max-name-bytes <n>    Do not rename the names longer than n bytes");
+            println!(
+                "  --max-length <n>        Truncate the stem of the names longer than n bytes"
+            );
+            println!(
+                "  --max-renames <n>       Stop renaming after n renames, the rest is skipped"
+            );
+            println!(
+                "  --newer-than <time>     Only rename the entries modified after (7d, 2024-01-31)"
+            );
+            println!("  --older-than <time>     Only rename the entries modified before (1y, 2024-01-31)");
+            println!("  --lowercase       Lowercase the cleaned names");
+            println!("  --german-sharp-s  Transliterate ß to ss instead of s");
+            println!("  --post-separator-case <case> Case of the letters after a separator (upper, lower)");
+            println!("  --profile <name>        Naming rules of the storage (default, sharepoint, windows, posix)");
+            println!(
+                "  --mode <mode>           Clean the non-ASCII chars (transliterate, unsafe-only)"
+            );
+            println!("  --strip-accents-only    Only transliterate accents, keep the other chars");
+            println!("  --shell-safe            No shell metacharacters and no leading -");
+            println!(
+                "  --keep-spaces           Keep the spaces, a run of spaces becomes one space"
+            );
+            println!("  --space-replacement <char>  Replace the whitespaces by this char (-)");
+            println!("  --non-utf8 <policy>     Separators for invalid UTF-8 (single, per-byte)");
+            println!("  --empty-placeholder <name>  Stem used when nothing is left of it");
+            println!(
+                "  --clobber <policy>      Existing rename targets (refuse, overwrite, trash)"
+            );
+            println!("  --on-collision <policy> Cleaned names already taken (error, suffix, skip)");
+            println!("  --romanize-hangul       Romanize the Hangul syllables (한국 -> hanguk)");
+            println!("  --redact <regex>        Replace the matching substrings by REDACTED");
+            println!("  --from-encoding <label> Transcode the names not valid in UTF-8 (latin1, shift_jis...)");
+            println!("  --nfc                   Normalize the names to NFC before cleaning them");
+            println!("  --watch <dir>           Watch a directory and clean the new files");
+            println!(
+                "  --undo <file.json>      Undo the renames of a previous run saved with --json"
+            );
+            println!("  --stdin                 Clean the names read from stdin, without renaming");
+            println!("  -0, --null              Read NUL-separated paths from stdin, print NUL-separated pairs");
+            return Err(ExitCode::Usage);
         } else if one_arg == "-v" || one_arg == "--version" {
             show_version();
-            return Err(1);
+            return Err(ExitCode::Usage);
+        } else if one_arg == "--version-json" {
+            println!("{}", version_json());
+            return Err(ExitCode::Usage);
         } else if one_arg == "-p" || one_arg == "--json-pretty" {
             #[cfg(feature = "serde")]
             {
-                output = match output {
-                    Output::JsonOutput {
+                output = Some(match output {
+                    Some(Output::JsonOutput {
                         json: json_output,
                         pretty: _,
-                    } => Output::JsonOutput {
+                    }) => Output::JsonOutput {
                         json: json_output,
                         pretty: true,
                     },
@@ -688,68 +4818,874 @@ pub fn parse_args(args: &[String]) -> Result<(NotoxArgs, HashSet<PathBuf>), i32>
                         json: JsonOutput::JsonDefault,
                         pretty: true,
                     },
-                };
+                });
+            }
+            #[cfg(not(feature = "serde"))]
+            {
+                println!("JSON output is not available, please use a notox version with the 'serde' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "-e" || one_arg == "--json-error" {
+            #[cfg(feature = "serde")]
+            {
+                output = Some(match output {
+                    Some(Output::JsonOutput { json: _, pretty }) => Output::JsonOutput {
+                        json: JsonOutput::JsonOnlyError,
+                        pretty,
+                    },
+                    _ => Output::JsonOutput {
+                        json: JsonOutput::JsonOnlyError,
+                        pretty: false,
+                    },
+                });
+            }
+            #[cfg(not(feature = "serde"))]
+            {
+                println!("JSON output is not available, please use a notox version with the 'serde' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "-j" || one_arg == "--json" {
+            #[cfg(feature = "serde")]
+            {
+                output = Some(match output {
+                    Some(Output::JsonOutput { json: _, pretty }) => Output::JsonOutput {
+                        json: JsonOutput::JsonDefault,
+                        pretty,
+                    },
+                    _ => Output::JsonOutput {
+                        json: JsonOutput::JsonDefault,
+                        pretty: false,
+                    },
+                });
+            }
+            #[cfg(not(feature = "serde"))]
+            {
+                println!("JSON output is not available, please use a notox version with the 'serde' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "--json-envelope" {
+            #[cfg(feature = "serde")]
+            {
+                output = Some(match output {
+                    Some(Output::JsonOutput { json: _, pretty }) => Output::JsonOutput {
+                        json: JsonOutput::JsonEnvelope,
+                        pretty,
+                    },
+                    _ => Output::JsonOutput {
+                        json: JsonOutput::JsonEnvelope,
+                        pretty: false,
+                    },
+                });
+            }
+            #[cfg(not(feature = "serde"))]
+            {
+                println!("JSON output is not available, please use a notox version with the 'serde' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "--json-tree" {
+            #[cfg(feature = "serde")]
+            {
+                output = Some(match output {
+                    Some(Output::JsonOutput { json: _, pretty }) => Output::JsonOutput {
+                        json: JsonOutput::JsonTree,
+                        pretty,
+                    },
+                    _ => Output::JsonOutput {
+                        json: JsonOutput::JsonTree,
+                        pretty: false,
+                    },
+                });
+            }
+            #[cfg(not(feature = "serde"))]
+            {
+                println!("JSON output is not available, please use a notox version with the 'serde' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "--json-lines" {
+            #[cfg(feature = "serde")]
+            {
+                output = Some(match output {
+                    Some(Output::JsonOutput { json: _, pretty }) => Output::JsonOutput {
+                        json: JsonOutput::JsonLines,
+                        pretty,
+                    },
+                    _ => Output::JsonOutput {
+                        json: JsonOutput::JsonLines,
+                        pretty: false,
+                    },
+                });
+            }
+            #[cfg(not(feature = "serde"))]
+            {
+                println!("JSON output is not available, please use a notox version with the 'serde' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "-i" || one_arg == "--interactive" {
+            interactive = true;
+        } else if one_arg == "--check" {
+            check = true;
+        } else if one_arg == "-q" || one_arg == "--quiet" {
+            output = Some(Output::Quiet);
+        } else if one_arg == "--csv" {
+            output = Some(Output::Csv);
+        } else if one_arg == "--emit-script" {
+            // the dialect is optional
+            let dialect = match args_iter.as_slice().first().map(String::as_str) {
+                Some("sh") => Some(ScriptDialect::Sh),
+                Some("ps1") => Some(ScriptDialect::PowerShell),
+                _ => None,
+            };
+            if dialect.is_some() {
+                args_iter.next();
+            }
+            output = Some(Output::Script(dialect.unwrap_or_default()));
+        } else if one_arg == "--category-report" {
+            output = Some(Output::CategoryReport);
+        } else if one_arg == "--compat-report" {
+            output = Some(Output::CompatReport);
+        } else if one_arg == "--report-encoding" {
+            output = Some(Output::EncodingClass);
+        } else if one_arg == "--skip-no-rename-dir-content" {
+            skip_no_rename_dir_content = true;
+        } else if one_arg == "--no-rename-dir" {
+            match args_iter.next() {
+                Some(dir_name) => no_rename_dirs.push(OsString::from(dir_name)),
+                None => {
+                    println!("Missing directory name after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+        } else if one_arg == "--exclude" {
+            match args_iter.next() {
+                Some(pattern) if !pattern.is_empty() => exclude.push(pattern.clone()),
+                _ => {
+                    println!("Missing pattern after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+        } else if one_arg == "--max-depth" {
+            match args_iter.next().map(|value| value.parse::<usize>()) {
+                Some(Ok(value)) => max_depth = Some(value),
+                _ => {
+                    println!("Missing or invalid number after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+        } else if one_arg == "--threads" {
+            match args_iter.next().map(|value| value.parse::<usize>()) {
+                Some(Ok(value)) if value > 0 => threads = Some(value),
+                _ => {
+                    println!("Missing or invalid number of threads after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+        } else if one_arg == "--chunk-size" {
+            match args_iter.next().map(|value| value.parse::<usize>()) {
+                Some(Ok(value)) if value > 0 => chunk_size = Some(value),
+                _ => {
+                    println!("Missing or invalid number of paths after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+        } else if one_arg == "--files-only" {
+            targets = CleanTargets {
+                files: true,
+                dirs: false,
+            };
+        } else if one_arg == "--dirs-only" {
+            targets = CleanTargets {
+                files: false,
+                dirs: true,
+            };
+        } else if one_arg == "--follow-symlinks" {
+            follow_symlinks = Some(true);
+        } else if one_arg == "--skip-hidden" {
+            clean_hidden = Some(false);
+        } else if one_arg == "--include-hidden" {
+            clean_hidden = Some(true);
+        } else if one_arg == "--output-file" {
+            match args_iter.next() {
+                Some(file_path) => output_file = Some(PathBuf::from(file_path)),
+                None => {
+                    println!("Missing file path after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+        } else if one_arg == "--resume" {
+            let Some(journal_path) = args_iter.next().map(PathBuf::from) else {
+                println!("Missing journal path after {}", one_arg);
+                return Err(ExitCode::Usage);
+            };
+            if journal_path.exists() {
+                match read_journal(&journal_path) {
+                    Ok(done) => journal_done = done,
+                    Err(e) => {
+                        println!("Cannot read journal {}: {}", journal_path.display(), e);
+                        return Err(ExitCode::Usage);
+                    }
+                }
+            }
+            journal = Some(journal_path);
+        } else if one_arg == "--backup" {
+            let Some(dir_path) = args_iter.next() else {
+                println!("Missing directory after {}", one_arg);
+                return Err(ExitCode::Usage);
+            };
+            backup_dir = Some(absolute_backup_dir(Path::new(dir_path)));
+        } else if one_arg == "--crlf" {
+            crlf = true;
+        } else if one_arg == "--canonicalize" {
+            canonicalize_input = true;
+        } else if one_arg == "--dedupe-extension" {
+            dedupe_extension = Some(true);
+        } else if one_arg == "--trim" {
+            trim = Some(true);
+        } else if one_arg == "--preserve-extension" {
+            preserve_extension = true;
+        } else if one_arg == "--watch" {
+            #[cfg(feature = "watch")]
+            match args_iter.next() {
+                Some(dir_path) => watch = Some(PathBuf::from(dir_path)),
+                None => {
+                    println!("Missing directory after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+            #[cfg(not(feature = "watch"))]
+            {
+                println!("Watch mode is not available, please use a notox version with the 'watch' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "--stdin" {
+            stdin = true;
+        } else if one_arg == "-0" || one_arg == "--null" {
+            null_separated = true;
+        } else if one_arg == "--fail-fast" {
+            stop_at_first_error = true;
+        } else if one_arg == "--config" {
+            let Some(file_path) = args_iter.next() else {
+                println!("Missing file path after {}", one_arg);
+                return Err(ExitCode::Usage);
+            };
+            #[cfg(feature = "serde")]
+            {
+                config_path = Some(PathBuf::from(file_path));
+            }
+            #[cfg(not(feature = "serde"))]
+            {
+                let _ = file_path;
+                println!("The config file is not available, please use a notox version with the 'serde' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "--undo" {
+            let Some(undo_path) = args_iter.next() else {
+                println!("Missing JSON file after {}", one_arg);
+                return Err(ExitCode::Usage);
+            };
+            #[cfg(feature = "serde")]
+            match std::fs::read_to_string(undo_path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| {
+                    serde_json::from_str::<Vec<PathChange>>(&content).map_err(|e| e.to_string())
+                }) {
+                Ok(changes) => undo_changes = Some(changes),
+                Err(e) => {
+                    println!("Cannot read the changes of {}: {}", undo_path, e);
+                    return Err(ExitCode::Usage);
+                }
+            }
+            #[cfg(not(feature = "serde"))]
+            {
+                println!("The undo of {} is not available, please use a notox version with the 'serde' feature.", undo_path);
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "--ext-allowed-chars" {
+            match args_iter.next() {
+                Some(value) => ext_allowed_chars.extend(value.chars()),
+                None => {
+                    println!("Missing characters after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+        } else if one_arg == "--allow" {
+            match args_iter.next() {
+                Some(value)
+                    if !value.is_empty()
+                        && value
+                            .chars()
+                            .all(|one_char| one_char.is_ascii_punctuation() && one_char != '_') =>
+                {
+                    allowed_extra.extend(value.chars())
+                }
+                Some(value) => {
+                    println!(
+                        "Invalid characters after {}: {:?}, only the ASCII punctuation except the separator _ can be kept",
+                        one_arg, value
+                    );
+                    return Err(ExitCode::Usage);
+                }
+                None => {
+                    println!("Missing characters after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+        } else if one_arg == "--opaque-ext" {
+            match args_iter.next() {
+                Some(value) => treat_as_file_exts.extend(
+                    value
+                        .split(',')
+                        .map(|ext| ext.trim().trim_start_matches('.'))
+                        .filter(|ext| !ext.is_empty())
+                        .map(str::to_string),
+                ),
+                None => {
+                    println!("Missing extensions after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+        } else if one_arg == "--quiet-unless-changes" {
+            quiet_unless_changes = true;
+        } else if one_arg == "--no-summary" {
+            no_summary = true;
+        } else if one_arg == "--progress" {
+            progress = true;
+        } else if one_arg == "--per-dir-summary" {
+            per_dir_summary = true;
+        } else if one_arg == "--metadata" {
+            include_metadata = true;
+        } else if one_arg == "--show-unchanged" {
+            show_unchanged = true;
+        } else if one_arg == "--preview-only-changed" {
+            show_unchanged = false;
+        } else if one_arg == "--redact" {
+            #[cfg(feature = "regex")]
+            match args_iter.next().map(|value| regex::Regex::new(value)) {
+                Some(Ok(pattern)) => redactions.push(Redaction {
+                    pattern,
+                    replacement: REDACTED_TOKEN.to_string(),
+                }),
+                Some(Err(e)) => {
+                    println!("Invalid pattern after {}: {}", one_arg, e);
+                    return Err(ExitCode::Usage);
+                }
+                None => {
+                    println!("Missing pattern after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+            #[cfg(not(feature = "regex"))]
+            {
+                println!("Redaction is not available, please use a notox version with the 'regex' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "--from-encoding" {
+            #[cfg(feature = "encoding")]
+            match args_iter.next() {
+                Some(label) => match encoding_rs::Encoding::for_label(label.as_bytes()) {
+                    Some(encoding) => source_encoding = Some(encoding),
+                    None => {
+                        println!("Unknown encoding after {}: {}", one_arg, label);
+                        return Err(ExitCode::Usage);
+                    }
+                },
+                None => {
+                    println!("Missing encoding after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            }
+            #[cfg(not(feature = "encoding"))]
+            {
+                println!("Transcoding is not available, please use a notox version with the 'encoding' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "--nfc" {
+            #[cfg(feature = "normalize")]
+            {
+                normalize_nfc = true;
+            }
+            #[cfg(not(feature = "normalize"))]
+            {
+                println!("Normalization is not available, please use a notox version with the 'normalize' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "--romanize-hangul" {
+            #[cfg(feature = "korean")]
+            {
+                romanize_hangul = true;
+            }
+            #[cfg(not(feature = "korean"))]
+            {
+                println!("Hangul romanization is not available, please use a notox version with the 'korean' feature.");
+                return Err(ExitCode::FeatureUnavailable);
+            }
+        } else if one_arg == "--keep-spaces" {
+            keep_spaces = Some(true);
+        } else if one_arg == "--space-replacement" {
+            let value_chars = args_iter
+                .next()
+                .map(|value| value.chars().collect::<Vec<char>>());
+            match value_chars.as_deref() {
+                Some(&[replacement])
+                    if (replacement.is_ascii_graphic() || replacement == ' ')
+                        && replacement != '/' =>
+                {
+                    space_replacement = Some(replacement)
+                }
+                _ => {
+                    println!(
+                        "Missing or invalid char after {}, one ASCII char (not /) is expected",
+                        one_arg
+                    );
+                    return Err(ExitCode::Usage);
+                }
+            }
+        } else if one_arg == "--shell-safe" {
+            shell_safe = true;
+        } else if one_arg == "--strip-accents-only" {
+            categories = vec![Category::Accents];
+        } else if one_arg == "--profile" {
+            profile = match args_iter
+                .next()
+                .map(|value| value.to_lowercase())
+                .as_deref()
+            {
+                Some("default") => Profile::Default,
+                Some("sharepoint") | Some("onedrive") => Profile::SharePoint,
+                Some("windows") => Profile::WindowsSafe,
+                Some("posix") => Profile::PortablePosix,
+                _ => {
+                    println!("Missing or unknown profile after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            };
+        } else if one_arg == "--mode" {
+            mode = match args_iter
+                .next()
+                .map(|value| value.to_lowercase())
+                .as_deref()
+            {
+                Some("transliterate") => CleanMode::Transliterate,
+                Some("unsafe-only") => CleanMode::UnsafeOnly,
+                _ => {
+                    println!("Missing or unknown mode after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            };
+        } else if one_arg == "--lowercase" {
+            lowercase = Some(true);
+        } else if one_arg == "--german-sharp-s" {
+            german_sharp_s = true;
+        } else if one_arg == "--post-separator-case" {
+            post_separator_case = match args_iter.next().map(String::as_str) {
+                Some("upper") => Some(CaseMode::Upper),
+                Some("lower") => Some(CaseMode::Lower),
+                _ => {
+                    println!("Missing or unknown case after {} (upper, lower)", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            };
+        } else if one_arg == "--non-utf8" {
+            non_utf8_policy = match args_iter.next().map(String::as_str) {
+                Some("single") => NonUtf8Policy::Single,
+                Some("per-byte") => NonUtf8Policy::PerByte,
+                _ => {
+                    println!("Missing or unknown policy after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            };
+        } else if one_arg == "--empty-placeholder" {
+            match args_iter.next() {
+                Some(value) if !value.is_empty() => empty_stem_placeholder = Some(value.clone()),
+                _ => {
+                    println!("Missing placeholder after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
             }
-            #[cfg(not(feature = "serde"))]
+        } else if one_arg == "--clobber" {
+            clobber_policy = match args_iter.next().map(String::as_str) {
+                Some("refuse") => ClobberPolicy::Refuse,
+                Some("overwrite") => ClobberPolicy::Overwrite,
+                Some("trash") => {
+                    #[cfg(feature = "trash")]
+                    {
+                        ClobberPolicy::Trash
+                    }
+                    #[cfg(not(feature = "trash"))]
+                    {
+                        println!("The trash is not available, please use a notox version with the 'trash' feature.");
+                        return Err(ExitCode::FeatureUnavailable);
+                    }
+                }
+                _ => {
+                    println!("Missing or unknown policy after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
+            };
+        } else if one_arg == "--on-collision" {
+            on_collision = match args_iter.next().map(String::as_str) {
+                Some("error") => Some(CollisionPolicy::Error),
+                Some("suffix") => Some(CollisionPolicy::Suffix),
+                Some("skip") => Some(CollisionPolicy::Skip),
+                _ => {
+                    println!(
+                        "Missing or unknown policy after {} (error, suffix, skip)",
+                        one_arg
+                    );
+                    return Err(ExitCode::Usage);
+                }
+            };
+        } else if one_arg == "--newer-than" || one_arg == "--older-than" {
+            match args_iter
+                .next()
+                .and_then(|value| parse_time_limit(value, SystemTime::now()))
             {
-                println!("JSON output is not available, please use a notox version with the 'serde' feature.");
-                return Err(2);
+                Some(limit) if one_arg == "--newer-than" => newer_than = Some(limit),
+                Some(limit) => older_than = Some(limit),
+                None => {
+                    println!(
+                        "Missing or invalid time after {} (like 7d or 2024-01-31)",
+                        one_arg
+                    );
+                    return Err(ExitCode::Usage);
+                }
             }
-        } else if one_arg == "-e" || one_arg == "--json-error" {
-            #[cfg(feature = "serde")]
-            {
-                output = match output {
-                    Output::JsonOutput { json: _, pretty } => Output::JsonOutput {
-                        json: JsonOutput::JsonOnlyError,
-                        pretty,
-                    },
-                    _ => Output::JsonOutput {
-                        json: JsonOutput::JsonOnlyError,
-                        pretty: false,
-                    },
-                };
+        } else if one_arg == "--max-renames" {
+            match args_iter.next().map(|value| value.parse::<usize>()) {
+                Some(Ok(value)) => rename_budget = Some(value),
+                _ => {
+                    println!("Missing or invalid number after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
             }
-            #[cfg(not(feature = "serde"))]
-            {
-                println!("JSON output is not available, please use a notox version with the 'serde' feature.");
-                return Err(2);
+        } else if one_arg == "--max-name-bytes" {
+            match args_iter.next().map(|value| value.parse::<usize>()) {
+                Some(Ok(value)) => reject_over_len = Some(value),
+                _ => {
+                    println!("Missing or invalid number after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
             }
-        } else if one_arg == "-j" || one_arg == "--json" {
-            #[cfg(feature = "serde")]
-            {
-                output = match output {
-                    Output::JsonOutput { json: _, pretty } => Output::JsonOutput {
-                        json: JsonOutput::JsonDefault,
-                        pretty,
-                    },
-                    _ => Output::JsonOutput {
-                        json: JsonOutput::JsonDefault,
-                        pretty: false,
-                    },
-                };
+        } else if one_arg == "--max-length" {
+            match args_iter.next().map(|value| value.parse::<usize>()) {
+                Some(Ok(value)) if value > 0 => max_length = Some(value),
+                _ => {
+                    println!("Missing or invalid number after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
             }
-            #[cfg(not(feature = "serde"))]
-            {
-                println!("JSON output is not available, please use a notox version with the 'serde' feature.");
-                return Err(2);
+        } else if one_arg == "--max-separators" {
+            match args_iter.next().map(|value| value.parse::<usize>()) {
+                Some(Ok(value)) => max_consecutive_separators = Some(value),
+                _ => {
+                    println!("Missing or invalid number after {}", one_arg);
+                    return Err(ExitCode::Usage);
+                }
             }
-        } else if one_arg == "-q" || one_arg == "--quiet" {
-            output = Output::Quiet;
+        } else if one_arg.trim().is_empty() {
+            // likely a broken script, do not fall back to the current directory
+            println!("Invalid empty path argument: {:?}", one_arg);
+            return Err(ExitCode::Usage);
         } else if one_arg == "*" {
             // should not happen with most shells
-            let paths = get_path_of_dir(".");
-            path_to_check.extend(paths);
+            path_args_given = true;
+            path_args_found = true;
+            path_to_check.extend(read_paths_to_check(Path::new("."))?);
         } else if std::fs::metadata(one_arg).is_ok() {
+            path_args_given = true;
+            path_args_found = true;
             path_to_check.insert(PathBuf::from(one_arg));
-        } else if output.is_verbose() {
-            println!("Cannot find path: {}", one_arg);
+        } else if one_arg.contains(['*', '?']) {
+            // glob not expanded by the shell, only the entries of the matching directories are cleaned
+            path_args_given = true;
+            let dirs = expand_glob(one_arg);
+            if dirs.is_empty() {
+                println!("No directory matches: {}", one_arg);
+                return Err(ExitCode::Usage);
+            }
+            path_args_found = true;
+            for one_dir in dirs {
+                path_to_check.extend(read_paths_to_check(&one_dir)?);
+            }
+        } else {
+            path_args_given = true;
+            missing_paths.push(one_arg);
+        }
+    }
+    #[cfg(feature = "serde")]
+    {
+        let config = match no_config {
+            true => Config::default(),
+            false => match Config::read(config_path.as_deref(), default_config) {
+                Ok(config) => config,
+                Err(e) => {
+                    println!("{}", e);
+                    return Err(ExitCode::Usage);
+                }
+            },
+        };
+        dry_run = dry_run.or(config.dry_run);
+        output = output.or(config.output.map(Output::from));
+        exclude.splice(0..0, config.exclude);
+        no_rename_dirs.splice(0..0, config.no_rename_dirs.into_iter().map(OsString::from));
+        max_depth = max_depth.or(config.max_depth);
+        follow_symlinks = follow_symlinks.or(config.follow_symlinks);
+        clean_hidden = clean_hidden.or(config.clean_hidden);
+        max_consecutive_separators =
+            max_consecutive_separators.or(config.max_consecutive_separators);
+        dedupe_extension = dedupe_extension.or(config.dedupe_extension);
+        trim = trim.or(config.trim);
+        keep_spaces = keep_spaces.or(config.keep_spaces);
+        lowercase = lowercase.or(config.lowercase);
+    }
+    #[cfg(not(feature = "serde"))]
+    let _ = (no_config, default_config);
+    let mut dry_run = dry_run.unwrap_or(true);
+    let output = output.unwrap_or(Output::Default);
+    if output.is_verbose() {
+        for one_path in missing_paths {
+            println!("Cannot find path: {}", one_path);
+        }
+    }
+    if path_args_given && !path_args_found {
+        println!("None of the given paths was found");
+        return Err(ExitCode::NoPathsFound);
+    }
+    if interactive && !matches!(output, Output::Default) {
+        println!("The interactive mode needs the default output, it cannot be used with --quiet, the JSON outputs or the reports");
+        return Err(ExitCode::Usage);
+    }
+    if check || matches!(output, Output::Script(_)) {
+        // the check and the script never rename, even with -d
+        dry_run = true;
+    }
+    if null_separated {
+        if stdin || interactive {
+            println!("The paths are read from stdin with -0, it cannot be used with --stdin or --interactive");
+            return Err(ExitCode::Usage);
+        }
+        let paths = read_null_paths(std::io::stdin().lock()).map_err(|e| {
+            println!("Cannot read the paths from stdin: {}", e);
+            ExitCode::ReadError
+        })?;
+        for one_path in paths {
+            match one_path.symlink_metadata() {
+                Ok(_) => {
+                    path_to_check.insert(one_path);
+                }
+                // stdout only gets the NUL-separated pairs
+                Err(_) if output.is_verbose() => {
+                    eprintln!("Cannot find path: {}", one_path.display())
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    // with -0, no path read means nothing to do, like `xargs -0 -r`,
+    // and the current directory is never cleaned instead of the given paths
+    if path_to_check.is_empty() && !null_separated && !path_args_given {
+        path_to_check.extend(read_paths_to_check(Path::new("."))?);
+    }
+    Ok((
+        NotoxArgs {
+            dry_run,
+            output,
+            no_rename_dirs,
+            skip_no_rename_dir_content,
+            targets,
+            exclude,
+            max_depth,
+            threads,
+            chunk_size,
+            follow_symlinks: follow_symlinks.unwrap_or(false),
+            clean_hidden: clean_hidden.unwrap_or(true),
+            output_file,
+            crlf,
+            canonicalize_input,
+            dedupe_extension: dedupe_extension.unwrap_or(false),
+            trim: trim.unwrap_or(false),
+            preserve_extension,
+            max_consecutive_separators: max_consecutive_separators.unwrap_or(1),
+            profile,
+            mode,
+            categories,
+            non_utf8_policy,
+            empty_stem_placeholder,
+            clobber_policy,
+            on_collision,
+            show_unchanged,
+            include_metadata,
+            quiet_unless_changes,
+            no_summary,
+            per_dir_summary,
+            progress,
+            treat_as_file_exts,
+            ext_allowed_chars,
+            allowed_extra,
+            journal,
+            journal_done,
+            backup_dir,
+            shell_safe,
+            reject_over_len,
+            max_length,
+            keep_spaces: keep_spaces.unwrap_or(false),
+            space_replacement,
+            rename_budget,
+            newer_than,
+            older_than,
+            lowercase: lowercase.unwrap_or(false),
+            post_separator_case,
+            overrides: HashMap::new(),
+            german_sharp_s,
+            #[cfg(feature = "korean")]
+            romanize_hangul,
+            #[cfg(feature = "regex")]
+            redactions,
+            #[cfg(feature = "encoding")]
+            source_encoding,
+            #[cfg(feature = "normalize")]
+            normalize_nfc,
+            #[cfg(feature = "watch")]
+            watch,
+            #[cfg(feature = "serde")]
+            undo: undo_changes,
+            stdin,
+            interactive,
+            null_separated,
+            check,
+            stop_at_first_error,
+        },
+        path_to_check,
+    ))
+}
+
+/// Apply the renames of previously computed changes, like a plan made with the dry-run
+///
+/// The `Changed` entries and the renames not done because of the dry-run are renamed again,
+/// respecting `dry_run` and `clobber_policy`, the other entries are ignored.
+/// The entries of a directory renamed before them are found under its new path.
+/// A source that does not exist anymore gives an error, the plan is outdated.
+pub fn apply_changes(changes: &[PathChange], args: &NotoxArgs) -> Vec<PathChange> {
+    let mut renamed_dirs: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut results = Vec::new();
+    for one_change in changes {
+        let (path, modified) = match one_change {
+            PathChange::Changed { path, modified } => (path, modified),
+            PathChange::WouldChange { path, modified } => (path, modified),
+            _ => continue,
+        };
+        let current_path = |planned: &Path| {
+            renamed_dirs
+                .iter()
+                .fold(planned.to_path_buf(), |acc, (old, new)| {
+                    match acc.strip_prefix(old) {
+                        Ok(rest) => new.join(rest),
+                        Err(_) => acc,
+                    }
+                })
+        };
+        let (path, modified) = (current_path(path), current_path(modified));
+        if path.symlink_metadata().is_err() {
+            results.push(PathChange::Error {
+                path,
+                error: "source not found, the plan is outdated".to_string(),
+            });
+            continue;
+        }
+        if args.dry_run {
+            results.push(dry_run_change(&path, modified, args));
+            continue;
+        }
+        let renames = rename_path(&path, modified.clone(), args);
+        if matches!(renames.last(), Some(PathChange::Changed { .. })) && modified.is_dir() {
+            renamed_dirs.push((path, modified));
+        }
+        results.extend(renames);
+    }
+    results
+}
+
+/// Clean the names of the paths read line by line, without touching the filesystem
+///
+/// Only the file name of each path is cleaned, like the renames of [`Notox::run`].
+/// A cleaned name gives a [`PathChange::WouldChange`] since nothing is renamed.
+/// The empty lines are skipped, the lines not valid in UTF-8 are cleaned like the names on the disk
+/// # Errors
+/// Return an error if the reader fails
+pub fn clean_lines<R: std::io::BufRead>(
+    reader: R,
+    options: &NotoxArgs,
+) -> std::io::Result<Vec<PathChange>> {
+    let mut changes = Vec::new();
+    for line in reader.split(b'\n') {
+        let line = line?;
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+        if line.is_empty() {
+            continue;
         }
+        let path = journal_path(line);
+        let Some(name) = path.file_name() else {
+            changes.push(PathChange::Unchanged { path });
+            continue;
+        };
+        let cleaned = clean_name(name, options);
+        changes.push(match cleaned == name {
+            true => PathChange::Unchanged { path },
+            false => PathChange::WouldChange {
+                modified: path.with_file_name(cleaned),
+                path,
+            },
+        });
     }
-    if path_to_check.is_empty() {
-        let paths = get_path_of_dir(".");
-        path_to_check.extend(paths);
+    Ok(changes)
+}
+
+/// Undo the renames of previously computed changes, like the JSON output of a run
+///
+/// Each `Changed` entry is renamed from its modified path back to its path, respecting `dry_run`,
+/// the other entries are ignored. The results of [`Notox::run`] list a directory before its entries,
+/// so the changes are undone in reverse order to rename the entries back before their directory.
+/// A modified path that does not exist anymore, or an original path that exists again, gives an error.
+pub fn undo(changes: &[PathChange], options: &NotoxArgs) -> Vec<PathChange> {
+    let mut results = Vec::new();
+    for one_change in changes.iter().rev() {
+        let PathChange::Changed { path, modified } = one_change else {
+            continue;
+        };
+        if modified.symlink_metadata().is_err() {
+            results.push(PathChange::Error {
+                path: modified.clone(),
+                error: "modified path not found, cannot undo".to_string(),
+            });
+            continue;
+        }
+        if path.symlink_metadata().is_ok() {
+            results.push(PathChange::ErrorRename {
+                path: modified.clone(),
+                modified: path.clone(),
+                error: "original path already exists".to_string(),
+            });
+            continue;
+        }
+        if options.dry_run {
+            results.push(PathChange::WouldChange {
+                path: modified.clone(),
+                modified: path.clone(),
+            });
+            continue;
+        }
+        match std::fs::rename(modified, path) {
+            Ok(_) => results.push(PathChange::Changed {
+                path: modified.clone(),
+                modified: path.clone(),
+            }),
+            Err(e) => results.push(PathChange::ErrorRename {
+                path: modified.clone(),
+                modified: path.clone(),
+                error: e.to_string(),
+            }),
+        }
     }
-    Ok((NotoxArgs { dry_run, output }, path_to_check))
+    results
 }
 
 /// Do the program, return the Vector of result
@@ -758,10 +5694,71 @@ pub fn notox(notox_args: &NotoxArgs, paths_to_check: &HashSet<PathBuf>) -> Vec<P
     Notox::new(notox_args).run(paths_to_check)
 }
 
+/// Make a decision function for [`Notox::run_with_decision`] asking each rename on a prompt
+///
+/// The question `Rename X -> Y? [y/N/a/q]` is written to `prompt` and the answer is read from `input`:
+/// `y` applies the rename, `a` applies it and all the remaining ones without asking,
+/// `q` aborts, and anything else skips it. The end of the input aborts.
+pub fn prompt_decisions<R: std::io::BufRead, W: Write>(
+    mut input: R,
+    mut prompt: W,
+) -> impl FnMut(&PathChange) -> Decision {
+    let mut approve_all = false;
+    move |one_change| {
+        if approve_all {
+            return Decision::Apply;
+        }
+        let modified = match one_change {
+            PathChange::WouldChange { modified, .. } => modified,
+            other => other.path(),
+        };
+        let _ = write!(
+            prompt,
+            "Rename {} -> {}? [y/N/a/q] ",
+            one_change.path().display(),
+            modified.display()
+        );
+        let _ = prompt.flush();
+        let mut answer = String::new();
+        match input.read_line(&mut answer) {
+            Ok(0) | Err(_) => return Decision::Abort,
+            Ok(_) => {}
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => Decision::Apply,
+            "a" | "all" => {
+                approve_all = true;
+                Decision::Apply
+            }
+            "q" | "quit" => Decision::Abort,
+            _ => Decision::Skip,
+        }
+    }
+}
+
+/// Decision taken on a proposed rename, see [`Notox::run_with_decision`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Apply the rename
+    Apply,
+
+    /// Skip the rename
+    Skip,
+
+    /// Skip the rename and all the remaining ones
+    Abort,
+}
+
+/// Number of changes waiting for the iterator of [`Notox::run_with_depth`] or the stream of
+/// `Notox::run_stream` (feature `tokio`) to be polled, the walk waits after it
+pub const STREAM_BUFFER: usize = 1024;
+
 /// Notox struct
 pub struct Notox {
     /// Options
     notox_args: NotoxArgs,
+    /// the `per_dir_summary` of the last run, written by the default output
+    dir_summaries: Mutex<Vec<(PathBuf, Summary)>>,
 }
 
 impl Notox {
@@ -769,13 +5766,14 @@ impl Notox {
     pub fn new(notox_args: &NotoxArgs) -> Notox {
         Notox {
             notox_args: notox_args.clone(),
+            dir_summaries: Mutex::new(Vec::new()),
         }
     }
 
     /// Run from args
     /// # Errors
     /// Returns error if parse_args fails
-    pub fn run_from_args(args: &[String]) -> Result<Vec<PathChange>, i32> {
+    pub fn run_from_args(args: &[String]) -> Result<Vec<PathChange>, ExitCode> {
         match parse_args(args) {
             Ok((notox_args, paths)) => Ok(Self::new(&notox_args).run(&paths)),
             Err(code) => Err(code),
@@ -783,13 +5781,77 @@ impl Notox {
     }
 
     /// Run main from args
-    pub fn run_main_from_args(args: &[String]) -> i32 {
-        match parse_args(args) {
+    ///
+    /// The `.notox.json` of the current directory is read like with [`parse_args_with_config`].
+    /// Returns [`ExitCode::NoPathsFound`] if no path could be processed
+    pub fn run_main_from_args(args: &[String]) -> ExitCode {
+        #[cfg(feature = "serde")]
+        let default_config = Some(Path::new(CONFIG_FILE_NAME));
+        #[cfg(not(feature = "serde"))]
+        let default_config = None;
+        match parse_args_with_config(args, default_config) {
             Ok((notox_args, paths)) => {
+                #[cfg(feature = "watch")]
+                if let Some(dir_path) = &notox_args.watch {
+                    return watch::watch(&notox_args, dir_path);
+                }
                 let notox_inst = Self::new(&notox_args);
-                let final_res = notox_inst.run(&paths);
+                #[cfg(feature = "serde")]
+                if let Some(changes) = &notox_args.undo {
+                    return match notox_inst.print_output(undo(changes, &notox_args)) {
+                        Ok(_) => ExitCode::Success,
+                        Err(code) => code,
+                    };
+                }
+                if notox_args.stdin {
+                    return match clean_lines(std::io::stdin().lock(), &notox_args) {
+                        Ok(changes) => notox_inst.print_stdin_output(changes),
+                        Err(e) => {
+                            println!("Cannot read the names from stdin: {}", e);
+                            ExitCode::ReadError
+                        }
+                    };
+                }
+                #[cfg(feature = "serde")]
+                if let (
+                    Output::JsonOutput {
+                        json: JsonOutput::JsonLines,
+                        ..
+                    },
+                    None,
+                ) = (&notox_args.output, &notox_args.output_file)
+                {
+                    return match notox_inst.run_streaming_json(
+                        &paths,
+                        &mut std::io::stdout(),
+                        false,
+                    ) {
+                        Ok(summary) if summary == Summary::default() => ExitCode::NoPathsFound,
+                        Ok(summary)
+                            if notox_args.check
+                                && summary.would_change + summary.would_fail > 0 =>
+                        {
+                            ExitCode::ChangesNeeded
+                        }
+                        Ok(_) => ExitCode::Success,
+                        Err(code) => code,
+                    };
+                }
+                let final_res = match notox_args.interactive {
+                    true => notox_inst.run_with_decision(
+                        &paths,
+                        prompt_decisions(std::io::stdin().lock(), std::io::stderr()),
+                    ),
+                    false => notox_inst.run(&paths),
+                };
+                let is_empty = final_res.is_empty();
+                let summary = Summary::from_results(&final_res);
+                let needs_changes =
+                    notox_args.check && summary.would_change + summary.would_fail > 0;
                 match notox_inst.print_output(final_res) {
-                    Ok(_) => 0,
+                    Ok(_) if is_empty => ExitCode::NoPathsFound,
+                    Ok(_) if needs_changes => ExitCode::ChangesNeeded,
+                    Ok(_) => ExitCode::Success,
                     Err(code) => code,
                 }
             }
@@ -798,61 +5860,588 @@ impl Notox {
     }
 
     /// Run the Notox instance
+    ///
+    /// The change of a directory always comes before the changes of its entries,
+    /// see [`sort_for_apply`] and [`sort_for_undo`] to order other results.
+    /// The order is the same on each run, even with rayon: the paths to check and the entries
+    /// of each directory are sorted by their original path
     pub fn run(&self, paths_to_check: &HashSet<PathBuf>) -> Vec<PathChange> {
+        let mut seen = HashSet::new();
+        self.run_with_sink(paths_to_check, None)
+            .into_iter()
+            .map(|(_, one_change)| one_change)
+            .filter(|one_change| is_first_occurrence(&mut seen, one_change))
+            .collect()
+    }
+
+    /// Run the Notox instance, with the counts of the results
+    pub fn run_with_summary(
+        &self,
+        paths_to_check: &HashSet<PathBuf>,
+    ) -> (Vec<PathChange>, Summary) {
+        let results = self.run(paths_to_check);
+        let summary = Summary::from_results(&results);
+        (results, summary)
+    }
+
+    /// Run the Notox instance on its own thread, with the depth of each entry
+    ///
+    /// The paths to check are at depth 0, the entries of a directory are one level deeper than it.
+    /// A directory comes before its entries, so the results can be rendered as a tree.
+    /// The identical errors are only reported once, see [`dedup_errors`].
+    /// The changes are yielded as soon as they are done, in the order of [`Notox::run_with_callback`],
+    /// and up to [`STREAM_BUFFER`] changes wait for the iterator to be polled.
+    /// The walk stops once the iterator is dropped, the paths not started yet are not cleaned,
+    /// but the changes still waiting in the buffer are lost with it.
+    pub fn run_with_depth(
+        &self,
+        paths_to_check: &HashSet<PathBuf>,
+    ) -> impl Iterator<Item = (usize, PathChange)> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(STREAM_BUFFER);
+        let notox = Notox::new(&self.notox_args);
+        let paths_to_check = paths_to_check.clone();
+        std::thread::spawn(move || {
+            // the receiver is dropped with the iterator
+            notox.run_with_sender(&paths_to_check, |depth, one_change| {
+                sender.send((depth, one_change)).is_ok()
+            });
+        });
+        receiver.into_iter()
+    }
+
+    /// Run the Notox instance and call the callback with each change as soon as it is done
+    ///
+    /// The order is the same as [`Notox::run_streaming_json`], the callback is never called concurrently,
+    /// even with rayon, so it can update a progress bar or count the errors.
+    /// Returns the [`Summary`] of the changes passed to the callback.
+    pub fn run_with_callback<F: FnMut(&PathChange) + Send>(
+        &self,
+        paths_to_check: &HashSet<PathBuf>,
+        callback: F,
+    ) -> Summary {
+        let state = Mutex::new((callback, Summary::default(), HashSet::new()));
+        let sink = |_depth: usize, one_change: PathChange| {
+            let mut guard = state.lock().unwrap_or_else(PoisonError::into_inner);
+            let (callback, summary, seen) = &mut *guard;
+            if !is_first_occurrence(seen, &one_change) {
+                return true;
+            }
+            summary.add(&one_change);
+            callback(&one_change);
+            true
+        };
+        self.run_with_sink(paths_to_check, Some(&sink));
+        let (_, summary, _) = state.into_inner().unwrap_or_else(PoisonError::into_inner);
+        summary
+    }
+
+    /// Run the Notox instance and write each change as a JSON line as soon as it is done
+    ///
+    /// The memory does not grow with the size of the tree, unlike [`Notox::run`].
+    /// The lines of different directories can be interleaved with rayon,
+    /// but a directory is always written before its entries.
+    /// With `only_errors`, only the errors are written, the [`Summary`] still counts all the changes.
+    /// The lines have the size and mtime of the files with `include_metadata`.
+    /// # Errors
+    /// Returns the exit code if the writer fails
+    #[cfg(feature = "serde")]
+    pub fn run_streaming_json<W: Write + Send>(
+        &self,
+        paths_to_check: &HashSet<PathBuf>,
+        writer: &mut W,
+        only_errors: bool,
+    ) -> Result<Summary, ExitCode> {
+        let mut write_error = None;
+        let summary = self.run_with_callback(paths_to_check, |one_change| {
+            if write_error.is_some() || (only_errors && !one_change.is_error()) {
+                return;
+            }
+            let line = match self.notox_args.include_metadata {
+                true => serde_json::to_string(&PathChangeWithMetadata::new(one_change)),
+                false => serde_json::to_string(one_change),
+            };
+            let written = line
+                .map_err(std::io::Error::other)
+                .and_then(|line| writeln!(writer, "{}", line));
+            if let Err(e) = written {
+                write_error = Some(e);
+            }
+        });
+        if let Some(e) = write_error {
+            return Err(output_error(e));
+        }
+        writer.flush().map_err(output_error)?;
+        Ok(summary)
+    }
+
+    /// Run the Notox instance and send each change once, the identical errors are only sent the first time
+    ///
+    /// The walk is stopped once `send` returns false. It is called without holding any lock,
+    /// so it can wait for its receiver without blocking the other threads
+    fn run_with_sender<F: Fn(usize, PathChange) -> bool + Sync>(
+        &self,
+        paths_to_check: &HashSet<PathBuf>,
+        send: F,
+    ) {
+        let seen = Mutex::new(HashSet::new());
+        let sink = |depth: usize, one_change: PathChange| {
+            let is_first = is_first_occurrence(
+                &mut seen.lock().unwrap_or_else(PoisonError::into_inner),
+                &one_change,
+            );
+            !is_first || send(depth, one_change)
+        };
+        self.run_with_sink(paths_to_check, Some(&sink));
+    }
+
+    /// Run the Notox instance, the changes are sent to the sink instead of being returned if there is one
+    ///
+    /// With `threads`, the traversal runs in its own rayon pool, or in the global one if it cannot be built
+    fn run_with_sink(
+        &self,
+        paths_to_check: &HashSet<PathBuf>,
+        sink: ChangeSink<'_>,
+    ) -> Vec<(usize, PathChange)> {
+        #[cfg(feature = "rayon")]
+        if let Some(threads) = self.notox_args.threads {
+            if let Ok(pool) = rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+                return pool.install(|| self.traverse(paths_to_check, sink));
+            }
+        }
+        self.traverse(paths_to_check, sink)
+    }
+
+    /// Clean the paths in the current rayon pool, see [`Notox::run_with_sink`]
+    fn traverse(
+        &self,
+        paths_to_check: &HashSet<PathBuf>,
+        sink: ChangeSink<'_>,
+    ) -> Vec<(usize, PathChange)> {
         if self.notox_args.is_vervose() {
             println!("Running with options: {}", &self.notox_args);
         }
+        let mut result_vec = Vec::new();
+        let walk = WalkState::new(&self.notox_args);
+        let canonicalized_paths;
+        let paths_to_check = match self.notox_args.canonicalize_input {
+            true => {
+                let (paths, errors) = canonicalize_paths(paths_to_check);
+                for one_error in errors {
+                    walk.emit(&mut result_vec, sink, 0, one_error);
+                }
+                canonicalized_paths = paths;
+                &canonicalized_paths
+            }
+            false => paths_to_check,
+        };
+        let (nested_paths, paths_to_check) = remove_nested_paths(paths_to_check, &self.notox_args);
+        let renames_done = AtomicUsize::new(0);
+        let clean_one = |one_path: &PathBuf| {
+            if walk.is_stopped() {
+                return Vec::new();
+            }
+            if self.notox_args.is_vervose() {
+                println!("Checking: {}", one_path.display());
+            }
+            match one_path.is_dir() {
+                true => clean_directory(one_path, &self.notox_args, &renames_done, sink, &walk),
+                false => {
+                    walk.tick();
+                    let mut path_changes = Vec::new();
+                    for one_change in clean_path(one_path, &self.notox_args, &renames_done) {
+                        walk.emit(&mut path_changes, sink, 0, one_change);
+                    }
+                    path_changes
+                }
+            }
+        };
+        for one_path in nested_paths {
+            result_vec.extend(clean_one(one_path));
+        }
         #[cfg(feature = "rayon")]
-        let iter = paths_to_check.par_iter();
+        let iter = paths_to_check
+            .par_iter()
+            .with_min_len(self.notox_args.chunk_size.unwrap_or(1));
         #[cfg(not(feature = "rayon"))]
         let iter = paths_to_check.iter();
 
-        let results = iter
-            .map(|one_path| {
-                if self.notox_args.is_vervose() {
-                    println!("Checking: {}", one_path.display());
-                }
-                match one_path.is_dir() {
-                    true => clean_directory(one_path, &self.notox_args),
-                    false => {
-                        let one_cleaned = clean_path(one_path, &self.notox_args);
-                        vec![one_cleaned]
+        let results = iter.flat_map(|one_path| clean_one(one_path));
+        result_vec.extend(results.collect::<Vec<(usize, PathChange)>>());
+        walk.finish();
+        *self
+            .dir_summaries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = walk
+            .dir_summaries
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        result_vec
+    }
+
+    /// Run the Notox instance, failing if there was any error
+    ///
+    /// Returns the [`Summary`] of the run, or the entries in error.
+    /// The renames not done because of the dry-run are not errors.
+    /// # Errors
+    /// Return the entries in error if there is at least one,
+    /// the other renames have still been applied (the run is not atomic)
+    pub fn run_and_collect_errors(
+        &self,
+        paths_to_check: &HashSet<PathBuf>,
+    ) -> Result<Summary, Vec<PathChange>> {
+        let results = self.run(paths_to_check);
+        let summary = Summary::from_results(&results);
+        match summary.errors {
+            0 => Ok(summary),
+            _ => Err(results.into_iter().filter(PathChange::is_error).collect()),
+        }
+    }
+
+    /// Run the Notox instance on a blocking thread of tokio, the changes are yielded as soon as they are done
+    ///
+    /// The walk and the renames are the ones of [`Notox::run_with_callback`], in the same order,
+    /// the async workers are never blocked. Up to [`STREAM_BUFFER`] changes wait for the stream to be polled.
+    /// The walk stops once the stream is dropped (like a cancelled request), the paths not started yet are not cleaned,
+    /// but the changes still waiting in the buffer are lost with it.
+    /// It must be called from a tokio runtime, with a quiet output since the verbose one prints to stdout
+    #[cfg(feature = "tokio")]
+    pub async fn run_stream(
+        &self,
+        paths_to_check: &HashSet<PathBuf>,
+    ) -> impl tokio_stream::Stream<Item = PathChange> {
+        let (sender, receiver) = tokio::sync::mpsc::channel(STREAM_BUFFER);
+        let notox = Notox::new(&self.notox_args);
+        let paths_to_check = paths_to_check.clone();
+        tokio::task::spawn_blocking(move || {
+            // the receiver is dropped with the stream
+            notox.run_with_sender(&paths_to_check, |_depth, one_change| {
+                sender.blocking_send(one_change).is_ok()
+            });
+        });
+        tokio_stream::wrappers::ReceiverStream::new(receiver)
+    }
+
+    /// Run the Notox instance, failing if there was any error
+    ///
+    /// Returns all the changes when there is no error, like [`Notox::run`].
+    /// With `stop_at_first_error`, the paths not started yet are not cleaned after the first error,
+    /// which is returned alone, otherwise all the errors are returned in [`NotoxError::Multiple`].
+    /// # Errors
+    /// Return the errors of the run, the other renames have still been applied (the run is not atomic)
+    pub fn try_run(
+        &self,
+        paths_to_check: &HashSet<PathBuf>,
+    ) -> Result<Vec<PathChange>, NotoxError> {
+        let results = self.run(paths_to_check);
+        let mut errors = results.iter().filter_map(NotoxError::from_change);
+        if self.notox_args.stop_at_first_error {
+            return match errors.next() {
+                Some(first_error) => Err(first_error),
+                None => Ok(results),
+            };
+        }
+        let errors = errors.collect::<Vec<NotoxError>>();
+        match errors.is_empty() {
+            true => Ok(results),
+            false => Err(NotoxError::Multiple(errors)),
+        }
+    }
+
+    /// Compute the changes of the paths like a dry-run, whatever the `dry_run` option
+    ///
+    /// The renames are [`PathChange::WouldChange`] entries, they can be done later with [`Notox::apply`]
+    pub fn plan(&self, paths_to_check: &HashSet<PathBuf>) -> Vec<PathChange> {
+        let plan_args = NotoxArgs {
+            dry_run: true,
+            ..self.notox_args.clone()
+        };
+        Notox::new(&plan_args).run(paths_to_check)
+    }
+
+    /// Do the renames of a plan made with [`Notox::plan`], whatever the `dry_run` option
+    ///
+    /// The tree is not walked again, see [`apply_changes`] for the outdated plans
+    pub fn apply(&self, plan: &[PathChange]) -> Vec<PathChange> {
+        let apply_args = NotoxArgs {
+            dry_run: false,
+            ..self.notox_args.clone()
+        };
+        apply_changes(plan, &apply_args)
+    }
+
+    /// Run the Notox instance, asking for a decision before each rename
+    ///
+    /// The paths are first checked like a dry-run (in parallel with rayon),
+    /// then `decide` is called sequentially for each proposed rename.
+    /// Skipped and aborted renames are reported as unchanged.
+    /// With `dry_run`, the applied renames stay as proposed renames.
+    pub fn run_with_decision<F: FnMut(&PathChange) -> Decision>(
+        &self,
+        paths_to_check: &HashSet<PathBuf>,
+        mut decide: F,
+    ) -> Vec<PathChange> {
+        let mut plan = self.plan(paths_to_check);
+        let mut approved = Vec::new();
+        let mut aborted = false;
+        for (idx, one_change) in plan.iter_mut().enumerate() {
+            if !one_change.is_dry_run_rename() {
+                continue;
+            }
+            let decision = match aborted {
+                true => Decision::Abort,
+                false => decide(one_change),
+            };
+            match decision {
+                Decision::Apply => approved.push(idx),
+                Decision::Skip | Decision::Abort => {
+                    aborted = decision == Decision::Abort;
+                    if let PathChange::WouldChange { path, .. } = one_change {
+                        *one_change = PathChange::Unchanged { path: path.clone() };
                     }
                 }
-            })
-            .flatten();
-        results.collect::<Vec<PathChange>>()
+            }
+        }
+        if !self.notox_args.dry_run {
+            // children are after their parent directory, rename them first
+            for idx in approved.into_iter().rev() {
+                if let PathChange::WouldChange { path, modified } = &plan[idx] {
+                    let renamed = rename_path(path, modified.clone(), &self.notox_args);
+                    plan.splice(idx..=idx, renamed);
+                }
+            }
+        }
+        plan
+    }
+
+    /// Print the names cleaned by [`clean_lines`]
+    ///
+    /// The default output only has the cleaned paths, one per line, to be used in scripts.
+    /// Returns the exit code
+    pub fn print_stdin_output(&self, final_res: Vec<PathChange>) -> ExitCode {
+        if final_res.is_empty() {
+            return ExitCode::NoPathsFound;
+        }
+        if self.notox_args.output != Output::Default {
+            return match self.print_output(final_res) {
+                Ok(_) => ExitCode::Success,
+                Err(code) => code,
+            };
+        }
+        let mut stdout = std::io::stdout().lock();
+        for one_change in &final_res {
+            let cleaned = match one_change {
+                PathChange::WouldChange { modified, .. } => modified,
+                other => other.path(),
+            };
+            if let Err(e) = writeln!(stdout, "{}", cleaned.display()) {
+                return output_error(e);
+            }
+        }
+        ExitCode::Success
     }
 
     /// Print the output of the program conforming to the options
+    ///
+    /// The output is written to stdout, or to `output_file` if set.
+    /// A file output is always UTF-8 without BOM with `\n` line endings,
+    /// unless `crlf` is set.
+    /// On Unix, `output_file` can be a named pipe or a socket, each line is then sent as soon as it is written.
+    /// # Errors
+    /// Return an error if the output cannot be serialized or written
+    pub fn print_output(&self, final_res: Vec<PathChange>) -> Result<(), ExitCode> {
+        let Some(output_file) = &self.notox_args.output_file else {
+            return self.print_output_to(final_res, &mut std::io::stdout().lock());
+        };
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            let file_type = std::fs::metadata(output_file).map(|metadata| metadata.file_type());
+            if let Ok(file_type) = file_type {
+                if file_type.is_socket() {
+                    let stream = std::os::unix::net::UnixStream::connect(output_file)
+                        .map_err(output_error)?;
+                    return self.print_output_lines(final_res, stream);
+                }
+                if file_type.is_fifo() {
+                    let fifo = std::fs::OpenOptions::new()
+                        .write(true)
+                        .open(output_file)
+                        .map_err(output_error)?;
+                    return self.print_output_lines(final_res, fifo);
+                }
+            }
+        }
+        let file = std::fs::File::create(output_file).map_err(output_error)?;
+        let mut file_writer = std::io::BufWriter::new(file);
+        match self.notox_args.crlf {
+            true => self.print_output_to(final_res, &mut CrlfWriter { inner: file_writer }),
+            false => self.print_output_to(final_res, &mut file_writer),
+        }
+    }
+
+    /// Print the output to a stream, flushing each line
+    /// # Errors
+    /// Return an error if the output cannot be serialized or written
+    #[cfg(unix)]
+    fn print_output_lines<W: Write>(
+        &self,
+        final_res: Vec<PathChange>,
+        stream: W,
+    ) -> Result<(), ExitCode> {
+        let mut line_writer = std::io::LineWriter::new(stream);
+        match self.notox_args.crlf {
+            true => self.print_output_to(final_res, &mut CrlfWriter { inner: line_writer }),
+            false => self.print_output_to(final_res, &mut line_writer),
+        }
+    }
+
+    /// Write the output of the program conforming to the options into a writer
     /// # Errors
-    /// Return an error if the output cannot be serialized
-    pub fn print_output(&self, final_res: Vec<PathChange>) -> Result<(), i32> {
+    /// Return an error if the output cannot be serialized or written
+    pub fn print_output_to<W: Write>(
+        &self,
+        final_res: Vec<PathChange>,
+        writer: &mut W,
+    ) -> Result<(), ExitCode> {
+        // the skipped paths and the links are not changes
+        if self.notox_args.quiet_unless_changes
+            && !final_res.iter().any(|one_change| {
+                one_change.is_error()
+                    || matches!(
+                        one_change,
+                        PathChange::Changed { .. }
+                            | PathChange::WouldChange { .. }
+                            | PathChange::Trashed { .. }
+                    )
+            })
+        {
+            return Ok(());
+        }
         match &self.notox_args.output {
+            Output::Default if self.notox_args.null_separated => {
+                write_null_pairs(&final_res, writer).map_err(output_error)?;
+            }
             Output::Default => {
-                let len = final_res.len();
+                let summary = Summary::from_results(&final_res);
                 for one_change in final_res {
+                    let mut note = match self.notox_args.is_redacted(one_change.path()) {
+                        true => " (redacted)".to_string(),
+                        false => String::new(),
+                    };
+                    // a longer name may exceed the limits of the paths
+                    if let Some(delta) = one_change.length_delta().filter(|delta| *delta > 0) {
+                        note.push_str(&format!(" (+{} bytes)", delta));
+                    }
                     match one_change {
-                        PathChange::Unchanged { .. } => {}
+                        PathChange::Unchanged { path } => {
+                            if self.notox_args.show_unchanged {
+                                writeln!(writer, "{} (unchanged)", path.display())
+                                    .map_err(output_error)?;
+                            }
+                        }
                         PathChange::Changed { path, modified } => {
-                            println!("{} -> {}", path.display(), modified.display());
+                            writeln!(
+                                writer,
+                                "{} -> {}{}",
+                                path.display(),
+                                modified.display(),
+                                note
+                            )
+                            .map_err(output_error)?;
                         }
                         PathChange::Error { path, error } => {
-                            println!("{} : {}", path.display(), error);
+                            writeln!(writer, "{} : {}", path.display(), error)
+                                .map_err(output_error)?;
+                        }
+                        PathChange::ErrorBackup {
+                            path,
+                            backup,
+                            error,
+                        } => {
+                            writeln!(
+                                writer,
+                                "{} : not renamed, cannot copy to {}, {}",
+                                path.display(),
+                                backup.display(),
+                                error
+                            )
+                            .map_err(output_error)?;
+                        }
+                        PathChange::Trashed { path } => {
+                            writeln!(writer, "{} -> trash", path.display())
+                                .map_err(output_error)?;
+                        }
+                        PathChange::Skipped { path, reason } => {
+                            writeln!(writer, "{} : skipped, {}", path.display(), reason)
+                                .map_err(output_error)?;
+                        }
+                        PathChange::Symlink { path, target } => {
+                            if self.notox_args.show_unchanged {
+                                writeln!(
+                                    writer,
+                                    "{} : symlink to {}",
+                                    path.display(),
+                                    target.display()
+                                )
+                                .map_err(output_error)?;
+                            }
+                        }
+                        PathChange::WouldChange { path, modified } => {
+                            writeln!(
+                                writer,
+                                "{} -> {} : {}{}",
+                                path.display(),
+                                modified.display(),
+                                DRY_RUN_ERROR,
+                                note
+                            )
+                            .map_err(output_error)?;
                         }
                         PathChange::ErrorRename {
                             path,
                             modified,
                             error,
                         } => {
-                            println!("{} -> {} : {}", path.display(), modified.display(), error);
+                            writeln!(
+                                writer,
+                                "{} -> {} : {}{}",
+                                path.display(),
+                                modified.display(),
+                                error,
+                                note
+                            )
+                            .map_err(output_error)?;
+                        }
+                        PathChange::WouldFail {
+                            path,
+                            modified,
+                            reason,
+                        } => {
+                            writeln!(
+                                writer,
+                                "{} -> {} : would fail, {}{}",
+                                path.display(),
+                                modified.display(),
+                                reason,
+                                note
+                            )
+                            .map_err(output_error)?;
                         }
                     }
                 }
-                if len == 1 {
-                    println!("{} file checked", len);
-                } else {
-                    println!("{} files checked", len);
+                // the directories are summarized after the changes, before the total
+                let dir_summaries = std::mem::take(
+                    &mut *self
+                        .dir_summaries
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner),
+                );
+                for (dir_path, dir_summary) in dir_summaries {
+                    writeln!(writer, "{}", dir_summary_line(&dir_path, &dir_summary))
+                        .map_err(output_error)?;
+                }
+                if !self.notox_args.no_summary {
+                    writeln!(writer, "{}", summary).map_err(output_error)?;
                 }
             }
             #[cfg(feature = "serde")]
@@ -861,38 +6450,148 @@ impl Notox {
                 pretty: json_pretty,
             } => {
                 let vec_to_json = match json_output {
-                    JsonOutput::JsonDefault => final_res,
+                    JsonOutput::JsonDefault
+                    | JsonOutput::JsonTree
+                    | JsonOutput::JsonLines
+                    | JsonOutput::JsonEnvelope => final_res,
                     JsonOutput::JsonOnlyError => {
-                        let mut vec_to_json: Vec<PathChange> = Vec::new();
-                        for one_change in final_res {
-                            match one_change {
-                                PathChange::Unchanged { .. } => {}
-                                PathChange::Changed { .. } => {}
-                                one_res @ PathChange::Error { .. } => {
-                                    vec_to_json.push(one_res);
-                                }
-                                one_res @ PathChange::ErrorRename { .. } => {
-                                    vec_to_json.push(one_res);
-                                }
-                            }
-                        }
-                        vec_to_json
+                        final_res.into_iter().filter(PathChange::is_error).collect()
                     }
                 };
-                let json_string = match json_pretty {
-                    true => serde_json::to_string_pretty(&vec_to_json),
-                    false => serde_json::to_string(&vec_to_json),
+                let json_string = match (json_output, self.notox_args.include_metadata) {
+                    (JsonOutput::JsonTree, _) => {
+                        to_json_string(&PathChangeTree::from_results(&vec_to_json), *json_pretty)
+                    }
+                    (JsonOutput::JsonEnvelope, include_metadata) => {
+                        let summary = Summary::from_results(&vec_to_json);
+                        match include_metadata {
+                            true => to_json_string(
+                                &JsonEnvelope::new(
+                                    vec_to_json
+                                        .iter()
+                                        .map(PathChangeWithMetadata::new)
+                                        .collect::<Vec<_>>(),
+                                    &summary,
+                                ),
+                                *json_pretty,
+                            ),
+                            false => to_json_string(
+                                &JsonEnvelope::new(&vec_to_json, &summary),
+                                *json_pretty,
+                            ),
+                        }
+                    }
+                    (JsonOutput::JsonLines, include_metadata) => vec_to_json
+                        .iter()
+                        .map(|one_change| match include_metadata {
+                            true => serde_json::to_string(&PathChangeWithMetadata::new(one_change)),
+                            false => serde_json::to_string(one_change),
+                        })
+                        .map(|line| line.map(|line| line + "\n"))
+                        .collect(),
+                    (_, true) => to_json_string(
+                        &vec_to_json
+                            .iter()
+                            .map(PathChangeWithMetadata::new)
+                            .collect::<Vec<_>>(),
+                        *json_pretty,
+                    ),
+                    (_, false) => to_json_string(&vec_to_json, *json_pretty),
                 };
                 match json_string {
-                    Ok(stringed) => println!("{}", stringed),
+                    // each line already ends with a newline, nothing is written without results
+                    Ok(stringed) if *json_output == JsonOutput::JsonLines => {
+                        write!(writer, "{}", stringed).map_err(output_error)?
+                    }
+                    Ok(stringed) => writeln!(writer, "{}", stringed).map_err(output_error)?,
                     Err(_) => {
-                        println!(r#"{{"error": "Cannot serialize result"}}"#);
-                        return Err(2);
+                        writeln!(writer, r#"{{"error": "Cannot serialize result"}}"#)
+                            .map_err(output_error)?;
+                        return Err(ExitCode::SerializeError);
+                    }
+                }
+            }
+            Output::CategoryReport => {
+                let tally = CategoryTally::from_results(&final_res);
+                writeln!(writer, "accents: {}", tally.accents).map_err(output_error)?;
+                writeln!(writer, "symbols: {}", tally.symbols).map_err(output_error)?;
+                writeln!(writer, "whitespace: {}", tally.whitespace).map_err(output_error)?;
+                writeln!(writer, "control: {}", tally.control).map_err(output_error)?;
+                writeln!(writer, "{} files checked", final_res.len()).map_err(output_error)?;
+            }
+            Output::EncodingClass => {
+                for one_change in &final_res {
+                    let encoding = match one_change.path().file_name() {
+                        Some(name) => NameEncoding::of(name),
+                        None => continue,
+                    };
+                    if encoding != NameEncoding::Ascii
+                        && !matches!(
+                            one_change,
+                            PathChange::Trashed { .. }
+                                | PathChange::Symlink { .. }
+                                | PathChange::Error { .. }
+                        )
+                    {
+                        writeln!(
+                            writer,
+                            "{} : {}",
+                            one_change.path().display(),
+                            encoding.name()
+                        )
+                        .map_err(output_error)?;
+                    }
+                }
+                let tally = EncodingTally::from_results(&final_res);
+                writeln!(writer, "ascii: {}", tally.ascii).map_err(output_error)?;
+                writeln!(writer, "utf-8: {}", tally.utf8).map_err(output_error)?;
+                writeln!(writer, "invalid utf-8: {}", tally.invalid_utf8).map_err(output_error)?;
+            }
+            Output::CompatReport => {
+                let mut incompatible = 0;
+                for one_change in &final_res {
+                    let name = match one_change.path().file_name() {
+                        Some(name) => name,
+                        None => continue,
+                    };
+                    let violations = ALL_FILESYSTEMS
+                        .iter()
+                        .filter_map(|filesystem| {
+                            let reasons = filesystem.violations(name);
+                            (!reasons.is_empty())
+                                .then(|| format!("{} ({})", filesystem.name(), reasons.join(", ")))
+                        })
+                        .collect::<Vec<String>>();
+                    if !violations.is_empty() {
+                        incompatible += 1;
+                        writeln!(
+                            writer,
+                            "{} : {}",
+                            one_change.path().display(),
+                            violations.join(", ")
+                        )
+                        .map_err(output_error)?;
                     }
                 }
+                writeln!(
+                    writer,
+                    "{} files checked, {} incompatible",
+                    final_res.len(),
+                    incompatible
+                )
+                .map_err(output_error)?;
+            }
+            Output::Csv => {
+                writeln!(writer, "{}", CSV_HEADER).map_err(output_error)?;
+                for one_change in &final_res {
+                    writeln!(writer, "{}", one_change.csv_row()).map_err(output_error)?;
+                }
+            }
+            Output::Script(dialect) => {
+                write_rename_script(&final_res, *dialect, writer).map_err(output_error)?;
             }
             Output::Quiet => {}
         }
-        Ok(())
+        writer.flush().map_err(output_error)
     }
 }
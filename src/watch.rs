@@ -0,0 +1,159 @@
+//! Watch mode, cleaning the files as they appear in a directory
+//!
+//! Available with the `watch` feature.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::AtomicUsize,
+        mpsc::{channel, RecvTimeoutError},
+    },
+    time::{Duration, Instant},
+};
+
+use notify::{
+    event::{ModifyKind, RenameMode},
+    Event, EventKind, RecursiveMode, Watcher,
+};
+
+use crate::{clean_directory, clean_path, ExitCode, Notox, NotoxArgs, PathChange, WalkState};
+
+/// Time without new event on a path before cleaning it
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Clean the paths of the watch events
+///
+/// The events of a path are debounced, and the events caused by its own renames are ignored
+pub struct WatchCleaner {
+    /// Options
+    notox_args: NotoxArgs,
+    /// Time without new event on a path before cleaning it
+    debounce: Duration,
+    /// Paths waiting to be cleaned, with the time of their last event
+    pending: HashMap<PathBuf, Instant>,
+    /// Paths created by the renames of the cleaner
+    own_renames: HashSet<PathBuf>,
+    /// Number of renames since the start, for the `rename_budget`
+    renames_done: AtomicUsize,
+}
+
+impl WatchCleaner {
+    /// Create a new WatchCleaner
+    pub fn new(notox_args: &NotoxArgs, debounce: Duration) -> WatchCleaner {
+        WatchCleaner {
+            notox_args: notox_args.clone(),
+            debounce,
+            pending: HashMap::new(),
+            own_renames: HashSet::new(),
+            renames_done: AtomicUsize::new(0),
+        }
+    }
+
+    /// Register a path created or renamed at `now`
+    pub fn push_event(&mut self, path: PathBuf, now: Instant) {
+        if self.own_renames.remove(&path) {
+            return;
+        }
+        self.pending.insert(path, now);
+    }
+
+    /// Clean the paths without new event since the debounce duration
+    ///
+    /// A directory is cleaned with its content, the entries of a directory renamed by the cleaner
+    /// are no longer at the paths of their events
+    pub fn clean_ready(&mut self, now: Instant) -> Vec<PathChange> {
+        let ready: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, last_event)| now.duration_since(**last_event) >= self.debounce)
+            .map(|(path, _)| path.clone())
+            .collect();
+        let mut results = Vec::new();
+        for path in ready {
+            self.pending.remove(&path);
+            if path.symlink_metadata().is_err() {
+                // already moved or removed
+                continue;
+            }
+            let changes = match path.is_dir() {
+                true => clean_directory(
+                    &path,
+                    &self.notox_args,
+                    &self.renames_done,
+                    None,
+                    &WalkState::new(&self.notox_args),
+                )
+                .into_iter()
+                .map(|(_, one_change)| one_change)
+                .collect(),
+                false => clean_path(&path, &self.notox_args, &self.renames_done),
+            };
+            for res in changes {
+                if let PathChange::Changed { modified, .. } = &res {
+                    self.own_renames.insert(modified.clone());
+                }
+                results.push(res);
+            }
+        }
+        results
+    }
+}
+
+/// Get the paths created by an event
+fn created_paths(event: Event) -> Vec<PathBuf> {
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => event.paths,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            event.paths.into_iter().skip(1).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Any)) => event
+            .paths
+            .into_iter()
+            .filter(|path| path.exists())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Watch a directory and clean the files created or renamed in it, until the watcher stops
+///
+/// Returns the exit code
+pub fn watch(notox_args: &NotoxArgs, dir_path: &Path) -> ExitCode {
+    let (sender, receiver) = channel();
+    let mut watcher = match notify::recommended_watcher(sender) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            println!("Cannot start the watcher: {}", e);
+            return ExitCode::WatchError;
+        }
+    };
+    if let Err(e) = watcher.watch(dir_path, RecursiveMode::Recursive) {
+        println!("Cannot watch {}: {}", dir_path.display(), e);
+        return ExitCode::WatchError;
+    }
+    if notox_args.is_vervose() {
+        println!("Watching: {}", dir_path.display());
+    }
+    let notox_inst = Notox::new(notox_args);
+    let mut cleaner = WatchCleaner::new(notox_args, DEFAULT_DEBOUNCE);
+    loop {
+        match receiver.recv_timeout(DEFAULT_DEBOUNCE / 2) {
+            Ok(Ok(event)) => {
+                let now = Instant::now();
+                for path in created_paths(event) {
+                    cleaner.push_event(path, now);
+                }
+            }
+            Ok(Err(e)) => println!("Watch error: {}", e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return ExitCode::Success,
+        }
+        let results = cleaner.clean_ready(Instant::now());
+        if !results.is_empty() {
+            if let Err(code) = notox_inst.print_output(results) {
+                return code;
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Fixtures shared by the integration tests
+#![allow(dead_code)]
+
+use std::{collections::HashSet, fs, path::Path};
+
+use notox::{NotoxArgs, Output, PathChange};
+
+/// Options of a run that prints nothing, the tests check the returned changes
+pub fn quiet_options(dry_run: bool) -> NotoxArgs {
+    NotoxArgs {
+        dry_run,
+        output: Output::Quiet,
+        ..Default::default()
+    }
+}
+
+/// Clean a single directory with the options
+pub fn run_dir(dir: &Path, options: &NotoxArgs) -> Vec<PathChange> {
+    notox::notox(options, &HashSet::from([dir.to_path_buf()]))
+}
+
+/// Create a directory with empty files, the directory left by a previous run is removed first
+///
+/// The files are relative to the directory, their missing parents are created
+pub fn create_tree(dir: &Path, files: &[&str]) {
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    for file in files {
+        let file_path = dir.join(file);
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(file_path, "").unwrap();
+    }
+}
+
+/// Get the names of the entries of a directory
+pub fn names_in(dir: &Path) -> HashSet<String> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect()
+}
+
+/// Get the arguments of the binary, with its name first like `std::env::args`
+pub fn args(extra: &[&str]) -> Vec<String> {
+    std::iter::once("notox")
+        .chain(extra.iter().copied())
+        .map(String::from)
+        .collect()
+}
@@ -22,7 +22,8 @@ mod tests {
             options,
             notox::NotoxArgs {
                 dry_run: true,
-                output: Output::Default
+                output: Output::Default,
+                ..Default::default()
             }
         );
         assert_eq!(res_path.len(), number + 1);
@@ -129,7 +130,8 @@ mod tests {
                 output: Output::JsonOutput {
                     json: JsonOutput::JsonDefault,
                     pretty: false
-                }
+                },
+                ..Default::default()
             }
         );
         assert_eq!(res_path.len(), 5);
@@ -149,10 +151,71 @@ mod tests {
             options,
             notox::NotoxArgs {
                 dry_run: false,
-                output: Output::Default
+                output: Output::Default,
+                ..Default::default()
             }
         );
         assert_eq!(res_path.len(), 5);
         cleanup(&dir);
     }
+
+    #[test]
+    fn test_no_rename_dir_is_descended() {
+        let dir = PathBuf::from("test_no_rename_dir");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        let kept = dir.join("keep me");
+        std::fs::create_dir_all(&kept).unwrap();
+        std::fs::File::create(kept.join("dirty name.txt")).unwrap();
+
+        let vec_args = [
+            "notox".to_string(),
+            dir.to_string_lossy().to_string(),
+            "--no-rename-dir".to_string(),
+            "keep me".to_string(),
+            "-d".to_string(),
+        ];
+        let (options, paths) = notox::parse_args(&vec_args).ok().unwrap();
+        assert_eq!(
+            options.no_rename_dirs,
+            vec![std::ffi::OsString::from("keep me")]
+        );
+        let res_path = notox::notox(&options, &paths);
+        assert_eq!(res_path.len(), 3);
+        assert!(res_path.contains(&notox::PathChange::Unchanged { path: kept.clone() }));
+        assert!(kept.is_dir());
+        assert!(kept.join("dirty_name.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_no_rename_dir_content_skipped() {
+        let dir = PathBuf::from("test_no_rename_dir_skipped");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        let kept = dir.join("keep me");
+        std::fs::create_dir_all(&kept).unwrap();
+        std::fs::File::create(kept.join("dirty name.txt")).unwrap();
+        std::fs::File::create(dir.join("other name.txt")).unwrap();
+
+        let vec_args = [
+            "notox".to_string(),
+            dir.to_string_lossy().to_string(),
+            "--no-rename-dir".to_string(),
+            "keep me".to_string(),
+            "--skip-no-rename-dir-content".to_string(),
+            "-d".to_string(),
+        ];
+        let (options, paths) = notox::parse_args(&vec_args).ok().unwrap();
+        assert!(options.skip_no_rename_dir_content);
+        let res_path = notox::notox(&options, &paths);
+        assert_eq!(res_path.len(), 3, "{:?}", res_path);
+        assert!(res_path.contains(&notox::PathChange::Unchanged { path: kept.clone() }));
+        // the directory and its content are kept, the other entries are cleaned
+        assert!(kept.join("dirty name.txt").exists());
+        assert!(dir.join("other_name.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
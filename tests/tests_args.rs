@@ -1,8 +1,14 @@
+mod common;
+
 #[cfg(test)]
 mod tests {
     #[cfg(feature = "serde")]
     use notox::JsonOutput;
-    use notox::{NotoxArgs, Output};
+    use std::collections::HashSet;
+
+    use notox::{
+        CaseMode, Category, CleanMode, ExitCode, NonUtf8Policy, NotoxArgs, Output, Profile,
+    };
 
     #[test]
     fn test_parse_args() {
@@ -12,6 +18,7 @@ mod tests {
                 NotoxArgs {
                     dry_run: true,
                     output: Output::Default,
+                    ..Default::default()
                 },
             ),
             (
@@ -23,6 +30,7 @@ mod tests {
                 NotoxArgs {
                     dry_run: false,
                     output: Output::Default,
+                    ..Default::default()
                 },
             ),
             #[cfg(feature = "serde")]
@@ -39,6 +47,7 @@ mod tests {
                         json: JsonOutput::JsonDefault,
                         pretty: false,
                     },
+                    ..Default::default()
                 },
             ),
             #[cfg(feature = "serde")]
@@ -55,6 +64,25 @@ mod tests {
                         json: JsonOutput::JsonDefault,
                         pretty: false,
                     },
+                    ..Default::default()
+                },
+            ),
+            #[cfg(feature = "serde")]
+            (
+                vec![
+                    "notox".to_string(),
+                    "README.md".to_string(),
+                    "-d".to_string(),
+                    "--json-tree".to_string(),
+                    "-p".to_string(),
+                ],
+                NotoxArgs {
+                    dry_run: false,
+                    output: Output::JsonOutput {
+                        json: JsonOutput::JsonTree,
+                        pretty: true,
+                    },
+                    ..Default::default()
                 },
             ),
             #[cfg(feature = "serde")]
@@ -71,6 +99,7 @@ mod tests {
                         json: JsonOutput::JsonOnlyError,
                         pretty: false,
                     },
+                    ..Default::default()
                 },
             ),
             #[cfg(feature = "serde")]
@@ -87,6 +116,7 @@ mod tests {
                         json: JsonOutput::JsonOnlyError,
                         pretty: false,
                     },
+                    ..Default::default()
                 },
             ),
             #[cfg(feature = "serde")]
@@ -103,6 +133,7 @@ mod tests {
                         json: JsonOutput::JsonDefault,
                         pretty: true,
                     },
+                    ..Default::default()
                 },
             ),
             #[cfg(feature = "serde")]
@@ -119,6 +150,7 @@ mod tests {
                         json: JsonOutput::JsonDefault,
                         pretty: true,
                     },
+                    ..Default::default()
                 },
             ),
             (
@@ -126,6 +158,7 @@ mod tests {
                 NotoxArgs {
                     dry_run: true,
                     output: Output::Default,
+                    ..Default::default()
                 },
             ),
             (
@@ -133,6 +166,7 @@ mod tests {
                 NotoxArgs {
                     dry_run: true,
                     output: Output::Default,
+                    ..Default::default()
                 },
             ),
             (
@@ -140,6 +174,7 @@ mod tests {
                 NotoxArgs {
                     dry_run: true,
                     output: Output::Quiet,
+                    ..Default::default()
                 },
             ),
             (
@@ -147,6 +182,7 @@ mod tests {
                 NotoxArgs {
                     dry_run: true,
                     output: Output::Quiet,
+                    ..Default::default()
                 },
             ),
         ];
@@ -157,7 +193,7 @@ mod tests {
                 assert_eq!(ok_res.0, one_test.1, "Args: {:?}", one_test.0);
             } else {
                 println!("Error parsing args: {:?} {:?}", res, one_test.0);
-                assert_eq!(res.err().unwrap(), 1);
+                assert_eq!(res.err().unwrap(), ExitCode::Usage);
             }
         }
     }
@@ -176,6 +212,7 @@ mod tests {
             NotoxArgs {
                 dry_run: true,
                 output: Output::Default,
+                ..Default::default()
             }
         );
         assert_eq!(vect.len(), 1);
@@ -191,6 +228,7 @@ mod tests {
             NotoxArgs {
                 dry_run: true,
                 output: Output::Default,
+                ..Default::default()
             }
         );
         let number = std::fs::read_dir(".")
@@ -200,4 +238,472 @@ mod tests {
             .count();
         assert_eq!(vect.len(), number);
     }
+
+    #[test]
+    fn test_parse_args_no_rename_dir_missing_name() {
+        let vec_args = ["notox".to_string(), "--no-rename-dir".to_string()];
+        let res = notox::parse_args(&vec_args);
+        assert_eq!(res.err().unwrap(), ExitCode::Usage);
+    }
+
+    #[test]
+    fn test_parse_args_empty_path() {
+        for empty in ["", " ", "\t\n"] {
+            let vec_args = ["notox".to_string(), "-q".to_string(), empty.to_string()];
+            assert_eq!(
+                notox::parse_args(&vec_args).err(),
+                Some(ExitCode::Usage),
+                "{:?}",
+                empty
+            );
+        }
+        // even with a valid path, an empty one is an error
+        let vec_args = [
+            "notox".to_string(),
+            "Cargo.toml".to_string(),
+            "".to_string(),
+        ];
+        assert_eq!(notox::parse_args(&vec_args).err(), Some(ExitCode::Usage));
+    }
+
+    #[test]
+    fn test_parse_args_missing_paths() {
+        // the current directory is not cleaned instead of the missing paths
+        let vec_args = ["notox", "-d", "does_not_exist"].map(String::from);
+        assert_eq!(
+            notox::parse_args(&vec_args).err(),
+            Some(ExitCode::NoPathsFound)
+        );
+        let vec_args = ["notox", "-q", "does_not_exist", "does_not_exist_either"].map(String::from);
+        assert_eq!(
+            notox::parse_args(&vec_args).err(),
+            Some(ExitCode::NoPathsFound)
+        );
+        // a missing path next to a found one is only reported
+        let vec_args = ["notox", "-q", "does_not_exist", "Cargo.toml"].map(String::from);
+        let (_, paths) = notox::parse_args(&vec_args).unwrap();
+        assert_eq!(paths.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_args_allow() {
+        let args = |value: &str| {
+            vec![
+                "notox".to_string(),
+                "--allow".to_string(),
+                value.to_string(),
+            ]
+        };
+        assert_eq!(
+            notox::parse_args(&args("+-")).unwrap().0.allowed_extra,
+            ['+', '-'].into()
+        );
+        assert_eq!(notox::parse_args(&args("+_")), Err(ExitCode::Usage));
+        assert_eq!(notox::parse_args(&args("é")), Err(ExitCode::Usage));
+        assert_eq!(notox::parse_args(&args("")), Err(ExitCode::Usage));
+        assert_eq!(
+            notox::parse_args(&["notox".to_string(), "--allow".to_string()]),
+            Err(ExitCode::Usage)
+        );
+    }
+
+    #[test]
+    fn test_parse_args_empty_placeholder() {
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--empty-placeholder".to_string(),
+            "unnamed".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).unwrap();
+        assert_eq!(options.empty_stem_placeholder, Some("unnamed".to_string()));
+
+        let vec_args = ["notox".to_string(), "--empty-placeholder".to_string()];
+        assert_eq!(notox::parse_args(&vec_args).err().unwrap(), ExitCode::Usage);
+    }
+
+    #[test]
+    #[cfg(feature = "encoding")]
+    fn test_parse_args_from_encoding() {
+        let args = |label: &str| {
+            vec![
+                "notox".to_string(),
+                "--from-encoding".to_string(),
+                label.to_string(),
+                "Cargo.toml".to_string(),
+            ]
+        };
+        let (notox_args, _) = notox::parse_args(&args("latin1")).unwrap();
+        assert_eq!(notox_args.source_encoding, Some(encoding_rs::WINDOWS_1252));
+        assert_eq!(
+            notox::parse_args(&args("not-an-encoding")),
+            Err(ExitCode::Usage)
+        );
+    }
+
+    #[test]
+    fn test_parse_args_ext_allowed_chars() {
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--ext-allowed-chars".to_string(),
+            "+#".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).unwrap();
+        assert_eq!(options.ext_allowed_chars, HashSet::from(['+', '#']));
+
+        let vec_args = ["notox".to_string(), "--ext-allowed-chars".to_string()];
+        assert_eq!(notox::parse_args(&vec_args).err().unwrap(), ExitCode::Usage);
+    }
+
+    #[test]
+    fn test_parse_args_keep_spaces() {
+        let (notox_args, _) = notox::parse_args(&[
+            "notox".to_string(),
+            "--keep-spaces".to_string(),
+            "Cargo.toml".to_string(),
+        ])
+        .unwrap();
+        assert!(notox_args.keep_spaces);
+    }
+
+    #[test]
+    #[cfg(feature = "korean")]
+    fn test_parse_args_romanize_hangul() {
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--romanize-hangul".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).unwrap();
+        assert!(options.romanize_hangul);
+    }
+
+    #[test]
+    fn test_parse_args_non_utf8() {
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--non-utf8".to_string(),
+            "per-byte".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).unwrap();
+        assert_eq!(options.non_utf8_policy, NonUtf8Policy::PerByte);
+
+        let vec_args = [
+            "notox".to_string(),
+            "--non-utf8".to_string(),
+            "many".to_string(),
+        ];
+        assert_eq!(notox::parse_args(&vec_args).err().unwrap(), ExitCode::Usage);
+    }
+
+    #[test]
+    fn test_parse_args_post_separator_case() {
+        let args = |value: &str| {
+            vec![
+                "notox".to_string(),
+                "--post-separator-case".to_string(),
+                value.to_string(),
+                "Cargo.toml".to_string(),
+            ]
+        };
+        let (notox_args, _) = notox::parse_args(&args("upper")).unwrap();
+        assert_eq!(notox_args.post_separator_case, Some(CaseMode::Upper));
+        let (notox_args, _) = notox::parse_args(&args("lower")).unwrap();
+        assert_eq!(notox_args.post_separator_case, Some(CaseMode::Lower));
+        assert_eq!(notox::parse_args(&args("title")), Err(ExitCode::Usage));
+    }
+
+    #[test]
+    fn test_parse_args_profile() {
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--profile".to_string(),
+            "SharePoint".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).ok().unwrap();
+        assert_eq!(options.profile, Profile::SharePoint);
+
+        for (name, profile) in [
+            ("windows", Profile::WindowsSafe),
+            ("POSIX", Profile::PortablePosix),
+        ] {
+            let vec_args = ["notox", "README.md", "--profile", name].map(String::from);
+            let (options, _) = notox::parse_args(&vec_args).ok().unwrap();
+            assert_eq!(options.profile, profile, "{}", name);
+        }
+
+        let vec_args = [
+            "notox".to_string(),
+            "--profile".to_string(),
+            "unknown".to_string(),
+        ];
+        assert_eq!(notox::parse_args(&vec_args).err().unwrap(), ExitCode::Usage);
+    }
+
+    #[test]
+    fn test_parse_args_max_separators() {
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--max-separators".to_string(),
+            "2".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).ok().unwrap();
+        assert_eq!(options.max_consecutive_separators, 2);
+
+        let vec_args = [
+            "notox".to_string(),
+            "--max-separators".to_string(),
+            "two".to_string(),
+        ];
+        assert_eq!(notox::parse_args(&vec_args).err().unwrap(), ExitCode::Usage);
+    }
+
+    #[test]
+    fn test_parse_args_space_replacement() {
+        let args = |value: &str| {
+            vec![
+                "notox".to_string(),
+                "--space-replacement".to_string(),
+                value.to_string(),
+            ]
+        };
+        assert_eq!(
+            notox::parse_args(&args("-")).unwrap().0.space_replacement,
+            Some('-')
+        );
+        assert_eq!(notox::parse_args(&args("--")), Err(ExitCode::Usage));
+        assert_eq!(notox::parse_args(&args("/")), Err(ExitCode::Usage));
+        assert_eq!(notox::parse_args(&args("é")), Err(ExitCode::Usage));
+        assert_eq!(
+            notox::parse_args(&["notox".to_string(), "--space-replacement".to_string()]),
+            Err(ExitCode::Usage)
+        );
+    }
+
+    #[test]
+    fn test_parse_args_strip_accents_only() {
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--strip-accents-only".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).unwrap();
+        assert_eq!(options.categories, vec![Category::Accents]);
+        assert!(!options.cleans_all_categories());
+    }
+
+    #[test]
+    fn test_parse_args_mode() {
+        let vec_args = ["notox", "README.md", "--mode", "unsafe-only"].map(String::from);
+        let (options, _) = notox::parse_args(&vec_args).ok().unwrap();
+        assert_eq!(options.mode, CleanMode::UnsafeOnly);
+
+        let vec_args = ["notox", "README.md", "--mode", "Transliterate"].map(String::from);
+        let (options, _) = notox::parse_args(&vec_args).ok().unwrap();
+        assert_eq!(options.mode, CleanMode::Transliterate);
+
+        for vec_args in [
+            vec!["notox", "--mode", "unknown"],
+            vec!["notox", "README.md", "--mode"],
+        ] {
+            let vec_args: Vec<String> = vec_args.into_iter().map(String::from).collect();
+            assert_eq!(notox::parse_args(&vec_args).err().unwrap(), ExitCode::Usage);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod config {
+    use std::{
+        ffi::OsString,
+        fs,
+        path::{Path, PathBuf},
+        process::Command,
+    };
+
+    use notox::{
+        parse_args, parse_args_with_config, Config, ConfigOutput, ExitCode, JsonOutput, Output,
+    };
+
+    use crate::common::args;
+
+    fn write_config(dir: &Path, content: &str) -> String {
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir(dir).unwrap();
+        let config_path = dir.join("notox.json");
+        fs::write(&config_path, content).unwrap();
+        config_path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let dir = PathBuf::from("test_config_defaults");
+        let config_path = write_config(
+            &dir,
+            r#"{"dry_run": false, "output": "quiet", "exclude": ["*.lock"], "no_rename_dirs": ["build"], "max_depth": 2}"#,
+        );
+        let (options, _) = parse_args(&args(&["--config", &config_path, "."])).unwrap();
+        assert!(!options.dry_run);
+        assert_eq!(options.output, Output::Quiet);
+        assert_eq!(options.exclude, vec!["*.lock".to_string()]);
+        assert_eq!(options.no_rename_dirs, vec![OsString::from("build")]);
+        assert_eq!(options.max_depth, Some(2));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_overridden_by_flags() {
+        let dir = PathBuf::from("test_config_overridden_by_flags");
+        let config_path = write_config(
+            &dir,
+            r#"{"output": "quiet", "exclude": ["*.lock"], "max_depth": 2}"#,
+        );
+        let (options, _) = parse_args(&args(&[
+            "-j",
+            "--max-depth",
+            "5",
+            "--exclude",
+            "node_modules",
+            "--config",
+            &config_path,
+            ".",
+        ]))
+        .unwrap();
+        assert_eq!(
+            options.output,
+            Output::JsonOutput {
+                json: JsonOutput::JsonDefault,
+                pretty: false
+            }
+        );
+        assert_eq!(options.max_depth, Some(5));
+        assert_eq!(
+            options.exclude,
+            vec!["*.lock".to_string(), "node_modules".to_string()]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_invalid() {
+        let dir = PathBuf::from("test_config_invalid");
+        let config_path = write_config(&dir, r#"{"unknown_option": true}"#);
+        assert_eq!(
+            parse_args(&args(&["--config", &config_path])),
+            Err(ExitCode::Usage)
+        );
+        assert_eq!(
+            parse_args(&args(&["--config", "test_config_invalid/missing.json"])),
+            Err(ExitCode::Usage)
+        );
+        assert_eq!(parse_args(&args(&["--config"])), Err(ExitCode::Usage));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_dry_run_and_no_config() {
+        let dir = PathBuf::from("test_config_dry_run_and_no_config");
+        let config_path = write_config(&dir, r#"{"dry_run": false, "output": "quiet"}"#);
+        let (options, _) =
+            parse_args(&args(&["--config", &config_path, "--dry-run", "."])).unwrap();
+        assert!(options.dry_run);
+        assert_eq!(options.output, Output::Quiet);
+        let (options, _) =
+            parse_args(&args(&["--config", &config_path, "--no-config", "."])).unwrap();
+        assert!(options.dry_run);
+        assert_eq!(options.output, Output::Default);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_flag_values() {
+        let dir = PathBuf::from("test_config_flag_values");
+        let config_path = write_config(&dir, r#"{"output": "quiet"}"#);
+        // the values of the other flags are not read as config flags
+        let (options, _) =
+            parse_args(&args(&["--exclude", "-h", "--config", &config_path, "."])).unwrap();
+        assert_eq!(options.output, Output::Quiet);
+        assert_eq!(options.exclude, vec!["-h".to_string()]);
+        let (options, _) = parse_args(&args(&["--exclude", "--config", &config_path])).unwrap();
+        assert_eq!(options.output, Output::Default);
+        assert_eq!(options.exclude, vec!["--config".to_string()]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_default_file() {
+        let dir = PathBuf::from("test_config_default_file");
+        let config_path = write_config(&dir, r#"{"output": "quiet"}"#);
+        let default_config = Some(Path::new(&config_path));
+        let (options, _) = parse_args_with_config(&args(&["."]), default_config).unwrap();
+        assert_eq!(options.output, Output::Quiet);
+        let (options, _) =
+            parse_args_with_config(&args(&["--no-config", "."]), default_config).unwrap();
+        assert_eq!(options.output, Output::Default);
+        // a missing default config is ignored
+        let missing = dir.join("missing.json");
+        let (options, _) = parse_args_with_config(&args(&["."]), Some(&missing)).unwrap();
+        assert_eq!(options.output, Output::Default);
+        // the default config cannot disable the dry-run
+        fs::write(&config_path, r#"{"dry_run": false}"#).unwrap();
+        assert_eq!(
+            parse_args_with_config(&args(&["."]), default_config),
+            Err(ExitCode::Usage)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn run_binary(dir: &Path, args: &[&str]) -> (Option<i32>, String) {
+        let output = Command::new(env!("CARGO_BIN_EXE_notox"))
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        )
+    }
+
+    #[test]
+    fn test_config_of_current_dir() {
+        let dir = PathBuf::from("test_config_of_current_dir");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a b"), "").unwrap();
+        fs::write(dir.join(".notox.json"), r#"{"dry_run": false}"#).unwrap();
+        // the config found in the current directory cannot rename without being asked
+        let (code, _) = run_binary(&dir, &["a b"]);
+        assert_eq!(code, Some(1));
+        assert!(dir.join("a b").exists());
+        let (code, _) = run_binary(&dir, &["--no-config", "a b"]);
+        assert_eq!(code, Some(0));
+        assert!(dir.join("a b").exists());
+
+        // the help and the version do not read the config
+        fs::write(dir.join(".notox.json"), "{ not json").unwrap();
+        let (_, stdout) = run_binary(&dir, &["--help"]);
+        assert!(stdout.starts_with("Usage: notox"), "{}", stdout);
+        let (_, stdout) = run_binary(&dir, &["--version"]);
+        assert!(stdout.starts_with("notox"), "{}", stdout);
+        let (code, stdout) = run_binary(&dir, &["a b"]);
+        assert_eq!(code, Some(1));
+        assert!(stdout.starts_with("Cannot read the config"), "{}", stdout);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_from_file() {
+        let dir = PathBuf::from("test_config_from_file");
+        let config_path = write_config(&dir, r#"{"output": "json-pretty", "trim": true}"#);
+        let config = Config::from_file(Path::new(&config_path)).unwrap();
+        assert_eq!(config.output, Some(ConfigOutput::JsonPretty));
+        assert_eq!(config.trim, Some(true));
+        assert_eq!(config.dry_run, None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,3 +1,5 @@
+mod common;
+
 #[cfg(test)]
 mod tests {
     // Add methods on commands
@@ -59,11 +61,13 @@ mod tests {
                     "path": "README.md",
                     "modified": null,
                     "error": null,
+                    "status": "unchanged",
                 },
                 {
                     "path": "Cargo.toml",
                     "modified": null,
                     "error": null,
+                    "status": "unchanged",
                 }
             ]);
             (json_val, ("README.md", "Cargo.toml"))
@@ -73,17 +77,19 @@ mod tests {
                     "path": "Cargo.toml",
                     "modified": null,
                     "error": null,
+                    "status": "unchanged",
                 },
                 {
                     "path": "README.md",
                     "modified": null,
                     "error": null,
+                    "status": "unchanged",
                 }
             ]);
             (json_val, ("Cargo.toml", "README.md"))
         };
         let str_json = format!(
-            r#"[{{"path":"{}","modified":null,"error":null}},{{"path":"{}","modified":null,"error":null}}]
+            r#"[{{"path":"{}","modified":null,"error":null,"status":"unchanged"}},{{"path":"{}","modified":null,"error":null,"status":"unchanged"}}]
 "#,
             name1, name2
         );
@@ -109,11 +115,13 @@ mod tests {
                     "path": "README.md",
                     "modified": null,
                     "error": null,
+                    "status": "unchanged",
                 },
                 {
                     "path": "Cargo.toml",
                     "modified": null,
                     "error": null,
+                    "status": "unchanged",
                 }
             ]);
             (json_val, ("README.md", "Cargo.toml"))
@@ -123,11 +131,13 @@ mod tests {
                     "path": "Cargo.toml",
                     "modified": null,
                     "error": null,
+                    "status": "unchanged",
                 },
                 {
                     "path": "README.md",
                     "modified": null,
                     "error": null,
+                    "status": "unchanged",
                 }
             ]);
             (json_val, ("Cargo.toml", "README.md"))
@@ -137,12 +147,14 @@ mod tests {
   {{
     "path": "{}",
     "modified": null,
-    "error": null
+    "error": null,
+    "status": "unchanged"
   }},
   {{
     "path": "{}",
     "modified": null,
-    "error": null
+    "error": null,
+    "status": "unchanged"
   }}
 ]
 "#,
@@ -166,6 +178,7 @@ mod tests {
                 json: JsonOutput::JsonDefault,
                 pretty: true,
             },
+            ..Default::default()
         };
         let path_to_check: HashSet<PathBuf> =
             HashSet::from(["README.md".into(), "Cargo.toml".into()]);
@@ -181,4 +194,488 @@ mod tests {
 
         assert_eq!(result_lib, result_bin);
     }
+
+    #[test]
+    fn test_main_no_paths_found() {
+        let empty_dir = std::path::PathBuf::from("test_main_no_paths_found");
+        if empty_dir.exists() {
+            std::fs::remove_dir_all(&empty_dir).unwrap();
+        }
+        std::fs::create_dir(&empty_dir).unwrap();
+        let mut cmd = Command::cargo_bin("notox").unwrap();
+
+        cmd.current_dir(&empty_dir);
+        cmd.assert()
+            .code(3)
+            .stdout(predicate::str::contains("0 files checked"));
+        std::fs::remove_dir(&empty_dir).unwrap();
+    }
+
+    /// Restore the permissions of a test directory and remove it, even if the test fails
+    #[cfg(unix)]
+    struct RemoveOnDrop(std::path::PathBuf);
+
+    #[cfg(unix)]
+    impl Drop for RemoveOnDrop {
+        fn drop(&mut self) {
+            use std::os::unix::fs::PermissionsExt;
+
+            let _ = std::fs::set_permissions(&self.0, std::fs::Permissions::from_mode(0o755));
+            let _ = std::fs::remove_dir(&self.0);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_main_unreadable_current_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::path::PathBuf::from("test_main_unreadable_current_dir");
+        if dir.exists() {
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir(&dir).unwrap();
+        let _guard = RemoveOnDrop(dir.clone());
+        // can be entered but not listed
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o300)).unwrap();
+        if std::fs::read_dir(&dir).is_ok() {
+            // the permissions are not enforced, like for root
+            return;
+        }
+        let mut cmd = Command::cargo_bin("notox").unwrap();
+
+        cmd.current_dir(&dir);
+        cmd.assert().code(2).stdout(predicate::str::contains(
+            "Cannot read the current directory",
+        ));
+    }
+
+    #[test]
+    fn test_exit_code_values() {
+        use notox::ExitCode;
+        assert_eq!(i32::from(ExitCode::Success), 0);
+        assert_eq!(i32::from(ExitCode::Usage), 1);
+        assert_eq!(i32::from(ExitCode::FeatureUnavailable), 2);
+        assert_eq!(i32::from(ExitCode::SerializeError), 2);
+        assert_eq!(i32::from(ExitCode::OutputError), 2);
+        assert_eq!(i32::from(ExitCode::WatchError), 2);
+        assert_eq!(i32::from(ExitCode::NoPathsFound), 3);
+        assert_eq!(i32::from(ExitCode::ReadError), 2);
+        assert_eq!(i32::from(ExitCode::ChangesNeeded), 4);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_main_version_json() {
+        use serde_json::Value;
+        let mut cmd = Command::cargo_bin("notox").unwrap();
+
+        cmd.arg("--version-json");
+        cmd.assert().failure();
+        let stdout = String::from_utf8(cmd.output().unwrap().stdout).unwrap();
+        let version: Value = serde_json::from_str(&stdout).unwrap();
+        assert_eq!(version["version"], env!("CARGO_PKG_VERSION"));
+        let features = version["features"].as_array().unwrap();
+        assert!(features.contains(&Value::from("serde")));
+        assert_eq!(
+            features.contains(&Value::from("rayon")),
+            cfg!(feature = "rayon")
+        );
+        assert_eq!(
+            features.contains(&Value::from("korean")),
+            cfg!(feature = "korean")
+        );
+        assert_eq!(
+            features.contains(&Value::from("encoding")),
+            cfg!(feature = "encoding")
+        );
+        assert_eq!(
+            features.contains(&Value::from("normalize")),
+            cfg!(feature = "normalize")
+        );
+        assert_eq!(
+            features.contains(&Value::from("tokio")),
+            cfg!(feature = "tokio")
+        );
+    }
+}
+
+mod check {
+    use std::{fs, path::PathBuf, process::Command};
+
+    use notox::{parse_args, ExitCode};
+
+    fn run_check(dir: &PathBuf) -> Option<i32> {
+        Command::new(env!("CARGO_BIN_EXE_notox"))
+            .args(["--check", "-d", "-q"])
+            .arg(dir)
+            .status()
+            .unwrap()
+            .code()
+    }
+
+    #[test]
+    fn test_check_changes_needed() {
+        let dir = PathBuf::from("test_check_changes_needed");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("café.txt"), "").unwrap();
+        assert_eq!(run_check(&dir), Some(i32::from(ExitCode::ChangesNeeded)));
+        // nothing is renamed, even with -d
+        assert!(dir.join("café.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_clean() {
+        let dir = PathBuf::from("test_check_clean");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("cafe.txt"), "").unwrap();
+        assert_eq!(run_check(&dir), Some(0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_check() {
+        let args = vec!["notox".to_string(), "-d".to_string(), "--check".to_string()];
+        let (options, _) = parse_args(&args).unwrap();
+        assert!(options.check);
+        assert!(options.dry_run);
+    }
+}
+
+mod interactive {
+    use std::{
+        fs,
+        io::Write,
+        path::PathBuf,
+        process::{Command, Stdio},
+    };
+
+    use notox::{parse_args, prompt_decisions, Decision, ExitCode, PathChange};
+
+    fn proposed(name: &str) -> PathChange {
+        PathChange::WouldChange {
+            path: PathBuf::from(name),
+            modified: PathBuf::from(name.replace(' ', "_")),
+        }
+    }
+
+    #[test]
+    fn test_prompt_decisions() {
+        let mut prompt = Vec::new();
+        let mut decide = prompt_decisions("y\n\nnope\nq\n".as_bytes(), &mut prompt);
+        assert_eq!(decide(&proposed("a b")), Decision::Apply);
+        assert_eq!(decide(&proposed("c d")), Decision::Skip);
+        assert_eq!(decide(&proposed("e f")), Decision::Skip);
+        assert_eq!(decide(&proposed("g h")), Decision::Abort);
+        // the end of the input aborts
+        assert_eq!(decide(&proposed("i j")), Decision::Abort);
+        drop(decide);
+        let prompt = String::from_utf8(prompt).unwrap();
+        assert!(prompt.starts_with("Rename a b -> a_b? [y/N/a/q] Rename c d -> c_d?"));
+    }
+
+    #[test]
+    fn test_prompt_decisions_all() {
+        let mut prompt = Vec::new();
+        let mut decide = prompt_decisions("n\nA\n".as_bytes(), &mut prompt);
+        assert_eq!(decide(&proposed("a b")), Decision::Skip);
+        assert_eq!(decide(&proposed("c d")), Decision::Apply);
+        assert_eq!(decide(&proposed("e f")), Decision::Apply);
+        assert_eq!(decide(&proposed("g h")), Decision::Apply);
+        drop(decide);
+        // nothing is asked once all the renames are approved
+        assert_eq!(
+            String::from_utf8(prompt).unwrap().matches("Rename").count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_interactive_binary() {
+        let dir = PathBuf::from("test_interactive_binary");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a b.txt"), "").unwrap();
+        fs::write(dir.join("c d.txt"), "").unwrap();
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_notox"))
+            .arg("-i")
+            .arg("-d")
+            .arg(&dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"a\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(stderr.matches("? [y/N/a/q] ").count(), 1);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(!stdout.contains("[y/N/a/q]"));
+        assert!(dir.join("a_b.txt").exists());
+        assert!(dir.join("c_d.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_interactive() {
+        let vec_args = ["notox".to_string(), "-i".to_string(), ".".to_string()];
+        assert!(parse_args(&vec_args).unwrap().0.interactive);
+        let mut others = vec!["-q", "--category-report"];
+        if cfg!(feature = "serde") {
+            others.push("-j");
+        }
+        for other in others {
+            let vec_args = [
+                "notox".to_string(),
+                "--interactive".to_string(),
+                other.to_string(),
+                ".".to_string(),
+            ];
+            assert_eq!(
+                parse_args(&vec_args).err(),
+                Some(ExitCode::Usage),
+                "{}",
+                other
+            );
+        }
+    }
+}
+
+mod null {
+    use std::{
+        fs,
+        io::Write,
+        path::PathBuf,
+        process::{Command, Stdio},
+    };
+
+    use notox::{read_null_paths, write_null_pairs, PathChange};
+
+    fn run_binary(args: &[&str], input: &[u8]) -> (Option<i32>, Vec<u8>) {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_notox"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        // notox may exit before reading, like with invalid arguments
+        let _ = child.stdin.take().unwrap().write_all(input);
+        let output = child.wait_with_output().unwrap();
+        (output.status.code(), output.stdout)
+    }
+
+    #[test]
+    fn test_read_null_paths() {
+        let input = b"a\nb.txt\0\0some dir/c d\0last";
+        assert_eq!(
+            read_null_paths(&input[..]).unwrap(),
+            vec![
+                PathBuf::from("a\nb.txt"),
+                PathBuf::from("some dir/c d"),
+                PathBuf::from("last"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_null_pairs() {
+        let changes = vec![
+            PathChange::Changed {
+                path: PathBuf::from("a\nb"),
+                modified: PathBuf::from("a_b"),
+            },
+            PathChange::Unchanged {
+                path: PathBuf::from("clean"),
+            },
+            PathChange::WouldChange {
+                path: PathBuf::from("c d"),
+                modified: PathBuf::from("c_d"),
+            },
+        ];
+        let mut output = Vec::new();
+        write_null_pairs(&changes, &mut output).unwrap();
+        assert_eq!(output, b"a\nb\0a_b\0c d\0c_d\0");
+    }
+
+    #[test]
+    fn test_null_newline_name() {
+        let dir = PathBuf::from("test_null_newline_name");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("a\nb.txt"), "").unwrap();
+        fs::write(dir.join("clean.txt"), "").unwrap();
+        let input = b"test_null_newline_name/a\nb.txt\0test_null_newline_name/clean.txt\0";
+        let (code, stdout) = run_binary(&["-0", "-d"], input);
+        assert_eq!(code, Some(0));
+        assert_eq!(
+            stdout,
+            b"test_null_newline_name/a\nb.txt\0test_null_newline_name/a_b.txt\0"
+        );
+        assert!(dir.join("a_b.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_null_empty_input() {
+        // nothing read is nothing to do, the current directory is not cleaned
+        let (code, stdout) = run_binary(&["--null"], b"");
+        assert_eq!(code, Some(3));
+        assert!(stdout.is_empty());
+    }
+
+    #[test]
+    fn test_null_with_stdin() {
+        let (code, _) = run_binary(&["-0", "--stdin"], b"a b\0");
+        assert_eq!(code, Some(1));
+    }
+}
+
+mod stdin {
+    use std::{
+        io::{BufReader, Read, Write},
+        path::PathBuf,
+        process::{Command, Stdio},
+    };
+
+    use notox::{clean_lines, parse_args, NotoxArgs, PathChange};
+
+    fn run_binary(args: &[&str], input: impl AsRef<[u8]>) -> (Option<i32>, String) {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_notox"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_ref())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_clean_lines() {
+        let input = "a b.txt\n\nclean.txt\r\nsome dir/Été.mp3\n";
+        let res = clean_lines(input.as_bytes(), &NotoxArgs::default()).unwrap();
+        assert_eq!(
+            res,
+            vec![
+                PathChange::WouldChange {
+                    path: PathBuf::from("a b.txt"),
+                    modified: PathBuf::from("a_b.txt"),
+                },
+                PathChange::Unchanged {
+                    path: PathBuf::from("clean.txt"),
+                },
+                PathChange::WouldChange {
+                    path: PathBuf::from("some dir/Été.mp3"),
+                    modified: PathBuf::from("some dir/Ete.mp3"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_clean_lines_not_utf8() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let input = b"a b\n\xff\xfe bad\nc d\n";
+        let res = clean_lines(&input[..], &NotoxArgs::default()).unwrap();
+        assert_eq!(
+            res,
+            vec![
+                PathChange::WouldChange {
+                    path: PathBuf::from("a b"),
+                    modified: PathBuf::from("a_b"),
+                },
+                PathChange::WouldChange {
+                    path: PathBuf::from(OsStr::from_bytes(b"\xff\xfe bad")),
+                    modified: PathBuf::from("_bad"),
+                },
+                PathChange::WouldChange {
+                    path: PathBuf::from("c d"),
+                    modified: PathBuf::from("c_d"),
+                },
+            ]
+        );
+        let (code, stdout) = run_binary(&["--stdin"], input);
+        assert_eq!(code, Some(0));
+        assert_eq!(stdout, "a_b\n_bad\nc_d\n");
+    }
+
+    /// Reader failing after its first line
+    struct FailingReader {
+        /// the first line was read
+        done: bool,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.done {
+                return Err(std::io::Error::other("broken"));
+            }
+            self.done = true;
+            buf[..4].copy_from_slice(b"a b\n");
+            Ok(4)
+        }
+    }
+
+    #[test]
+    fn test_clean_lines_read_error() {
+        let reader = BufReader::new(FailingReader { done: false });
+        assert!(clean_lines(reader, &NotoxArgs::default()).is_err());
+    }
+
+    #[test]
+    fn test_stdin_binary() {
+        let input = "test stdin not a file.txt\nclean.txt\n";
+        let (code, stdout) = run_binary(&["--stdin", "-d"], input);
+        assert_eq!(code, Some(0));
+        assert_eq!(stdout, "test_stdin_not_a_file.txt\nclean.txt\n");
+        assert!(!PathBuf::from("test_stdin_not_a_file.txt").exists());
+
+        let (code, stdout) = run_binary(&["--stdin"], "");
+        assert_eq!(code, Some(3));
+        assert_eq!(stdout, "");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_stdin_json_lines() {
+        let (code, stdout) = run_binary(&["--stdin", "--json-lines"], "a b.txt\n");
+        assert_eq!(code, Some(0));
+        let records: Vec<PathChange> = stdout
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            records,
+            vec![PathChange::WouldChange {
+                path: PathBuf::from("a b.txt"),
+                modified: PathBuf::from("a_b.txt"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_args_stdin() {
+        let vec_args = ["notox".to_string(), "--stdin".to_string()];
+        let res = parse_args(&vec_args).unwrap();
+        assert!(res.0.stdin);
+    }
 }
@@ -1,10 +1,12 @@
+mod common;
+
 #[cfg(test)]
 mod tests {
     use std::{collections::HashSet, path::PathBuf};
 
     #[cfg(feature = "serde")]
     use notox::JsonOutput;
-    use notox::{Notox, NotoxArgs, Output};
+    use notox::{Notox, NotoxArgs, Output, PathChange};
 
     #[test]
     fn test_print_output() {
@@ -12,18 +14,22 @@ mod tests {
             NotoxArgs {
                 dry_run: true,
                 output: Output::Default,
+                ..Default::default()
             },
             NotoxArgs {
                 dry_run: false,
                 output: Output::Default,
+                ..Default::default()
             },
             NotoxArgs {
                 dry_run: true,
                 output: Output::Quiet,
+                ..Default::default()
             },
             NotoxArgs {
                 dry_run: false,
                 output: Output::Quiet,
+                ..Default::default()
             },
             #[cfg(feature = "serde")]
             NotoxArgs {
@@ -32,6 +38,7 @@ mod tests {
                     json: JsonOutput::JsonDefault,
                     pretty: false,
                 },
+                ..Default::default()
             },
             #[cfg(feature = "serde")]
             NotoxArgs {
@@ -40,6 +47,7 @@ mod tests {
                     json: JsonOutput::JsonDefault,
                     pretty: false,
                 },
+                ..Default::default()
             },
             #[cfg(feature = "serde")]
             NotoxArgs {
@@ -48,6 +56,7 @@ mod tests {
                     json: JsonOutput::JsonDefault,
                     pretty: true,
                 },
+                ..Default::default()
             },
             #[cfg(feature = "serde")]
             NotoxArgs {
@@ -56,6 +65,7 @@ mod tests {
                     json: JsonOutput::JsonOnlyError,
                     pretty: false,
                 },
+                ..Default::default()
             },
             #[cfg(feature = "serde")]
             NotoxArgs {
@@ -64,6 +74,7 @@ mod tests {
                     json: JsonOutput::JsonOnlyError,
                     pretty: false,
                 },
+                ..Default::default()
             },
             #[cfg(feature = "serde")]
             NotoxArgs {
@@ -72,6 +83,7 @@ mod tests {
                     json: JsonOutput::JsonOnlyError,
                     pretty: true,
                 },
+                ..Default::default()
             },
         ];
         for options in args.iter() {
@@ -120,6 +132,7 @@ mod tests {
         let options = NotoxArgs {
             dry_run: true,
             output: Output::Default,
+            ..Default::default()
         };
         let to_correct = PathBuf::from("tes t verbose dry.txt");
         let read_only = PathBuf::from("test_verbose_dry.txt");
@@ -143,6 +156,7 @@ mod tests {
         let options = NotoxArgs {
             dry_run: false,
             output: Output::Default,
+            ..Default::default()
         };
         let to_correct = PathBuf::from("tes t verbose.txt");
         let read_only = PathBuf::from("test_verbose.txt");
@@ -170,6 +184,7 @@ mod tests {
                 json: JsonOutput::JsonDefault,
                 pretty: false,
             },
+            ..Default::default()
         };
         let to_correct = PathBuf::from("tes t json.txt");
         let read_only = PathBuf::from("test_json.txt");
@@ -197,6 +212,7 @@ mod tests {
                 json: JsonOutput::JsonOnlyError,
                 pretty: false,
             },
+            ..Default::default()
         };
         let to_correct = PathBuf::from("tes t json error.txt");
         let read_only = PathBuf::from("test_json_error.txt");
@@ -224,6 +240,7 @@ mod tests {
                 json: JsonOutput::JsonOnlyError,
                 pretty: false,
             },
+            ..Default::default()
         };
         let to_correct = PathBuf::from("tes t json error dry.txt");
         let read_only = PathBuf::from("test_json_error_dry.txt");
@@ -241,4 +258,1554 @@ mod tests {
         // cleanup
         cleanup(&to_correct, &read_only)
     }
+
+    fn default_output(show_unchanged: bool) -> String {
+        let options = NotoxArgs {
+            dry_run: true,
+            output: Output::Default,
+            show_unchanged,
+            ..Default::default()
+        };
+        let final_res = vec![
+            PathChange::Unchanged {
+                path: PathBuf::from("clean.txt"),
+            },
+            PathChange::WouldChange {
+                path: PathBuf::from("not clean.txt"),
+                modified: PathBuf::from("not_clean.txt"),
+            },
+            PathChange::Symlink {
+                path: PathBuf::from("link"),
+                target: PathBuf::from("clean.txt"),
+            },
+        ];
+        let mut buffer = Vec::new();
+        Notox::new(&options)
+            .print_output_to(final_res, &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_print_output_show_unchanged() {
+        assert_eq!(
+            default_output(false),
+            "not clean.txt -> not_clean.txt : dry-run\n2 files checked: 0 changed, 1 would change, 1 unchanged, 0 errors\n"
+        );
+        assert_eq!(
+            default_output(true),
+            "clean.txt (unchanged)\nnot clean.txt -> not_clean.txt : dry-run\nlink : symlink to clean.txt\n2 files checked: 0 changed, 1 would change, 1 unchanged, 0 errors\n"
+        );
+
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--show-unchanged".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).unwrap();
+        assert!(options.show_unchanged);
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--show-unchanged".to_string(),
+            "--preview-only-changed".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).unwrap();
+        assert!(!options.show_unchanged);
+    }
+
+    fn quiet_unless_changes_output(final_res: Vec<PathChange>) -> String {
+        let options = NotoxArgs {
+            dry_run: true,
+            output: Output::Default,
+            quiet_unless_changes: true,
+            ..Default::default()
+        };
+        assert!(!options.is_vervose());
+        let mut buffer = Vec::new();
+        Notox::new(&options)
+            .print_output_to(final_res, &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_print_output_quiet_unless_changes() {
+        let unchanged = PathChange::Unchanged {
+            path: PathBuf::from("clean.txt"),
+        };
+        assert_eq!(quiet_unless_changes_output(vec![unchanged.clone()]), "");
+        assert_eq!(quiet_unless_changes_output(vec![]), "");
+        assert_eq!(
+            quiet_unless_changes_output(vec![
+                unchanged.clone(),
+                PathChange::Skipped {
+                    path: PathBuf::from(".hidden"),
+                    reason: "hidden".to_string(),
+                },
+                PathChange::Symlink {
+                    path: PathBuf::from("link"),
+                    target: PathBuf::from("clean.txt"),
+                },
+            ]),
+            ""
+        );
+        assert_ne!(
+            quiet_unless_changes_output(vec![PathChange::WouldFail {
+                path: PathBuf::from("a b"),
+                modified: PathBuf::from("a_b"),
+                reason: "target already exists".to_string(),
+            }]),
+            ""
+        );
+        assert_eq!(
+            quiet_unless_changes_output(vec![
+                unchanged.clone(),
+                PathChange::Changed {
+                    path: PathBuf::from("not clean.txt"),
+                    modified: PathBuf::from("not_clean.txt"),
+                },
+            ]),
+            "not clean.txt -> not_clean.txt\n2 files checked: 1 changed, 1 unchanged, 0 errors\n"
+        );
+        assert_eq!(
+            quiet_unless_changes_output(vec![
+                unchanged,
+                PathChange::Error {
+                    path: PathBuf::from("unreadable"),
+                    error: "cannot read".to_string(),
+                },
+            ]),
+            "unreadable : cannot read\n2 files checked: 0 changed, 1 unchanged, 1 error\n"
+        );
+
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--quiet-unless-changes".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).unwrap();
+        assert!(options.quiet_unless_changes);
+    }
+}
+
+mod compat_report {
+    use std::{collections::HashSet, ffi::OsStr, path::PathBuf};
+
+    use notox::{parse_args, Filesystem, Notox, NotoxArgs, Output, ALL_FILESYSTEMS};
+
+    fn violating(name: &str) -> Vec<Filesystem> {
+        ALL_FILESYSTEMS
+            .into_iter()
+            .filter(|filesystem| !filesystem.violations(OsStr::new(name)).is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn test_invalid_on_fat32_valid_on_ext4() {
+        assert_eq!(
+            Filesystem::Fat32.violations(OsStr::new("report: v2?.txt")),
+            vec![r#"forbidden chars ":?""#.to_string()]
+        );
+        assert!(Filesystem::Ext4
+            .violations(OsStr::new("report: v2?.txt"))
+            .is_empty());
+        assert_eq!(
+            violating("report: v2?.txt"),
+            vec![Filesystem::Fat32, Filesystem::ExFat, Filesystem::Ntfs]
+        );
+    }
+
+    #[test]
+    fn test_filesystem_rules() {
+        assert_eq!(violating("normal name.txt"), vec![]);
+        assert_eq!(
+            Filesystem::Ntfs.violations(OsStr::new("nul.tar.gz")),
+            vec!["reserved name".to_string()]
+        );
+        assert_eq!(
+            Filesystem::ExFat.violations(OsStr::new("ends with dot.")),
+            vec!["ends with a dot or a space".to_string()]
+        );
+        assert_eq!(
+            violating("tab\there"),
+            vec![Filesystem::Fat32, Filesystem::ExFat, Filesystem::Ntfs]
+        );
+        // 200 chars but 400 bytes: too long for ext4 only
+        let long_name = "é".repeat(200);
+        assert_eq!(violating(&long_name), vec![Filesystem::Ext4]);
+        assert_eq!(
+            Filesystem::Ext4.violations(OsStr::new(&long_name)),
+            vec!["too long (400 bytes, max 255)".to_string()]
+        );
+        // 300 chars: too long for all of them
+        assert_eq!(violating(&"a".repeat(300)), ALL_FILESYSTEMS.to_vec());
+    }
+
+    #[test]
+    fn test_compat_report_output() {
+        let source = PathBuf::from("test compat: report.txt");
+        std::fs::File::create(&source).unwrap();
+
+        let notox_inst = Notox::new(&NotoxArgs {
+            dry_run: true,
+            output: Output::CompatReport,
+            ..Default::default()
+        });
+        let final_res = notox_inst.run(&HashSet::from([
+            source.clone(),
+            PathBuf::from("Cargo.toml"),
+        ]));
+        let mut buffer = Vec::new();
+        notox_inst.print_output_to(final_res, &mut buffer).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            concat!(
+                "test compat: report.txt : FAT32 (forbidden chars \":\"), ",
+                "exFAT (forbidden chars \":\"), NTFS (forbidden chars \":\")\n",
+                "2 files checked, 1 incompatible\n"
+            )
+        );
+        // the report does not rename
+        assert!(source.exists());
+
+        std::fs::remove_file(&source).unwrap();
+    }
+
+    #[test]
+    fn test_compat_report_args() {
+        let (notox_args, _) = parse_args(&[
+            "notox".to_string(),
+            "--compat-report".to_string(),
+            "Cargo.toml".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(notox_args.output, Output::CompatReport);
+    }
+}
+
+mod csv {
+    use std::path::PathBuf;
+
+    use notox::{parse_args, Notox, NotoxArgs, Output, PathChange, CSV_HEADER};
+
+    #[test]
+    fn test_csv_rows() {
+        let rows = [
+            (
+                PathChange::Unchanged {
+                    path: PathBuf::from("clean.txt"),
+                },
+                "unchanged,clean.txt,,",
+            ),
+            (
+                PathChange::Changed {
+                    path: PathBuf::from("a b.txt"),
+                    modified: PathBuf::from("a_b.txt"),
+                },
+                "changed,a b.txt,a_b.txt,",
+            ),
+            (
+                PathChange::ErrorRename {
+                    path: PathBuf::from("a,b.txt"),
+                    modified: PathBuf::from("a_b.txt"),
+                    error: "Permission denied".to_string(),
+                },
+                "error-rename,\"a,b.txt\",a_b.txt,Permission denied",
+            ),
+            (
+                PathChange::WouldChange {
+                    path: PathBuf::from("say \"hi\".txt"),
+                    modified: PathBuf::from("say_hi_.txt"),
+                },
+                "would-change,\"say \"\"hi\"\".txt\",say_hi_.txt,",
+            ),
+            (
+                PathChange::Skipped {
+                    path: PathBuf::from("node_modules"),
+                    reason: "excluded".to_string(),
+                },
+                "skipped,node_modules,,excluded",
+            ),
+            (
+                PathChange::Error {
+                    path: PathBuf::from("line\nbreak"),
+                    error: "Error while reading directory".to_string(),
+                },
+                "error,\"line\nbreak\",,Error while reading directory",
+            ),
+        ];
+        for (one_change, expected) in rows {
+            assert_eq!(one_change.csv_row(), expected);
+        }
+    }
+
+    #[test]
+    fn test_csv_output() {
+        let res = vec![
+            PathChange::WouldChange {
+                path: PathBuf::from("a b.txt"),
+                modified: PathBuf::from("a_b.txt"),
+            },
+            PathChange::Unchanged {
+                path: PathBuf::from("clean.txt"),
+            },
+        ];
+        let mut buffer = Vec::new();
+        Notox::new(&NotoxArgs {
+            output: Output::Csv,
+            ..Default::default()
+        })
+        .print_output_to(res, &mut buffer)
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            format!(
+                "{}\nwould-change,a b.txt,a_b.txt,\nunchanged,clean.txt,,\n",
+                CSV_HEADER
+            )
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_csv_non_utf8() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let one_change = PathChange::Unchanged {
+            path: PathBuf::from(OsStr::from_bytes(b"a\xffb")),
+        };
+        assert_eq!(one_change.csv_row(), "unchanged,a\u{FFFD}b,,");
+    }
+
+    #[test]
+    fn test_parse_args_csv() {
+        let vec_args = ["notox".to_string(), "--csv".to_string(), ".".to_string()];
+        assert_eq!(parse_args(&vec_args).unwrap().0.output, Output::Csv);
+    }
+}
+
+mod dedup_errors {
+    use std::path::PathBuf;
+
+    use notox::{dedup_errors, PathChange};
+
+    fn read_error(path: &str, error: &str) -> PathChange {
+        PathChange::Error {
+            path: PathBuf::from(path),
+            error: error.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_dedup_errors() {
+        let unchanged = PathChange::Unchanged {
+            path: PathBuf::from("a"),
+        };
+        let mut results = vec![
+            read_error("locked", "Permission denied (os error 13)"),
+            unchanged.clone(),
+            read_error("locked", "Permission denied (os error 13)"),
+            read_error("locked", "Not a directory (os error 20)"),
+            read_error("other", "Permission denied (os error 13)"),
+            unchanged.clone(),
+            read_error("locked", "Permission denied (os error 13)"),
+        ];
+        dedup_errors(&mut results);
+        assert_eq!(
+            results,
+            vec![
+                read_error("locked", "Permission denied (os error 13)"),
+                unchanged.clone(),
+                read_error("locked", "Not a directory (os error 20)"),
+                read_error("other", "Permission denied (os error 13)"),
+                unchanged,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dedup_errors_keeps_rename_errors() {
+        let rename_error = PathChange::ErrorRename {
+            path: PathBuf::from("a b"),
+            modified: PathBuf::from("a_b"),
+            error: "permission denied".to_string(),
+        };
+        let mut results = vec![rename_error.clone(), rename_error.clone()];
+        dedup_errors(&mut results);
+        assert_eq!(results, vec![rename_error.clone(), rename_error]);
+    }
+}
+
+#[cfg(feature = "serde")]
+mod json_envelope {
+    use std::path::PathBuf;
+
+    use notox::{
+        parse_args, transliteration_version, JsonOutput, Notox, NotoxArgs, Output, PathChange,
+        JSON_ENVELOPE_VERSION,
+    };
+    use serde_json::Value;
+
+    fn envelope_options(pretty: bool) -> NotoxArgs {
+        NotoxArgs {
+            output: Output::JsonOutput {
+                json: JsonOutput::JsonEnvelope,
+                pretty,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_args_json_envelope() {
+        let vec_args = [
+            "notox".to_string(),
+            "-p".to_string(),
+            "--json-envelope".to_string(),
+            ".".to_string(),
+        ];
+        let res = parse_args(&vec_args).unwrap();
+        assert_eq!(res.0.output, envelope_options(true).output);
+    }
+
+    #[test]
+    fn test_print_output_json_envelope() {
+        let res = vec![
+            PathChange::WouldChange {
+                path: PathBuf::from("a b.txt"),
+                modified: PathBuf::from("a_b.txt"),
+            },
+            PathChange::Unchanged {
+                path: PathBuf::from("clean.txt"),
+            },
+        ];
+        let notox_inst = Notox::new(&envelope_options(false));
+        let mut output = Vec::new();
+        notox_inst.print_output_to(res, &mut output).unwrap();
+        let envelope: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(envelope["version"], Value::from(JSON_ENVELOPE_VERSION));
+        assert_eq!(
+            envelope["notox_version"],
+            Value::from(env!("CARGO_PKG_VERSION"))
+        );
+        assert_eq!(
+            envelope["transliteration_version"],
+            Value::from(transliteration_version())
+        );
+        let results = envelope["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["status"], Value::from("would-change"));
+        assert_eq!(envelope["summary"]["checked"], Value::from(2));
+        assert_eq!(envelope["summary"]["would_change"], Value::from(1));
+        assert_eq!(envelope["summary"]["unchanged"], Value::from(1));
+        assert_eq!(
+            envelope["summary"]["categories"]["whitespace"],
+            Value::from(1)
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+mod json_lines {
+    use std::{fs, path::PathBuf, process::Command};
+
+    use notox::{parse_args, JsonOutput, Notox, NotoxArgs, Output, PathChange};
+
+    fn json_lines_options() -> NotoxArgs {
+        NotoxArgs {
+            dry_run: true,
+            output: Output::JsonOutput {
+                json: JsonOutput::JsonLines,
+                pretty: false,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_args_json_lines() {
+        let vec_args = [
+            "notox".to_string(),
+            "--json-lines".to_string(),
+            ".".to_string(),
+        ];
+        let res = parse_args(&vec_args).unwrap();
+        assert_eq!(res.0.output, json_lines_options().output);
+    }
+
+    #[test]
+    fn test_print_output_json_lines() {
+        let res = vec![
+            PathChange::WouldChange {
+                path: PathBuf::from("a b.txt"),
+                modified: PathBuf::from("a_b.txt"),
+            },
+            PathChange::Unchanged {
+                path: PathBuf::from("clean.txt"),
+            },
+        ];
+        let mut buffer = Vec::new();
+        Notox::new(&json_lines_options())
+            .print_output_to(res.clone(), &mut buffer)
+            .unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(output.lines().count(), 2);
+        assert!(output.ends_with('\n'));
+        let records: Vec<PathChange> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records, res);
+
+        let mut buffer = Vec::new();
+        Notox::new(&json_lines_options())
+            .print_output_to(Vec::new(), &mut buffer)
+            .unwrap();
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_binary_json_lines() {
+        let dir = PathBuf::from("test_binary_json_lines");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub dir")).unwrap();
+        fs::write(dir.join("sub dir").join("c d.txt"), "").unwrap();
+
+        let output = Command::new(env!("CARGO_BIN_EXE_notox"))
+            .arg("--json-lines")
+            .arg(&dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let records: Vec<PathChange> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                PathChange::Unchanged { path: dir.clone() },
+                PathChange::WouldChange {
+                    path: dir.join("sub dir"),
+                    modified: dir.join("sub_dir"),
+                },
+                PathChange::WouldChange {
+                    path: dir.join("sub dir").join("c d.txt"),
+                    modified: dir.join("sub dir").join("c_d.txt"),
+                },
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(feature = "serde")]
+mod json_tree {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{JsonOutput, Notox, NotoxArgs, Output, PathChange, PathChangeTree};
+    use serde_json::{json, Value};
+
+    fn json_tree(dry_run: bool, path: &str) -> Value {
+        let options = NotoxArgs {
+            dry_run,
+            output: Output::JsonOutput {
+                json: JsonOutput::JsonTree,
+                pretty: true,
+            },
+            ..Default::default()
+        };
+        let notox_inst = Notox::new(&options);
+        let final_res = notox_inst.run(&HashSet::from([PathBuf::from(path)]));
+        let mut buffer = Vec::new();
+        notox_inst.print_output_to(final_res, &mut buffer).unwrap();
+        serde_json::from_slice(&buffer).unwrap()
+    }
+
+    fn sorted_children(node: &Value) -> Vec<Value> {
+        let mut children = node["children"].as_array().unwrap().clone();
+        children.sort_by_key(|child| child["name"].as_str().unwrap().to_string());
+        children
+    }
+
+    #[test]
+    fn test_json_tree_nested_shape() {
+        let dir = "test_json_tree";
+        std::fs::create_dir_all(format!("{}/sub dir", dir)).unwrap();
+        std::fs::File::create(format!("{}/a b.txt", dir)).unwrap();
+        std::fs::File::create(format!("{}/sub dir/c.txt", dir)).unwrap();
+
+        // dry-run: the entries stay under the original name of the directory
+        let json = json_tree(true, dir);
+        let roots = json.as_array().unwrap();
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0]["name"], dir);
+        assert_eq!(roots[0]["path"], dir);
+        assert_eq!(roots[0]["modified"], Value::Null);
+        let children = sorted_children(&roots[0]);
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0]["name"], "a b.txt");
+        assert_eq!(children[0]["modified"], "test_json_tree/a_b.txt");
+        assert_eq!(children[0]["children"], json!([]));
+        assert_eq!(children[1]["name"], "sub dir");
+        assert_eq!(children[1]["modified"], "test_json_tree/sub_dir");
+        assert_eq!(
+            children[1]["children"],
+            json!([{
+                "name": "c.txt",
+                "path": "test_json_tree/sub dir/c.txt",
+                "modified": null,
+                "error": null,
+                "status": "unchanged",
+                "children": [],
+            }])
+        );
+
+        // renamed: the entries are found under the new name of the directory
+        let json = json_tree(false, dir);
+        let children = sorted_children(&json[0]);
+        assert_eq!(children[1]["name"], "sub dir");
+        assert_eq!(children[1]["modified"], "test_json_tree/sub_dir");
+        assert_eq!(
+            children[1]["children"][0]["path"],
+            "test_json_tree/sub_dir/c.txt"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_tree_missing_parents() {
+        let results = vec![PathChange::Unchanged {
+            path: PathBuf::from("a/b/c.txt"),
+        }];
+        let tree = PathChangeTree::from_results(&results);
+        assert_eq!(
+            tree,
+            vec![PathChangeTree {
+                name: "a".to_string(),
+                change: None,
+                children: vec![PathChangeTree {
+                    name: "b".to_string(),
+                    change: None,
+                    children: vec![PathChangeTree {
+                        name: "c.txt".to_string(),
+                        change: Some(&results[0]),
+                        children: vec![],
+                    }],
+                }],
+            }]
+        );
+        assert_eq!(
+            serde_json::to_value(&tree).unwrap(),
+            json!([{"name": "a", "children": [{"name": "b", "children": [{
+                "name": "c.txt",
+                "path": "a/b/c.txt",
+                "modified": null,
+                "error": null,
+                "status": "unchanged",
+                "children": [],
+            }]}]}])
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+mod metadata {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{JsonOutput, Notox, NotoxArgs, Output, PathChange, PathChangeWithMetadata};
+    use serde_json::Value;
+
+    fn json_output(include_metadata: bool, paths: &[PathBuf]) -> Value {
+        let options = NotoxArgs {
+            dry_run: true,
+            output: Output::JsonOutput {
+                json: JsonOutput::JsonDefault,
+                pretty: false,
+            },
+            include_metadata,
+            ..Default::default()
+        };
+        let notox_inst = Notox::new(&options);
+        let final_res = notox_inst.run(&paths.iter().cloned().collect::<HashSet<PathBuf>>());
+        let mut buffer = Vec::new();
+        notox_inst.print_output_to(final_res, &mut buffer).unwrap();
+        serde_json::from_slice(&buffer).unwrap()
+    }
+
+    #[test]
+    fn test_json_with_metadata() {
+        let existing = PathBuf::from("test metadata.txt");
+        std::fs::write(&existing, "12345").unwrap();
+        let missing = PathBuf::from("test metadata missing.txt");
+
+        let json = json_output(true, &[existing.clone(), missing.clone()]);
+        for entry in json.as_array().unwrap() {
+            match entry["path"].as_str().unwrap() {
+                "test metadata.txt" => {
+                    assert_eq!(entry["size"], 5);
+                    assert!(entry["mtime"].as_u64().unwrap() > 0);
+                }
+                _ => {
+                    // the metadata of a missing file is omitted
+                    assert!(entry.get("size").is_none());
+                    assert!(entry.get("mtime").is_none());
+                }
+            }
+        }
+
+        let json = json_output(false, std::slice::from_ref(&existing));
+        assert!(json[0].get("size").is_none());
+        assert!(json[0].get("mtime").is_none());
+
+        std::fs::remove_file(&existing).unwrap();
+    }
+
+    #[test]
+    fn test_metadata_of_renamed_path() {
+        let target = PathBuf::from("Cargo.toml");
+        let change = PathChange::Changed {
+            path: PathBuf::from("not existing Cargo.toml"),
+            modified: target.clone(),
+        };
+        let with_metadata = PathChangeWithMetadata::new(&change);
+        assert_eq!(
+            with_metadata.size,
+            Some(std::fs::metadata(&target).unwrap().len())
+        );
+        let json = serde_json::to_value(&with_metadata).unwrap();
+        assert_eq!(json["modified"], "Cargo.toml");
+        assert!(json["mtime"].is_u64());
+    }
+
+    #[test]
+    fn test_parse_args_metadata() {
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--metadata".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).unwrap();
+        assert!(options.include_metadata);
+    }
+}
+
+mod output_file {
+    use std::{collections::HashSet, path::PathBuf};
+
+    #[cfg(feature = "serde")]
+    use notox::JsonOutput;
+    use notox::{ExitCode, Notox, NotoxArgs, Output};
+
+    fn run_to_file(output: Output, output_file: &PathBuf, crlf: bool) -> Vec<u8> {
+        let options = NotoxArgs {
+            dry_run: true,
+            output,
+            output_file: Some(output_file.clone()),
+            crlf,
+            ..Default::default()
+        };
+        let paths_to_check = HashSet::from([
+            PathBuf::from("README.md"),
+            PathBuf::from("Cargo.toml"),
+            PathBuf::from("not existing file.txt"),
+        ]);
+        let notox_inst = Notox::new(&options);
+        let final_res = notox_inst.run(&paths_to_check);
+        notox_inst.print_output(final_res).unwrap();
+        let content = std::fs::read(output_file).unwrap();
+        std::fs::remove_file(output_file).unwrap();
+        content
+    }
+
+    #[test]
+    fn test_output_file_lf_without_bom() {
+        let output_file = PathBuf::from("test_output_file_lf.txt");
+        let content = run_to_file(Output::Default, &output_file, false);
+        assert!(!content.starts_with(&[0xEF, 0xBB, 0xBF]));
+        assert!(!content.contains(&b'\r'));
+        let content = String::from_utf8(content).unwrap();
+        assert_eq!(
+            content,
+            "not existing file.txt -> not_existing_file.txt : dry-run\n3 files checked: 0 changed, 1 would change, 2 unchanged, 0 errors\n"
+        );
+    }
+
+    #[test]
+    fn test_output_file_crlf() {
+        let output_file = PathBuf::from("test_output_file_crlf.txt");
+        let content = run_to_file(Output::Default, &output_file, true);
+        assert!(!content.starts_with(&[0xEF, 0xBB, 0xBF]));
+        let content = String::from_utf8(content).unwrap();
+        assert_eq!(
+            content,
+            "not existing file.txt -> not_existing_file.txt : dry-run\r\n3 files checked: 0 changed, 1 would change, 2 unchanged, 0 errors\r\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_output_file_json_pretty_lf() {
+        let output_file = PathBuf::from("test_output_file_json_pretty.json");
+        let content = run_to_file(
+            Output::JsonOutput {
+                json: JsonOutput::JsonDefault,
+                pretty: true,
+            },
+            &output_file,
+            false,
+        );
+        assert!(!content.starts_with(&[0xEF, 0xBB, 0xBF]));
+        assert!(!content.contains(&b'\r'));
+        let json: serde_json::Value = serde_json::from_slice(&content).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_parse_args_output_file() {
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--output-file".to_string(),
+            "out.txt".to_string(),
+            "--crlf".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).ok().unwrap();
+        assert_eq!(options.output_file, Some(PathBuf::from("out.txt")));
+        assert!(options.crlf);
+
+        let vec_args = ["notox".to_string(), "--output-file".to_string()];
+        assert_eq!(notox::parse_args(&vec_args).err().unwrap(), ExitCode::Usage);
+    }
+
+    #[cfg(unix)]
+    fn stream_options(output_file: &std::path::Path) -> NotoxArgs {
+        NotoxArgs {
+            dry_run: true,
+            output: Output::Default,
+            output_file: Some(output_file.to_path_buf()),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    const STREAM_OUTPUT: &str =
+        "not existing stream.txt -> not_existing_stream.txt : dry-run\n1 file checked: 0 changed, 1 would change, 0 unchanged, 0 errors\n";
+
+    #[test]
+    #[cfg(unix)]
+    fn test_output_to_fifo() {
+        use std::io::Read;
+
+        let fifo = PathBuf::from("test_output_fifo");
+        let _ = std::fs::remove_file(&fifo);
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let reader_fifo = fifo.clone();
+        let reader = std::thread::spawn(move || {
+            let mut content = String::new();
+            std::fs::File::open(&reader_fifo)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            content
+        });
+        let notox_inst = Notox::new(&stream_options(&fifo));
+        let final_res = notox_inst.run(&HashSet::from([PathBuf::from("not existing stream.txt")]));
+        notox_inst.print_output(final_res).unwrap();
+        assert_eq!(reader.join().unwrap(), STREAM_OUTPUT);
+        // the fifo is not replaced by a regular file
+        assert!(std::os::unix::fs::FileTypeExt::is_fifo(
+            &std::fs::metadata(&fifo).unwrap().file_type()
+        ));
+
+        std::fs::remove_file(&fifo).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_output_to_socket() {
+        use std::io::Read;
+
+        let socket = PathBuf::from("test_output_socket");
+        let _ = std::fs::remove_file(&socket);
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        let reader = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut content = String::new();
+            stream.read_to_string(&mut content).unwrap();
+            content
+        });
+        let notox_inst = Notox::new(&stream_options(&socket));
+        let final_res = notox_inst.run(&HashSet::from([PathBuf::from("not existing stream.txt")]));
+        notox_inst.print_output(final_res).unwrap();
+        assert_eq!(reader.join().unwrap(), STREAM_OUTPUT);
+
+        std::fs::remove_file(&socket).unwrap();
+    }
+}
+
+mod partition {
+    use std::path::PathBuf;
+
+    use notox::{partition, PartitionedChanges, PathChange};
+
+    fn mixed_results() -> Vec<PathChange> {
+        vec![
+            PathChange::Unchanged {
+                path: PathBuf::from("a"),
+            },
+            PathChange::Error {
+                path: PathBuf::from("b"),
+                error: "cannot read".to_string(),
+            },
+            PathChange::Changed {
+                path: PathBuf::from("c d"),
+                modified: PathBuf::from("c_d"),
+            },
+            PathChange::ErrorRename {
+                path: PathBuf::from("e f"),
+                modified: PathBuf::from("e_f"),
+                error: "permission denied".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_partition_mixed() {
+        let (ok, errors) = partition(mixed_results());
+        assert_eq!(ok.len(), 2);
+        assert_eq!(errors.len(), 2);
+        assert!(ok.iter().all(|one_change| !one_change.is_error()));
+        assert!(errors.iter().all(PathChange::is_error));
+    }
+
+    #[test]
+    fn test_partition_collect() {
+        let partitioned: PartitionedChanges = mixed_results().into_iter().collect();
+        let (ok, errors) = partition(mixed_results());
+        assert_eq!(partitioned.ok, ok);
+        assert_eq!(partitioned.errors, errors);
+        assert_eq!(partitioned.into_result().err().unwrap(), errors);
+
+        let only_ok: PartitionedChanges = ok.clone().into_iter().collect();
+        assert_eq!(only_ok.into_result().ok().unwrap(), ok);
+    }
+}
+
+mod per_dir_summary {
+    use std::{fs, path::Path, process::Command};
+
+    use crate::common::create_tree;
+
+    fn run_notox(dir: &Path, extra_args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_notox"))
+            .args(extra_args)
+            .arg(dir)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    const FILES: &[&str] = &[
+        "a b.txt",
+        "clean.txt",
+        "sub dir/c d.txt",
+        "sub dir/e f.txt",
+        "sub dir/ok.txt",
+    ];
+
+    #[test]
+    fn test_per_dir_summary() {
+        let dir = Path::new("test_per_dir_summary");
+        create_tree(dir, FILES);
+        let stdout = run_notox(dir, &["--per-dir-summary", "-d"]);
+        let sep = std::path::MAIN_SEPARATOR;
+        let sub_line = format!(
+            "{}{}sub_dir{}: 2 changed, 1 unchanged",
+            dir.display(),
+            sep,
+            sep
+        );
+        let top_line = format!("{}{}: 2 changed, 1 unchanged", dir.display(), sep);
+        let lines: Vec<&str> = stdout.lines().collect();
+        let sub_index = lines.iter().position(|line| *line == sub_line);
+        let top_index = lines.iter().position(|line| *line == top_line);
+        assert!(sub_index.is_some(), "{}", stdout);
+        assert!(top_index.is_some(), "{}", stdout);
+        // a directory is summarized once all its entries are done
+        assert!(sub_index < top_index, "{}", stdout);
+        assert!(stdout.contains("7 files checked"), "{}", stdout);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_per_dir_summary_dry_run() {
+        let dir = Path::new("test_per_dir_summary_dry_run");
+        create_tree(dir, FILES);
+        let stdout = run_notox(dir, &["--per-dir-summary"]);
+        let sep = std::path::MAIN_SEPARATOR;
+        let sub_line = format!(
+            "{}{}sub dir{}: 0 changed, 1 unchanged, 2 would change",
+            dir.display(),
+            sep,
+            sep
+        );
+        assert!(stdout.lines().any(|line| line == sub_line), "{}", stdout);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_per_dir_summary_output_file() {
+        let dir = Path::new("test_per_dir_summary_output_file");
+        let output_file = Path::new("test_per_dir_summary_output_file.txt");
+        create_tree(dir, FILES);
+        let stdout = run_notox(
+            dir,
+            &[
+                "--per-dir-summary",
+                "--output-file",
+                "test_per_dir_summary_output_file.txt",
+            ],
+        );
+        assert!(!stdout.contains(" changed, "), "{}", stdout);
+        let output = fs::read_to_string(output_file).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        let sep = std::path::MAIN_SEPARATOR;
+        let top_line = format!(
+            "{}{}: 0 changed, 1 unchanged, 2 would change",
+            dir.display(),
+            sep
+        );
+        // the directories come after the changes, before the total
+        assert_eq!(lines[lines.len() - 2], top_line, "{}", output);
+        assert!(
+            lines[lines.len() - 1].contains("files checked"),
+            "{}",
+            output
+        );
+        fs::remove_dir_all(dir).unwrap();
+        fs::remove_file(output_file).unwrap();
+    }
+
+    #[test]
+    fn test_per_dir_summary_off() {
+        let dir = Path::new("test_per_dir_summary_off");
+        create_tree(dir, FILES);
+        let stdout = run_notox(dir, &["-d"]);
+        assert!(!stdout.contains("/: "), "{}", stdout);
+        // not printed with the other outputs
+        create_tree(dir, FILES);
+        let stdout = run_notox(dir, &["--per-dir-summary", "-d", "-q"]);
+        assert!(stdout.is_empty(), "{}", stdout);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+mod progress {
+    use std::{fs, path::PathBuf, process::Command};
+
+    use notox::{parse_args, PROGRESS_INTERVAL};
+
+    fn run_progress(dir: &PathBuf, extra_arg: Option<&str>) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_notox"))
+            .arg("--progress")
+            .args(extra_arg)
+            .arg(dir)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stderr).unwrap()
+    }
+
+    #[test]
+    fn test_progress() {
+        let dir = PathBuf::from("test_progress");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        for idx in 0..PROGRESS_INTERVAL + 1 {
+            fs::write(dir.join(format!("file {}.txt", idx)), "").unwrap();
+        }
+        let stderr = run_progress(&dir, None);
+        let lines: Vec<&str> = stderr.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", stderr);
+        assert!(lines[0].starts_with("1000 paths processed in "));
+        // the directory and its files
+        assert!(lines[1].starts_with("1002 paths processed in "));
+        assert!(lines[1].ends_with(", done"));
+        // only with the default output
+        assert_eq!(run_progress(&dir, Some("-q")), "");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_progress() {
+        let args = ["notox".to_string(), "--progress".to_string()];
+        assert!(parse_args(&args).unwrap().0.progress);
+        assert!(!parse_args(&["notox".to_string()]).unwrap().0.progress);
+    }
+}
+
+#[cfg(unix)]
+mod report_encoding {
+    use std::{collections::HashSet, ffi::OsStr, os::unix::ffi::OsStrExt, path::PathBuf};
+
+    use notox::{EncodingTally, NameEncoding, Notox, NotoxArgs, Output, PathChange};
+
+    #[test]
+    fn test_name_encoding() {
+        let names: [(&[u8], NameEncoding); 5] = [
+            (b"plain name.txt", NameEncoding::Ascii),
+            ("café.txt".as_bytes(), NameEncoding::Utf8),
+            ("日本.txt".as_bytes(), NameEncoding::Utf8),
+            // "café" in Latin-1
+            (b"caf\xe9.txt", NameEncoding::InvalidUtf8),
+            // truncated sequence
+            (b"\xe6\x97.txt", NameEncoding::InvalidUtf8),
+        ];
+        for (name, expected) in names {
+            assert_eq!(
+                NameEncoding::of(OsStr::from_bytes(name)),
+                expected,
+                "{:?}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_encoding_tally() {
+        let results = vec![
+            PathChange::Unchanged {
+                path: PathBuf::from("a.txt"),
+            },
+            PathChange::Changed {
+                path: PathBuf::from(OsStr::from_bytes(b"dir/caf\xe9.txt")),
+                modified: PathBuf::from("dir/caf_.txt"),
+            },
+            PathChange::Changed {
+                path: PathBuf::from("été.txt"),
+                modified: PathBuf::from("ete.txt"),
+            },
+            PathChange::Error {
+                path: PathBuf::from("dîr"),
+                error: "cannot read".to_string(),
+            },
+        ];
+        assert_eq!(
+            EncodingTally::from_results(&results),
+            EncodingTally {
+                ascii: 1,
+                utf8: 1,
+                invalid_utf8: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_report_encoding_output() {
+        let dir = PathBuf::from("test_report_encoding");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::File::create(dir.join("plain.txt")).unwrap();
+        std::fs::File::create(dir.join("été.txt")).unwrap();
+        std::fs::File::create(dir.join(OsStr::from_bytes(b"caf\xe9.txt"))).unwrap();
+
+        let notox_inst = Notox::new(&NotoxArgs {
+            dry_run: true,
+            output: Output::EncodingClass,
+            ..Default::default()
+        });
+        let final_res = notox_inst.run(&HashSet::from([dir.clone()]));
+        let mut buffer = Vec::new();
+        notox_inst.print_output_to(final_res, &mut buffer).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        let mut lines: Vec<&str> = output.lines().collect();
+        lines[..2].sort();
+        assert_eq!(
+            lines,
+            vec![
+                "test_report_encoding/caf\u{fffd}.txt : invalid utf-8",
+                "test_report_encoding/été.txt : utf-8",
+                "ascii: 2",
+                "utf-8: 1",
+                "invalid utf-8: 1",
+            ]
+        );
+        // the report does not rename
+        assert!(dir.join(OsStr::from_bytes(b"caf\xe9.txt")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+mod script {
+    use std::{fs, path::PathBuf, process::Command};
+
+    use notox::{
+        parse_args, powershell_quote, sh_quote, write_rename_script, Output, PathChange,
+        ScriptDialect,
+    };
+
+    #[test]
+    fn test_sh_quote() {
+        assert_eq!(sh_quote(&PathBuf::from("a b")), "'a b'");
+        assert_eq!(sh_quote(&PathBuf::from("it's")), r"'it'\''s'");
+        assert_eq!(sh_quote(&PathBuf::from("a\nb")), "'a\nb'");
+        assert_eq!(sh_quote(&PathBuf::from("")), "''");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_sh_quote_non_utf8() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let path = PathBuf::from(OsStr::from_bytes(b"caf\xe9.txt"));
+        assert_eq!(sh_quote(&path), r#"'caf'"$(printf '\351')"'.txt'"#);
+    }
+
+    #[test]
+    fn test_powershell_quote() {
+        assert_eq!(powershell_quote("a b"), "'a b'");
+        assert_eq!(powershell_quote("it's"), "'it''s'");
+        assert_eq!(powershell_quote("it\u{2019}s"), "'it\u{2019}\u{2019}s'");
+    }
+
+    #[test]
+    fn test_write_rename_script() {
+        let changes = vec![
+            PathChange::WouldChange {
+                path: PathBuf::from("a b"),
+                modified: PathBuf::from("a_b"),
+            },
+            PathChange::Unchanged {
+                path: PathBuf::from("a b/clean.txt"),
+            },
+            PathChange::WouldChange {
+                path: PathBuf::from("a b/c d.txt"),
+                modified: PathBuf::from("a b/c_d.txt"),
+            },
+        ];
+        let mut sh_script = Vec::new();
+        write_rename_script(&changes, ScriptDialect::Sh, &mut sh_script).unwrap();
+        // the entries of a directory are renamed before it
+        assert_eq!(
+            String::from_utf8(sh_script).unwrap(),
+            "#!/bin/sh\nset -e\nmv -- 'a b/c d.txt' 'a b/c_d.txt'\nmv -- 'a b' 'a_b'\n"
+        );
+        let mut ps1_script = Vec::new();
+        write_rename_script(&changes, ScriptDialect::PowerShell, &mut ps1_script).unwrap();
+        assert_eq!(
+            String::from_utf8(ps1_script).unwrap(),
+            "$ErrorActionPreference = 'Stop'\nRename-Item -LiteralPath 'a b/c d.txt' -NewName 'c_d.txt'\nRename-Item -LiteralPath 'a b' -NewName 'a_b'\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_args_emit_script() {
+        let args = ["notox", "-d", "--emit-script", "ps1", "Cargo.toml"].map(String::from);
+        let (notox_args, paths) = parse_args(&args).unwrap();
+        assert_eq!(notox_args.output, Output::Script(ScriptDialect::PowerShell));
+        // the script never renames
+        assert!(notox_args.dry_run);
+        assert_eq!(paths.len(), 1);
+        let args = ["notox", "--emit-script", "Cargo.toml"].map(String::from);
+        let (notox_args, paths) = parse_args(&args).unwrap();
+        assert_eq!(notox_args.output, Output::Script(ScriptDialect::Sh));
+        assert!(paths.contains(&PathBuf::from("Cargo.toml")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_emit_script_run() {
+        let dir = PathBuf::from("test_emit_script_run");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("it's dir")).unwrap();
+        fs::write(dir.join("it's dir").join("a\nb.txt"), "").unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_notox"))
+            .args(["-d", "--emit-script", "sh"])
+            .arg(&dir)
+            .output()
+            .unwrap();
+        // nothing is renamed by notox
+        assert!(dir.join("it's dir").join("a\nb.txt").exists());
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(String::from_utf8(output.stdout).unwrap())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert!(dir.join("it_s_dir").join("a_b.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(feature = "serde")]
+mod streaming_json {
+    use std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+    };
+
+    use notox::{Notox, PathChange, Summary};
+
+    use crate::common::quiet_options;
+
+    fn streamed(notox_inst: &Notox, dir: &Path, only_errors: bool) -> (Vec<PathChange>, Summary) {
+        let mut buffer = Vec::new();
+        let summary = notox_inst
+            .run_streaming_json(
+                &HashSet::from([dir.to_path_buf()]),
+                &mut buffer,
+                only_errors,
+            )
+            .unwrap();
+        let records = String::from_utf8(buffer)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        (records, summary)
+    }
+
+    #[test]
+    fn test_run_streaming_json() {
+        let dir = PathBuf::from("test_streaming_json");
+        std::fs::create_dir_all(dir.join("sub dir")).unwrap();
+        std::fs::File::create(dir.join("a b.txt")).unwrap();
+        std::fs::File::create(dir.join("clean.txt")).unwrap();
+        std::fs::File::create(dir.join("sub dir/c d.txt")).unwrap();
+        std::fs::File::create(dir.join("test_streaming_json_target.txt")).unwrap();
+        std::fs::File::create(dir.join("test streaming json target.txt")).unwrap();
+
+        let notox_inst = Notox::new(&quiet_options(true));
+        let expected = notox_inst.run(&HashSet::from([dir.clone()]));
+
+        let (records, summary) = streamed(&notox_inst, &dir, false);
+        assert_eq!(summary, Summary::from_results(&expected));
+        assert_eq!(records.len(), expected.len());
+        assert_eq!(
+            records.iter().collect::<HashSet<_>>(),
+            expected.iter().collect::<HashSet<_>>()
+        );
+        // a directory is written before its entries
+        let position = |path: PathBuf| records.iter().position(|one| one.path() == path).unwrap();
+        assert!(position(dir.clone()) < position(dir.join("sub dir")));
+        assert!(position(dir.join("sub dir")) < position(dir.join("sub dir/c d.txt")));
+
+        // only the errors are written, the summary counts all the changes
+        let (records, only_errors_summary) = streamed(&notox_inst, &dir, true);
+        assert_eq!(only_errors_summary, summary);
+        let expected_errors: HashSet<_> = expected.iter().filter(|one| one.is_error()).collect();
+        assert_eq!(records.iter().collect::<HashSet<_>>(), expected_errors);
+        assert!(records.contains(&PathChange::WouldFail {
+            path: dir.join("test streaming json target.txt"),
+            modified: dir.join("test_streaming_json_target.txt"),
+            reason: "target already exists".to_string(),
+        }));
+        assert!(!records
+            .iter()
+            .any(|one| matches!(one, PathChange::Unchanged { .. })));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+mod summary {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{CategoryTally, Notox, NotoxArgs, PathChange, Summary};
+
+    use crate::common::quiet_options;
+
+    #[test]
+    fn test_run_with_summary() {
+        let options = quiet_options(true);
+        let (results, summary) = Notox::new(&options).run_with_summary(&HashSet::from([
+            PathBuf::from("Cargo.toml"),
+            PathBuf::from("test run with summary é.txt"),
+        ]));
+        assert_eq!(results.len(), 2);
+        assert_eq!(summary, Summary::from_results(&results));
+        assert_eq!(summary.checked, 2);
+        assert_eq!(summary.would_change, 1);
+        assert_eq!(summary.unchanged, 1);
+        assert_eq!(
+            summary.categories,
+            CategoryTally {
+                accents: 1,
+                whitespace: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_summary_display() {
+        let results = vec![
+            PathChange::Changed {
+                path: PathBuf::from("a b"),
+                modified: PathBuf::from("a_b"),
+            },
+            PathChange::Trashed {
+                path: PathBuf::from("a_b"),
+            },
+            PathChange::Unchanged {
+                path: PathBuf::from("c"),
+            },
+            PathChange::Error {
+                path: PathBuf::from("d"),
+                error: "cannot read".to_string(),
+            },
+        ];
+        assert_eq!(
+            Summary::from_results(&results).to_string(),
+            "3 files checked: 1 changed, 1 unchanged, 1 error, 1 trashed"
+        );
+        assert_eq!(
+            Summary::default().to_string(),
+            "0 files checked: 0 changed, 0 unchanged, 0 errors"
+        );
+    }
+
+    #[test]
+    fn test_no_summary() {
+        let results = vec![
+            PathChange::Changed {
+                path: PathBuf::from("a b"),
+                modified: PathBuf::from("a_b"),
+            },
+            PathChange::Unchanged {
+                path: PathBuf::from("c"),
+            },
+        ];
+        let mut buffer = Vec::new();
+        Notox::new(&NotoxArgs {
+            no_summary: true,
+            ..Default::default()
+        })
+        .print_output_to(results, &mut buffer)
+        .unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap(), "a b -> a_b\n");
+
+        let vec_args = [
+            "notox".to_string(),
+            "--no-summary".to_string(),
+            ".".to_string(),
+        ];
+        assert!(notox::parse_args(&vec_args).unwrap().0.no_summary);
+    }
+}
+
+mod would_change {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{Notox, NotoxArgs, Output, PathChange, Summary};
+
+    fn dry_run_options(output: Output) -> NotoxArgs {
+        NotoxArgs {
+            dry_run: true,
+            output,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dry_run_would_change() {
+        let source = PathBuf::from("test would change.txt");
+        let res = Notox::new(&dry_run_options(Output::Quiet)).run(&HashSet::from([source.clone()]));
+        assert_eq!(
+            res,
+            vec![PathChange::WouldChange {
+                path: source,
+                modified: PathBuf::from("test_would_change.txt"),
+            }]
+        );
+        assert!(!res[0].is_error());
+        assert!(res[0].is_dry_run_rename());
+        let summary = Summary::from_results(&res);
+        assert_eq!(summary.would_change, 1);
+        assert_eq!(summary.errors, 0);
+    }
+
+    #[test]
+    fn test_would_change_default_output() {
+        let res = vec![PathChange::WouldChange {
+            path: PathBuf::from("a b.txt"),
+            modified: PathBuf::from("a_b.txt"),
+        }];
+        let mut buffer = Vec::new();
+        Notox::new(&dry_run_options(Output::Default))
+            .print_output_to(res, &mut buffer)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "a b.txt -> a_b.txt : dry-run\n1 file checked: 0 changed, 1 would change, 0 unchanged, 0 errors\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_would_change_serde() {
+        let would_change = PathChange::WouldChange {
+            path: PathBuf::from("a b"),
+            modified: PathBuf::from("a_b"),
+        };
+        let json = serde_json::to_string(&would_change).unwrap();
+        assert_eq!(
+            json,
+            r#"{"path":"a b","modified":"a_b","error":null,"status":"would-change"}"#
+        );
+        let deserialized: PathChange = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, would_change);
+        // written before the dry-run renames had their own status
+        let legacy: PathChange =
+            serde_json::from_str(r#"{"path":"a b","modified":"a_b","error":"dry-run"}"#).unwrap();
+        assert_eq!(legacy, would_change);
+        // a rename that was done has its own status
+        let changed = PathChange::Changed {
+            path: PathBuf::from("a b"),
+            modified: PathBuf::from("a_b"),
+        };
+        let json = serde_json::to_string(&changed).unwrap();
+        assert_eq!(
+            json,
+            r#"{"path":"a b","modified":"a_b","error":null,"status":"changed"}"#
+        );
+        assert_eq!(serde_json::from_str::<PathChange>(&json).unwrap(), changed);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json_error_without_dry_run_renames() {
+        use notox::JsonOutput;
+
+        let options = dry_run_options(Output::JsonOutput {
+            json: JsonOutput::JsonOnlyError,
+            pretty: false,
+        });
+        let res = vec![
+            PathChange::WouldChange {
+                path: PathBuf::from("a b"),
+                modified: PathBuf::from("a_b"),
+            },
+            PathChange::Error {
+                path: PathBuf::from("missing"),
+                error: "not found".to_string(),
+            },
+        ];
+        let mut buffer = Vec::new();
+        Notox::new(&options)
+            .print_output_to(res, &mut buffer)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "[{\"path\":\"missing\",\"modified\":null,\"error\":\"not found\",\"status\":\"error\"}]\n"
+        );
+    }
 }
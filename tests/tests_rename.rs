@@ -1,17 +1,28 @@
+mod common;
+
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashSet, path::PathBuf};
+    use std::{
+        collections::{HashMap, HashSet},
+        fs,
+        path::PathBuf,
+    };
 
-    use notox::{NotoxArgs, Output, PathChange};
-    const TESTS_FIELDS_NOT_DRY_RUN: NotoxArgs = NotoxArgs {
-        dry_run: false,
-        output: Output::Default,
+    use notox::{
+        CaseMode, Category, CleanMode, NonUtf8Policy, NotoxArgs, Output, PathChange, Profile,
     };
+    fn tests_fields_not_dry_run() -> NotoxArgs {
+        NotoxArgs {
+            dry_run: false,
+            output: Output::Default,
+            ..Default::default()
+        }
+    }
 
     #[test]
     fn no_rename() {
         let paths = HashSet::from([PathBuf::from("my_file")]);
-        let res = notox::notox(&TESTS_FIELDS_NOT_DRY_RUN, &paths);
+        let res = notox::notox(&tests_fields_not_dry_run(), &paths);
         assert_eq!(res.len(), 1);
         let correct_path = PathBuf::from("my_file");
         match &res[0] {
@@ -26,7 +37,7 @@ mod tests {
     fn rename() {
         let base_path = PathBuf::from("my?..file");
         let paths = HashSet::from([base_path.clone()]);
-        let res = notox::notox(&TESTS_FIELDS_NOT_DRY_RUN, &paths);
+        let res = notox::notox(&tests_fields_not_dry_run(), &paths);
         assert_eq!(res.len(), 1);
         match &res[0] {
             PathChange::ErrorRename {
@@ -88,7 +99,7 @@ mod tests {
             let result_to_test = PathBuf::from(one_test.1);
             println!("Testing: {:?} -> {:?}", path_to_test, result_to_test);
             let paths = HashSet::from([path_to_test.clone()]);
-            let res = notox::notox(&TESTS_FIELDS_NOT_DRY_RUN, &paths);
+            let res = notox::notox(&tests_fields_not_dry_run(), &paths);
             assert_eq!(res.len(), 1);
             match &res[0] {
                 PathChange::ErrorRename {
@@ -126,7 +137,7 @@ mod tests {
             let path_to_test = PathBuf::from(one_test);
             println!("Testing: {:?}", path_to_test);
             let res = notox::notox(
-                &TESTS_FIELDS_NOT_DRY_RUN,
+                &tests_fields_not_dry_run(),
                 &HashSet::from([path_to_test.clone()]),
             );
             assert_eq!(res.len(), 1);
@@ -203,7 +214,7 @@ mod tests {
                 current_char.escape_unicode()
             );
             let paths = HashSet::from([path_to_test.clone()]);
-            let res = notox::notox(&TESTS_FIELDS_NOT_DRY_RUN, &paths);
+            let res = notox::notox(&tests_fields_not_dry_run(), &paths);
             assert_eq!(res.len(), 1);
             match &res[0] {
                 PathChange::ErrorRename {
@@ -300,4 +311,1528 @@ mod tests {
         let curr_char: Option<char> = std::char::from_u32(one_bytes_u32);
         assert_eq!(curr_char, Some(one_bytes));
     }
+
+    #[test]
+    fn test_transliteration_version() {
+        assert!(!notox::transliteration_version().is_empty());
+        assert_eq!(
+            notox::transliteration_version(),
+            notox::TRANSLITERATION_VERSION
+        );
+    }
+
+    #[test]
+    fn test_allow() {
+        let names = [
+            ("foo+bar-1.0.tgz", "", "foo_bar-1.0.tgz"),
+            ("foo+bar-1.0.tgz", "+", "foo+bar-1.0.tgz"),
+            ("a+b (c).txt", "+()", "a+b_(c).txt"),
+            ("a[b]=c", "=", "a_b_=c"),
+            ("++é", "+", "++e"),
+            // the separator is still collapsed even if given
+            ("a__b", "_", "a_b"),
+        ];
+        for (name, allowed, expected) in names {
+            let options = NotoxArgs {
+                allowed_extra: allowed.chars().collect(),
+                ..Default::default()
+            };
+            assert_eq!(
+                notox::clean_str(name, &options),
+                expected,
+                "{} {}",
+                name,
+                allowed
+            );
+        }
+    }
+
+    #[test]
+    fn test_ext_allowed_chars() {
+        let names = [
+            ("main.c++", "", "main.c_"),
+            ("main.c++", "+", "main.c++"),
+            ("my+file.c++", "+", "my_file.c++"),
+            ("program.c#", "+#", "program.c#"),
+            ("archive.tar+.c++", "+", "archive.tar_.c++"),
+            // the leading dot of a dotfile is not an extension
+            (".c++", "+", ".c_"),
+            ("noext+", "+", "noext_"),
+            ("file.é€", "€", "file.e€"),
+        ];
+        for (name, allowed, expected) in names {
+            let options = NotoxArgs {
+                ext_allowed_chars: allowed.chars().collect(),
+                ..Default::default()
+            };
+            assert_eq!(
+                notox::clean_str(name, &options),
+                expected,
+                "{} {}",
+                name,
+                allowed
+            );
+        }
+    }
+
+    #[test]
+    fn test_dedupe_extension() {
+        let options = NotoxArgs {
+            dedupe_extension: true,
+            ..Default::default()
+        };
+        let names = [
+            ("file.txt.txt", "file.txt"),
+            ("image.jpg.jpg", "image.jpg"),
+            ("image.JPG.jpg", "image.jpg"),
+            ("my file.txt.txt.txt", "my_file.txt"),
+            ("archive.tar.gz", "archive.tar.gz"),
+            ("file.txt", "file.txt"),
+            (".txt.txt", ".txt.txt"),
+            ("file..", "file.."),
+        ];
+        for (name, expected) in names {
+            assert_eq!(notox::clean_str(name, &options), expected, "{}", name);
+        }
+        assert_eq!(
+            notox::clean_str("file.txt.txt", &NotoxArgs::default()),
+            "file.txt.txt"
+        );
+    }
+
+    #[test]
+    fn test_emoji_stem_keeps_extension() {
+        let names = [
+            (None, 1, "😀.png", "_.png"),
+            (None, 0, "😀.png", ".png"),
+            (Some("unnamed"), 1, "😀.png", "unnamed.png"),
+            (Some("unnamed"), 0, "😀.png", "unnamed.png"),
+            (Some("unnamed"), 1, "😀 ❤.JPG", "unnamed.JPG"),
+            // no extension
+            (Some("unnamed"), 1, "😀", "unnamed"),
+            // the stem is not empty
+            (Some("unnamed"), 1, "😀 cat.png", "_cat.png"),
+            // dotfile, no extension
+            (Some("unnamed"), 1, ".❤", "unnamed"),
+            // unchanged names are kept
+            (Some("unnamed"), 1, "_.png", "_.png"),
+            (Some("unnamed"), 1, ".bashrc", ".bashrc"),
+        ];
+        for (placeholder, max, name, expected) in names {
+            let options = NotoxArgs {
+                empty_stem_placeholder: placeholder.map(str::to_string),
+                max_consecutive_separators: max,
+                ..Default::default()
+            };
+            assert_eq!(
+                notox::clean_str(name, &options),
+                expected,
+                "{} {:?} {}",
+                name,
+                placeholder,
+                max
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_consecutive_separators() {
+        let names = [
+            ("my  __ file.txt", 1, "my_file.txt"),
+            ("my  __ file.txt", 2, "my__file.txt"),
+            ("my  __ file.txt", 3, "my___file.txt"),
+            ("my__file.txt", 2, "my__file.txt"),
+            ("my ❤❤❤❤ file.txt", 2, "my__file.txt"),
+            ("my ❤❤❤❤ file.txt", 10, "my______file.txt"),
+            ("my  __ file.txt", 0, "myfile.txt"),
+        ];
+        for (name, max, expected) in names {
+            let options = NotoxArgs {
+                max_consecutive_separators: max,
+                ..Default::default()
+            };
+            assert_eq!(
+                notox::clean_str(name, &options),
+                expected,
+                "{} {}",
+                name,
+                max
+            );
+        }
+    }
+
+    #[test]
+    fn test_keep_spaces() {
+        let options = NotoxArgs {
+            keep_spaces: true,
+            ..Default::default()
+        };
+        let names = [
+            ("Crème brûlée.txt", "Creme brulee.txt"),
+            ("été   2024 (copy).jpg", "ete 2024 _copy_.jpg"),
+            ("a\tb c.txt", "a_b c.txt"),
+            ("no space.txt", "no space.txt"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(notox::clean_str(name, &options), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_space_replacement() {
+        let options = NotoxArgs {
+            space_replacement: Some('-'),
+            ..Default::default()
+        };
+        let names = [
+            ("My File.txt", "My-File.txt"),
+            ("a  b", "a-b"),
+            ("a\tb", "a-b"),
+            ("a \t b", "a-b"),
+            ("a\u{00A0}b", "a-b"),
+            ("a \u{00A0}\tb", "a-b"),
+            // the other chars still use the separator
+            ("a(b).txt", "a_b_.txt"),
+            ("é è", "e-e"),
+            // a run of separators, whitespaces and replacements is collapsed
+            ("a - - b", "a-b"),
+            ("my - file.txt", "my-file.txt"),
+            ("a ( b.txt", "a-b.txt"),
+            ("a (b).txt", "a-b_.txt"),
+            ("a_ b", "a-b"),
+            ("a--b", "a-b"),
+            // a run with a whitespace uses the replacement, whatever the order
+            ("a( b", "a-b"),
+            ("r (1) é.txt", "r-1-e.txt"),
+            ("a —b", "a-b"),
+            ("a— b", "a-b"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(notox::clean_str(name, &options), expected, "{:?}", name);
+        }
+        let options = NotoxArgs {
+            space_replacement: Some('-'),
+            max_consecutive_separators: 2,
+            ..Default::default()
+        };
+        assert_eq!(notox::clean_str("a - - b", &options), "a--b");
+        assert_eq!(notox::clean_str("a( b", &options), "a--b");
+        assert_eq!(notox::clean_str("a (b", &options), "a--b");
+        let options = NotoxArgs {
+            space_replacement: Some('-'),
+            max_consecutive_separators: 0,
+            ..Default::default()
+        };
+        assert_eq!(notox::clean_str("a - b", &options), "ab");
+        assert_eq!(notox::clean_str("a\u{00A0}b", &NotoxArgs::default()), "a_b");
+        // the whitespaces are kept if their category is not cleaned
+        let options = NotoxArgs {
+            space_replacement: Some('-'),
+            categories: vec![Category::Symbols],
+            ..Default::default()
+        };
+        assert_eq!(notox::clean_str("a b(c).txt", &options), "a b_c_.txt");
+    }
+
+    #[test]
+    fn test_post_separator_case() {
+        let names = [
+            (
+                Some(CaseMode::Upper),
+                "my file—report.txt",
+                "my_File_Report.txt",
+            ),
+            (
+                Some(CaseMode::Upper),
+                "MyFile—Report.txt",
+                "MyFile_Report.txt",
+            ),
+            (
+                Some(CaseMode::Upper),
+                "été à la plage.jpg",
+                "ete_A_La_Plage.jpg",
+            ),
+            (Some(CaseMode::Upper), "a_1 b.txt", "a_1_B.txt"),
+            (
+                Some(CaseMode::Lower),
+                "MyFile—Report.txt",
+                "MyFile_report.txt",
+            ),
+            (
+                Some(CaseMode::Lower),
+                "My File Report.TXT",
+                "My_file_report.TXT",
+            ),
+            (None, "My File Report.TXT", "My_File_Report.TXT"),
+        ];
+        for (post_separator_case, name, expected) in names {
+            let options = NotoxArgs {
+                post_separator_case,
+                ..Default::default()
+            };
+            assert_eq!(notox::clean_str(name, &options), expected, "{}", name);
+        }
+        // the other separators
+        let options = NotoxArgs {
+            post_separator_case: Some(CaseMode::Upper),
+            space_replacement: Some('-'),
+            ..Default::default()
+        };
+        assert_eq!(
+            notox::clean_str("my file (report).txt", &options),
+            "my-File-Report_.txt"
+        );
+        let options = NotoxArgs {
+            post_separator_case: Some(CaseMode::Upper),
+            keep_spaces: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            notox::clean_str("my file—report.txt", &options),
+            "my File_Report.txt"
+        );
+    }
+
+    #[test]
+    fn test_strip_accents_only() {
+        let options = NotoxArgs {
+            categories: vec![Category::Accents],
+            ..Default::default()
+        };
+        let names = [
+            ("café.txt", "cafe.txt"),
+            ("café #1 ❤.txt", "cafe #1 ❤.txt"),
+            ("Ærø  (copy).txt", "Aro  (copy).txt"),
+            ("my file\t$.txt", "my file\t$.txt"),
+            ("été–hiver.txt", "ete–hiver.txt"),
+            ("e\u{301}te\u{301}.txt", "ete.txt"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(notox::clean_str(name, &options), expected, "{}", name);
+        }
+        let options = NotoxArgs::default();
+        assert!(options.cleans_all_categories());
+        assert_eq!(notox::clean_str("café #1 ❤.txt", &options), "cafe_1_.txt");
+    }
+
+    #[test]
+    fn test_shell_safe_names() {
+        let options = NotoxArgs {
+            shell_safe: true,
+            ..Default::default()
+        };
+        let names = [
+            ("-rf", "_-rf"),
+            ("--help.txt", "_--help.txt"),
+            ("price $100.txt", "price_100.txt"),
+            ("$HOME", "_HOME"),
+            ("a`b`*?~!.sh", "a_b_.sh"),
+            ("safe-name.txt", "safe-name.txt"),
+        ];
+        for (name, expected) in names {
+            let cleaned_name = notox::clean_str(name, &options);
+            assert_eq!(cleaned_name, expected, "{}", name);
+            assert!(notox::is_shell_safe(&cleaned_name));
+        }
+        // the leading - is kept without the option
+        assert_eq!(notox::clean_str("-rf", &NotoxArgs::default()), "-rf");
+        assert!(!notox::is_shell_safe("-rf"));
+
+        // symbols kept by other options are still replaced
+        let options = NotoxArgs {
+            categories: vec![Category::Accents],
+            ext_allowed_chars: HashSet::from(['+']),
+            ..options
+        };
+        assert_eq!(notox::clean_str("-café $1.c++", &options), "_-cafe__1.c__");
+    }
+
+    #[test]
+    fn test_is_shell_safe() {
+        assert!(notox::is_shell_safe("file_1.tar.gz"));
+        assert!(notox::is_shell_safe(".bashrc"));
+        for name in ["", ".", "..", "-x", "a b", "a$b", "a*", "é"] {
+            assert!(!notox::is_shell_safe(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_profile_sharepoint() {
+        let options = NotoxArgs {
+            profile: Profile::SharePoint,
+            ..Default::default()
+        };
+        for forbidden in notox::SHAREPOINT_FORBIDDEN_CHARS
+            .iter()
+            .filter(|c| **c != '/')
+        {
+            let name = format!(" my{}file.txt ", forbidden);
+            let res = notox::clean_str(&name, &options);
+            assert!(!res.contains(notox::SHAREPOINT_FORBIDDEN_CHARS), "{}", res);
+            assert!(!res.starts_with(' ') && !res.ends_with(' '), "{}", res);
+        }
+        let names = [
+            ("CON.txt", "CON_.txt"),
+            ("con", "con_"),
+            ("LPT1.tar.gz", "LPT1_.tar.gz"),
+            ("CONSOLE.txt", "CONSOLE.txt"),
+            (".lock", "_.lock"),
+            ("desktop.ini", "_desktop.ini"),
+            ("my_vti_file.txt", "myvti_file.txt"),
+            ("file.", "file"),
+            ("file...", "file"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(notox::clean_str(name, &options), expected, "{}", name);
+        }
+        // the reserved names are kept without a profile
+        assert_eq!(
+            notox::clean_str("CON.txt", &NotoxArgs::default()),
+            "CON.txt"
+        );
+        assert_eq!(notox::clean_str("file.", &NotoxArgs::default()), "file.");
+    }
+
+    #[test]
+    fn test_profile_windows_and_posix() {
+        let names = [
+            (Profile::WindowsSafe, "a:b?c*d|e.txt", "a_b_c_d_e.txt"),
+            (Profile::WindowsSafe, "my file .", "my file"),
+            (Profile::WindowsSafe, "CON.txt", "CON_.txt"),
+            (Profile::WindowsSafe, "nul", "nul_"),
+            (Profile::WindowsSafe, "COM1.tar.gz", "COM1_.tar.gz"),
+            (Profile::WindowsSafe, "CONSOLE.txt", "CONSOLE.txt"),
+            (Profile::WindowsSafe, "a+b.txt", "a+b.txt"),
+            (Profile::PortablePosix, "a:b?c+d e.txt", "a_b_c_d_e.txt"),
+            (Profile::PortablePosix, "-rf", "_-rf"),
+            (Profile::PortablePosix, "CON.txt", "CON.txt"),
+            (
+                Profile::PortablePosix,
+                "report-2024_v1.0.txt",
+                "report-2024_v1.0.txt",
+            ),
+        ];
+        for (profile, name, expected) in names {
+            // the characters kept by the options are cleaned by the profile
+            let options = NotoxArgs {
+                profile,
+                keep_spaces: true,
+                allowed_extra: HashSet::from([':', '?', '*', '|', '+']),
+                ..Default::default()
+            };
+            assert_eq!(notox::clean_str(name, &options), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_unsafe_only_keeps_letters() {
+        let options = NotoxArgs {
+            mode: CleanMode::UnsafeOnly,
+            ..Default::default()
+        };
+        let names = [
+            "café.txt",
+            "日本語.txt",
+            "Привет.pdf",
+            "ελληνικά",
+            "한국어_문서.hwp",
+            "Straße",
+            "٣٤٥.txt",
+            "cafe\u{301}.txt",
+            "ｆｕｌｌ.txt",
+        ];
+        for name in names {
+            assert_eq!(notox::clean_str(name, &options), name, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_unsafe_only_replaces_unsafe_chars() {
+        let options = NotoxArgs {
+            mode: CleanMode::UnsafeOnly,
+            ..Default::default()
+        };
+        let names = [
+            ("café 日本語.txt", "café_日本語.txt"),
+            ("a\u{202E}b.txt", "a_b.txt"),
+            ("a\u{85}b", "a_b"),
+            ("a\u{A0}b", "a_b"),
+            ("a\u{200B}b", "a_b"),
+            ("a\u{2028}b", "a_b"),
+            ("a\nb\tc", "a_b_c"),
+            ("a;b|c$d.txt", "a_b_c_d.txt"),
+            ("a\\b", "a_b"),
+            ("a／b", "a_b"),
+            ("😀photo.png", "_photo.png"),
+            ("report–final", "report-final"),
+            ("été 💥 ★ été", "été_été"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(notox::clean_str(name, &options), expected, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_transliterate_is_default() {
+        let options = NotoxArgs::default();
+        assert_eq!(options.mode, CleanMode::Transliterate);
+        assert_eq!(notox::clean_str("café 日本語.txt", &options), "cafe_.txt");
+        assert_eq!(notox::clean_str("Straße", &options), "Strase");
+    }
+
+    #[test]
+    fn test_unsafe_only_with_options() {
+        let options = NotoxArgs {
+            mode: CleanMode::UnsafeOnly,
+            overrides: HashMap::from([('é', "e".to_string())]),
+            lowercase: true,
+            ..Default::default()
+        };
+        assert_eq!(notox::clean_str("Café Ünïcode", &options), "cafe_ünïcode");
+
+        let options = NotoxArgs {
+            mode: CleanMode::UnsafeOnly,
+            shell_safe: true,
+            ..Default::default()
+        };
+        assert_eq!(notox::clean_str("café", &options), "caf_");
+    }
+
+    #[test]
+    fn test_is_kept_unicode() {
+        for one_char in ['é', '日', 'Я', '٣', '\u{301}', 'ｆ'] {
+            assert!(notox::is_kept_unicode(one_char), "{:?}", one_char);
+        }
+        for one_char in [
+            'a', '1', '_', '\u{85}', '\u{A0}', '\u{202E}', '★', '😀', '／',
+        ] {
+            assert!(!notox::is_kept_unicode(one_char), "{:?}", one_char);
+        }
+    }
+
+    #[test]
+    fn test_unsafe_only_rename() {
+        let dir_path = PathBuf::from("tests/tests_unsafe_only");
+        let _ = fs::remove_dir_all(&dir_path);
+        fs::create_dir_all(&dir_path).unwrap();
+        let file_path = dir_path.join("日本語 $(echo).txt");
+        fs::write(&file_path, "").unwrap();
+        let options = NotoxArgs {
+            dry_run: false,
+            output: Output::Quiet,
+            mode: CleanMode::UnsafeOnly,
+            ..Default::default()
+        };
+        let res = notox::notox(&options, &HashSet::from([file_path.clone()]));
+        let expected = dir_path.join("日本語_echo_.txt");
+        assert_eq!(
+            res,
+            vec![PathChange::Changed {
+                path: file_path,
+                modified: expected.clone(),
+            }]
+        );
+        assert!(expected.exists());
+        fs::remove_dir_all(&dir_path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "korean")]
+    fn test_romanize_syllable() {
+        use notox::korean::romanize_syllable;
+
+        let syllables = [
+            ('가', "ga"),
+            ('한', "han"),
+            ('국', "guk"),
+            ('서', "seo"),
+            ('울', "ul"),
+            ('녕', "nyeong"),
+            ('힣', "hit"),
+        ];
+        for (syllable, expected) in syllables {
+            assert_eq!(romanize_syllable(syllable).unwrap(), expected);
+        }
+        assert_eq!(romanize_syllable('a'), None);
+        assert_eq!(romanize_syllable('ㄱ'), None);
+    }
+
+    #[test]
+    #[cfg(feature = "korean")]
+    fn test_romanize_hangul_names() {
+        let options = NotoxArgs {
+            romanize_hangul: true,
+            ..Default::default()
+        };
+        let names = [
+            ("한국.txt", "hanguk.txt"),
+            ("서울 사진.jpg", "seoul_sajin.jpg"),
+            ("김치 recipe.md", "gimchi_recipe.md"),
+            ("안녕하세요!.txt", "annyeonghaseyo_.txt"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(notox::clean_str(name, &options), expected, "{}", name);
+        }
+        // the option is off by default
+        assert_eq!(notox::clean_str("한국.txt", &NotoxArgs::default()), "_.txt");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_non_utf8_single() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let names: [(&[u8], &str); 12] = [
+            // invalid first byte
+            (b"a\xffb.txt", "a_b.txt"),
+            // run of invalid bytes
+            (b"a\xff\xfe\x80b.txt", "a_b.txt"),
+            // continuation byte without first byte
+            (b"a\x80b.txt", "a_b.txt"),
+            // truncated sequence followed by ASCII
+            (b"a\xe2\x82b.txt", "a_b.txt"),
+            // truncated sequence at the end
+            (b"file.txt\xf0\x9f", "file.txt_"),
+            // surrogate
+            (b"a\xed\xa0\x80b.txt", "a_b.txt"),
+            // overlong encodings of `/` and `a`
+            (b"a\xe0\x80\xafb.txt", "a_b.txt"),
+            (b"\xf0\x80\x81\xa1.txt", "_.txt"),
+            (b"\xc1\x81.txt", "_.txt"),
+            // above U+10FFFF
+            (b"a\xf4\x90\x80\x80b.txt", "a_b.txt"),
+            // invalid bytes then a valid char
+            (b"\xffcaf\xc3\xa9", "_cafe"),
+            // truncated sequence followed by a valid sequence
+            (b"a\xc3\xc3\xa9", "a_e"),
+        ];
+        for (name, expected) in names {
+            for max in [1, 10] {
+                let options = NotoxArgs {
+                    max_consecutive_separators: max,
+                    ..Default::default()
+                };
+                assert_eq!(
+                    notox::clean_file_name(OsStr::from_bytes(name), &options),
+                    OsStr::new(expected),
+                    "{:?} {}",
+                    name,
+                    max
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_non_utf8_per_byte() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let per_byte = |max_consecutive_separators| NotoxArgs {
+            non_utf8_policy: NonUtf8Policy::PerByte,
+            max_consecutive_separators,
+            ..Default::default()
+        };
+        let names: [(&[u8], &str); 8] = [
+            (b"a\xffb.txt", "a_b.txt"),
+            (b"a\xff\xfe\x80b.txt", "a___b.txt"),
+            (b"a\xe2\x82b.txt", "a__b.txt"),
+            (b"file.txt\xf0\x9f", "file.txt__"),
+            (b"a\xed\xa0\x80b.txt", "a___b.txt"),
+            (b"a\xe0\x80\xafb.txt", "a___b.txt"),
+            (b"a\xf4\x90\x80\x80b.txt", "a____b.txt"),
+            (b"\xf0\x9f", "__"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(
+                notox::clean_file_name(OsStr::from_bytes(name), &per_byte(10)),
+                OsStr::new(expected),
+                "{:?}",
+                name
+            );
+        }
+        // runs are still limited by the maximum of consecutive separators
+        assert_eq!(
+            notox::clean_file_name(OsStr::from_bytes(b"a\xff\xfe\x80b.txt"), &per_byte(1)),
+            OsStr::new("a_b.txt")
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_non_utf8_only_truncated() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        // the pending bytes of a truncated sequence are flushed at the end of the name
+        let options = NotoxArgs::default();
+        for (name, expected) in [
+            (&b"\xf0\x9f"[..], "_"),
+            (b"\xe2\x82", "_"),
+            (b"\xc3", "_"),
+            (b"a__\xf0\x9f\x98", "a_"),
+        ] {
+            assert_eq!(
+                notox::clean_file_name(OsStr::from_bytes(name), &options),
+                OsStr::new(expected),
+                "{:?}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(all(unix, feature = "encoding"))]
+    fn test_transcode() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let cleaned = |name: &[u8], label: Option<&str>| {
+            let options = NotoxArgs {
+                source_encoding: label
+                    .map(|label| encoding_rs::Encoding::for_label(label.as_bytes()).unwrap()),
+                ..Default::default()
+            };
+            notox::clean_file_name(OsStr::from_bytes(name), &options)
+        };
+        // "café crème.txt" in Latin-1
+        let name = b"caf\xe9 cr\xe8me.txt";
+        assert_eq!(cleaned(name, Some("latin1")), "cafe_creme.txt");
+        // without transcoding, the invalid bytes are only replaced
+        assert_eq!(cleaned(name, None), "caf_cr_me.txt");
+        // the names valid in UTF-8 are not transcoded
+        assert_eq!(cleaned("café.txt".as_bytes(), Some("latin1")), "cafe.txt");
+
+        // "日本語 ファイル.txt" in Shift-JIS
+        let name = b"\x93\xfa\x96\x7b\x8c\xea \x83\x74\x83\x40\x83\x43\x83\x8b.txt";
+        assert_eq!(
+            cleaned(name, Some("shift_jis")),
+            cleaned("日本語 ファイル.txt".as_bytes(), None)
+        );
+        assert_ne!(cleaned(name, Some("shift_jis")), cleaned(name, None));
+    }
+}
+
+mod audit {
+    use std::ffi::OsStr;
+
+    use notox::{audit, NameIssue};
+
+    #[test]
+    fn test_audit_clean() {
+        assert_eq!(audit(OsStr::new("file.txt")), vec![]);
+        // the accents are cleaned but are not a problem
+        assert_eq!(audit(OsStr::new("café.txt")), vec![]);
+        assert_eq!(audit(OsStr::new(".")), vec![]);
+    }
+
+    #[test]
+    fn test_audit_issues() {
+        assert_eq!(
+            audit(OsStr::new(" a\tb\u{200B}c ")),
+            vec![
+                NameIssue::LeadingWhitespace,
+                NameIssue::ControlChar {
+                    offset: 2,
+                    one_char: '\t'
+                },
+                NameIssue::InvisibleChar {
+                    offset: 4,
+                    one_char: '\u{200B}'
+                },
+                NameIssue::TrailingWhitespace,
+            ]
+        );
+        assert_eq!(
+            audit(OsStr::new("file\u{202E}txt.exe.")),
+            vec![
+                NameIssue::InvisibleChar {
+                    offset: 4,
+                    one_char: '\u{202E}'
+                },
+                NameIssue::TrailingDot,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_audit_display() {
+        assert_eq!(
+            NameIssue::InvisibleChar {
+                offset: 4,
+                one_char: '\u{200B}'
+            }
+            .to_string(),
+            "invisible char U+200B at byte 4"
+        );
+        assert_eq!(
+            NameIssue::ControlChar {
+                offset: 0,
+                one_char: '\n'
+            }
+            .to_string(),
+            r"control char '\n' at byte 0"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_audit_invalid_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        assert_eq!(
+            audit(OsStr::from_bytes(b"a\xff\xfeb\x01")),
+            vec![
+                NameIssue::InvalidUtf8 { offset: 1, len: 2 },
+                NameIssue::ControlChar {
+                    offset: 4,
+                    one_char: '\u{1}'
+                },
+            ]
+        );
+    }
+}
+
+mod categories {
+    use std::{collections::HashSet, ffi::OsStr, path::PathBuf};
+
+    use notox::{name_categories, Category, CategoryTally, Notox, NotoxArgs, Output};
+
+    #[test]
+    fn test_name_categories() {
+        assert_eq!(name_categories(OsStr::new("clean_name-1.txt")), vec![]);
+        assert_eq!(
+            name_categories(OsStr::new("café.txt")),
+            vec![Category::Accents]
+        );
+        assert_eq!(
+            name_categories(OsStr::new("é b#\u{1}.txt")),
+            vec![
+                Category::Accents,
+                Category::Whitespace,
+                Category::Symbols,
+                Category::Control
+            ]
+        );
+        assert_eq!(
+            name_categories(OsStr::new("❤.txt")),
+            vec![Category::Symbols]
+        );
+    }
+
+    #[test]
+    fn test_category_tally() {
+        let options = NotoxArgs {
+            dry_run: true,
+            output: Output::CategoryReport,
+            ..Default::default()
+        };
+        let paths_to_check = HashSet::from([
+            PathBuf::from("café.txt"),
+            PathBuf::from("a b.txt"),
+            PathBuf::from("a#b.txt"),
+            PathBuf::from("a\u{1}b.txt"),
+            PathBuf::from("é b#.txt"),
+            PathBuf::from("clean.txt"),
+        ]);
+        let notox_inst = Notox::new(&options);
+        let res = notox_inst.run(&paths_to_check);
+        assert_eq!(
+            CategoryTally::from_results(&res),
+            CategoryTally {
+                accents: 2,
+                symbols: 2,
+                whitespace: 2,
+                control: 1,
+            }
+        );
+        let mut output = Vec::new();
+        notox_inst.print_output_to(res, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "accents: 2\nsymbols: 2\nwhitespace: 2\ncontrol: 1\n6 files checked\n"
+        );
+    }
+}
+
+mod clean_file_name {
+    use std::{collections::HashSet, ffi::OsStr, path::PathBuf};
+
+    use notox::{clean_file_name, clean_str, Category, NotoxArgs, PathChange};
+
+    use crate::common::quiet_options;
+
+    fn options() -> NotoxArgs {
+        quiet_options(true)
+    }
+
+    #[test]
+    fn test_clean_str() {
+        let options = options();
+        assert_eq!(
+            clean_str("été à la plage.jpg", &options),
+            "ete_a_la_plage.jpg"
+        );
+        assert_eq!(clean_str("clean.txt", &options), "clean.txt");
+        let options = NotoxArgs {
+            categories: vec![Category::Accents],
+            ..options
+        };
+        assert_eq!(
+            clean_str("été à la plage.jpg", &options),
+            "ete a la plage.jpg"
+        );
+    }
+
+    #[test]
+    fn test_clean_file_name_same_as_run() {
+        let options = options();
+        let names = [
+            "my file.txt",
+            "Crème brûlée (1).JPG",
+            "😀 ❤.png",
+            "résumé—final?.pdf",
+            "a\tb\nc",
+            "already_clean.rs",
+            ".hidden file",
+        ];
+        for name in names {
+            let res = notox::notox(&options, &HashSet::from([PathBuf::from(name)]));
+            let expected = match &res[0] {
+                PathChange::Unchanged { path } => path.clone(),
+                PathChange::WouldChange { modified, .. } => modified.clone(),
+                _ => panic!("Unexpected result {:?}", res[0]),
+            };
+            let cleaned = clean_file_name(OsStr::new(name), &options);
+            assert_eq!(PathBuf::from(&cleaned), expected, "{}", name);
+            assert_eq!(clean_str(name, &options), cleaned.to_string_lossy());
+            // nothing was created
+            assert!(!expected.exists());
+        }
+    }
+}
+
+mod german_sharp_s {
+    use std::collections::HashMap;
+
+    use notox::{clean_str, parse_args, NotoxArgs};
+
+    fn sharp_s_options() -> NotoxArgs {
+        NotoxArgs {
+            german_sharp_s: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_german_sharp_s() {
+        let options = sharp_s_options();
+        let names = [
+            ("Straße.txt", "Strasse.txt"),
+            ("GROẞ.txt", "GROSS.txt"),
+            ("ß ß.txt", "ss_ss.txt"),
+            ("a  ß!!.txt", "a_ss_.txt"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(clean_str(name, &options), expected, "{}", name);
+        }
+        assert_eq!(clean_str("Straße.txt", &NotoxArgs::default()), "Strase.txt");
+    }
+
+    #[test]
+    fn test_german_sharp_s_lowercase() {
+        let options = NotoxArgs {
+            lowercase: true,
+            ..sharp_s_options()
+        };
+        assert_eq!(
+            clean_str("GROẞE Straße.TXT", &options),
+            "grosse_strasse.txt"
+        );
+    }
+
+    #[test]
+    fn test_german_sharp_s_overrides() {
+        let options = NotoxArgs {
+            overrides: HashMap::from([('ß', "sz".to_string())]),
+            ..sharp_s_options()
+        };
+        assert_eq!(clean_str("Straße ẞ.txt", &options), "Strasze_SS.txt");
+    }
+
+    #[test]
+    fn test_parse_args_german_sharp_s() {
+        let vec_args = [
+            "notox".to_string(),
+            "--german-sharp-s".to_string(),
+            ".".to_string(),
+        ];
+        let res = parse_args(&vec_args).unwrap();
+        assert!(res.0.german_sharp_s);
+    }
+}
+
+mod length_delta {
+    use std::{ffi::OsStr, path::PathBuf};
+
+    use notox::{name_length_delta, Notox, NotoxArgs, PathChange};
+
+    #[test]
+    fn test_name_length_delta() {
+        let options = NotoxArgs::default();
+        // `Œ` is 2 bytes, `OE` is 2 bytes
+        assert_eq!(name_length_delta(OsStr::new("Œuvre"), &options), 0);
+        // `Ꜳ` is 3 bytes, `AA` is 2 bytes
+        assert_eq!(name_length_delta(OsStr::new("Ꜳ"), &options), -1);
+        // `ǳ` is 2 bytes, `dz` is 2 bytes, the space is replaced
+        assert_eq!(name_length_delta(OsStr::new("a ǳ"), &options), 0);
+        assert_eq!(name_length_delta(OsStr::new("file.txt"), &options), 0);
+        let options = NotoxArgs {
+            german_sharp_s: true,
+            ..Default::default()
+        };
+        // `ß` is 2 bytes, `ss` is 2 bytes
+        assert_eq!(name_length_delta(OsStr::new("Straße"), &options), 0);
+        let options = NotoxArgs {
+            overrides: [('é', "e_acute".to_string())].into(),
+            ..Default::default()
+        };
+        // `é` is 2 bytes, `e_acute` is 7 bytes
+        assert_eq!(name_length_delta(OsStr::new("café"), &options), 5);
+    }
+
+    #[test]
+    fn test_length_delta_of_change() {
+        let change = PathChange::WouldChange {
+            path: PathBuf::from("dir").join("Ꜳ"),
+            modified: PathBuf::from("dir").join("AA"),
+        };
+        assert_eq!(change.length_delta(), Some(-1));
+        let unchanged = PathChange::Unchanged {
+            path: PathBuf::from("file.txt"),
+        };
+        assert_eq!(unchanged.length_delta(), None);
+    }
+
+    #[test]
+    fn test_length_delta_in_output() {
+        let notox_inst = Notox::new(&NotoxArgs::default());
+        let mut output = Vec::new();
+        notox_inst
+            .print_output_to(
+                vec![
+                    PathChange::WouldChange {
+                        path: PathBuf::from("café"),
+                        modified: PathBuf::from("cafe_acute"),
+                    },
+                    PathChange::WouldChange {
+                        path: PathBuf::from("Ꜳ"),
+                        modified: PathBuf::from("AA"),
+                    },
+                ],
+                &mut output,
+            )
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("café -> cafe_acute : dry-run (+5 bytes)\n"));
+        assert!(output.contains("Ꜳ -> AA : dry-run\n"));
+    }
+}
+
+mod lowercase {
+    use notox::{clean_str, parse_args, CaseMode, NotoxArgs};
+
+    fn lowercase_options() -> NotoxArgs {
+        NotoxArgs {
+            lowercase: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_lowercase() {
+        let options = lowercase_options();
+        let names = [
+            ("README.md", "readme.md"),
+            ("Été Photo.JPG", "ete_photo.jpg"),
+            ("ÀÉÎ  Õ.TXT", "aei_o.txt"),
+            ("My  (File).tar.GZ", "my_file_.tar.gz"),
+            (".Bashrc", ".bashrc"),
+            ("already_clean.txt", "already_clean.txt"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(clean_str(name, &options), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_lowercase_with_post_separator_case() {
+        let options = NotoxArgs {
+            post_separator_case: Some(CaseMode::Upper),
+            ..lowercase_options()
+        };
+        assert_eq!(
+            clean_str("MY FILE report.TXT", &options),
+            "my_File_Report.txt"
+        );
+    }
+
+    #[test]
+    fn test_lowercase_off() {
+        assert_eq!(
+            clean_str("Été Photo.JPG", &NotoxArgs::default()),
+            "Ete_Photo.JPG"
+        );
+    }
+
+    #[test]
+    fn test_parse_args_lowercase() {
+        let vec_args = [
+            "notox".to_string(),
+            "--lowercase".to_string(),
+            ".".to_string(),
+        ];
+        let res = parse_args(&vec_args).unwrap();
+        assert!(res.0.lowercase);
+    }
+}
+
+mod max_length {
+    use notox::{clean_str, parse_args, ExitCode, NotoxArgs};
+
+    fn max_length_options(max_length: usize) -> NotoxArgs {
+        NotoxArgs {
+            max_length: Some(max_length),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_max_length_long_name() {
+        let name = format!("{}.pdf", "Œuvre complète ".repeat(27));
+        assert!(name.chars().count() >= 400);
+        let cleaned = clean_str(&name, &max_length_options(255));
+        assert!(cleaned.len() <= 255, "{}", cleaned.len());
+        assert!(cleaned.ends_with(".pdf"), "{}", cleaned);
+        assert!(!cleaned.ends_with("_.pdf"), "{}", cleaned);
+        assert!(cleaned.starts_with("OEuvre_complete_OEuvre"), "{}", cleaned);
+    }
+
+    #[test]
+    fn test_max_length_truncation() {
+        let names = [
+            // no trailing separator
+            ("my long file.txt", 12, "my_long.txt"),
+            ("my long file.txt", 13, "my_long_f.txt"),
+            // short names are kept
+            ("short.txt", 12, "short.txt"),
+            // no extension, dotfiles have no extension
+            ("été en famille", 8, "ete_en_f"),
+            (".bashrc.backup", 7, ".bashrc"),
+            // the extension alone does not fit
+            ("a.verylongextension", 6, "a.very"),
+            // without a trailing dot
+            ("a.tar", 2, "a"),
+            // no limit
+            ("hello world.txt", 0, "hello_world.txt"),
+        ];
+        for (name, max_length, expected) in names {
+            assert_eq!(
+                clean_str(name, &max_length_options(max_length)),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_length_char_boundary() {
+        let options = NotoxArgs {
+            max_length: Some(6),
+            // no category, the accents are kept
+            categories: Vec::new(),
+            ..Default::default()
+        };
+        let cleaned = clean_str("aéééé.t", &options);
+        assert_eq!(cleaned, "aé.t");
+    }
+
+    #[test]
+    fn test_parse_args_max_length() {
+        let vec_args = [
+            "notox".to_string(),
+            "--max-length".to_string(),
+            "100".to_string(),
+            ".".to_string(),
+        ];
+        assert_eq!(parse_args(&vec_args).unwrap().0.max_length, Some(100));
+        for invalid in ["0", "many"] {
+            let vec_args = [
+                "notox".to_string(),
+                "--max-length".to_string(),
+                invalid.to_string(),
+            ];
+            assert_eq!(parse_args(&vec_args).err(), Some(ExitCode::Usage));
+        }
+    }
+}
+
+#[cfg(feature = "normalize")]
+mod normalize {
+    use std::collections::HashMap;
+
+    use notox::{clean_str, parse_args, NotoxArgs};
+
+    /// `café` with the precomposed `é`
+    const CAFE_NFC: &str = "caf\u{e9}";
+
+    /// `café` with `e` and the combining acute accent
+    const CAFE_NFD: &str = "cafe\u{301}";
+
+    fn nfc_options() -> NotoxArgs {
+        NotoxArgs {
+            normalize_nfc: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize_both_forms() {
+        let options = nfc_options();
+        assert_eq!(clean_str(CAFE_NFC, &options), "cafe");
+        assert_eq!(clean_str(CAFE_NFD, &options), clean_str(CAFE_NFC, &options));
+    }
+
+    #[test]
+    fn test_normalize_precomposed_override() {
+        let mut options = nfc_options();
+        options.overrides = HashMap::from([('é', "e_acute".to_string())]);
+        assert_eq!(clean_str(CAFE_NFC, &options), "cafe_acute");
+        assert_eq!(clean_str(CAFE_NFD, &options), "cafe_acute");
+        options.normalize_nfc = false;
+        assert_eq!(clean_str(CAFE_NFD, &options), "cafe");
+    }
+
+    #[test]
+    fn test_parse_args_nfc() {
+        let args = vec!["notox".to_string(), "--nfc".to_string()];
+        assert!(parse_args(&args).unwrap().0.normalize_nfc);
+        assert!(!parse_args(&["notox".to_string()]).unwrap().0.normalize_nfc);
+    }
+}
+
+mod overrides {
+    use std::collections::{HashMap, HashSet};
+
+    use notox::{clean_str, transliterate, NotoxArgs, TRANSLITERATIONS};
+
+    fn options_with(overrides: &[(char, &str)]) -> NotoxArgs {
+        NotoxArgs {
+            overrides: overrides
+                .iter()
+                .map(|(one_char, replacement)| (*one_char, replacement.to_string()))
+                .collect::<HashMap<_, _>>(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_transliterate() {
+        assert_eq!(transliterate('é'), Some("e"));
+        assert_eq!(transliterate('Ꜳ'), Some("AA"));
+        assert_eq!(transliterate('ß'), Some("s"));
+        assert_eq!(transliterate('–'), Some("-"));
+        assert_eq!(transliterate('€'), None);
+    }
+
+    #[test]
+    fn test_transliterations_unique() {
+        let mut seen = HashSet::new();
+        for (chars, _) in TRANSLITERATIONS {
+            for one_char in chars.chars() {
+                assert!(seen.insert(one_char), "{} is mapped twice", one_char);
+            }
+        }
+    }
+
+    #[test]
+    fn test_overrides() {
+        let options = options_with(&[('ß', "ss"), ('Ø', "OE"), ('€', "EUR")]);
+        assert_eq!(clean_str("Straße.txt", &options), "Strasse.txt");
+        assert_eq!(clean_str("Ørsted ø.txt", &options), "OErsted_o.txt");
+        assert_eq!(clean_str("10€.txt", &options), "10EUR.txt");
+        // the ASCII chars are not looked up
+        let options = options_with(&[('a', "b"), (' ', "-")]);
+        assert_eq!(clean_str("a b.txt", &options), "a_b.txt");
+    }
+
+    #[test]
+    fn test_no_overrides() {
+        let options = NotoxArgs::default();
+        assert_eq!(clean_str("Straße.txt", &options), "Strase.txt");
+        assert_eq!(clean_str("10€.txt", &options), "10_.txt");
+    }
+}
+
+mod preserve_extension {
+    use notox::{clean_str, parse_args, NotoxArgs};
+
+    fn preserve_options() -> NotoxArgs {
+        NotoxArgs {
+            preserve_extension: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_preserve_extension() {
+        let options = preserve_options();
+        let names = [
+            ("résumé.PDF", "resume.PDF"),
+            ("my.photo.v2.JPG", "my_photo_v2.JPG"),
+            ("my. file.txt", "my_file.txt"),
+            ("archive.tar.gz", "archive_tar.gz"),
+            ("clean.txt", "clean.txt"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(clean_str(name, &options), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_preserve_extension_edge_cases() {
+        let options = preserve_options();
+        let names = [
+            // dotfiles have no stem
+            (".bashrc", ".bashrc"),
+            (".config.old.json", ".config_old.json"),
+            // no extension
+            ("no extension", "no_extension"),
+            ("été", "ete"),
+            // ending with a dot, the extension is empty
+            ("file.", "file."),
+            ("a.b.", "a_b."),
+        ];
+        for (name, expected) in names {
+            assert_eq!(clean_str(name, &options), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_preserve_extension_off() {
+        assert_eq!(
+            clean_str("my.photo v2.JPG", &NotoxArgs::default()),
+            "my.photo_v2.JPG"
+        );
+    }
+
+    #[test]
+    fn test_parse_args_preserve_extension() {
+        let vec_args = [
+            "notox".to_string(),
+            "--preserve-extension".to_string(),
+            ".".to_string(),
+        ];
+        assert!(parse_args(&vec_args).unwrap().0.preserve_extension);
+    }
+}
+
+#[cfg(feature = "regex")]
+mod redact {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{ExitCode, Notox, NotoxArgs, Output, PathChange, Redaction};
+
+    fn aws_key_options() -> NotoxArgs {
+        NotoxArgs {
+            dry_run: true,
+            output: Output::Default,
+            redactions: vec![Redaction {
+                pattern: regex::Regex::new("AKIA[0-9A-Z]{16}").unwrap(),
+                replacement: "REDACTED".to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_redact_aws_key() {
+        let options = aws_key_options();
+        let names = [
+            (
+                "backup_AKIAABCDEFGHIJ123456_prod.sql",
+                "backup_REDACTED_prod.sql",
+            ),
+            (
+                "AKIAABCDEFGHIJ123456 et AKIA0000000000000000.txt",
+                "REDACTED_et_REDACTED.txt",
+            ),
+            ("backup AKIA1234.sql", "backup_AKIA1234.sql"),
+        ];
+        for (name, expected) in names {
+            let res = notox::notox(&options, &HashSet::from([PathBuf::from(name)]));
+            match &res[0] {
+                PathChange::WouldChange { modified, .. } => {
+                    assert_eq!(modified, &PathBuf::from(expected), "{}", name)
+                }
+                _ => panic!("Unexpected result {:?}", res[0]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_redacted_in_output() {
+        let options = aws_key_options();
+        assert!(options.is_redacted(&PathBuf::from("dir/key_AKIAABCDEFGHIJ123456")));
+        assert!(!options.is_redacted(&PathBuf::from("AKIAABCDEFGHIJ123456/key")));
+
+        let notox_inst = Notox::new(&options);
+        let final_res = notox_inst.run(&HashSet::from([PathBuf::from(
+            "key AKIAABCDEFGHIJ123456.txt",
+        )]));
+        let mut buffer = Vec::new();
+        notox_inst.print_output_to(final_res, &mut buffer).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "key AKIAABCDEFGHIJ123456.txt -> key_REDACTED.txt : dry-run (redacted)\n1 file checked: 0 changed, 1 would change, 0 unchanged, 0 errors\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_args_redact() {
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--redact".to_string(),
+            "AKIA[0-9A-Z]{16}".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).unwrap();
+        assert_eq!(options.redactions, aws_key_options().redactions);
+
+        let vec_args = [
+            "notox".to_string(),
+            "--redact".to_string(),
+            "AKIA[".to_string(),
+        ];
+        assert_eq!(notox::parse_args(&vec_args).err().unwrap(), ExitCode::Usage);
+    }
+}
+
+mod report {
+    use std::ffi::OsStr;
+
+    use notox::{clean_file_name, clean_name_with_report, NotoxArgs, Substitution};
+
+    fn substitution(original: &str, replacement: &str) -> Substitution {
+        Substitution {
+            original: original.to_string(),
+            replacement: replacement.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_report_substitutions() {
+        let options = NotoxArgs::default();
+        let (cleaned, report) = clean_name_with_report(OsStr::new("café  (1).txt"), &options);
+        assert_eq!(cleaned, "cafe_1_.txt");
+        assert_eq!(
+            report,
+            vec![
+                substitution("é", "e"),
+                substitution(" ", "_"),
+                substitution(" ", ""),
+                substitution("(", ""),
+                substitution(")", "_"),
+            ]
+        );
+        assert_eq!(report[0].to_string(), r#""é" -> "e""#);
+    }
+
+    #[test]
+    fn test_report_unchanged() {
+        let options = NotoxArgs::default();
+        let (cleaned, report) = clean_name_with_report(OsStr::new("file.txt"), &options);
+        assert_eq!(cleaned, "file.txt");
+        assert!(report.is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_report_invalid_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let options = NotoxArgs::default();
+        let name = OsStr::from_bytes(b"a\xff\xfeb");
+        let (cleaned, report) = clean_name_with_report(name, &options);
+        assert_eq!(cleaned, clean_file_name(name, &options));
+        assert_eq!(report, vec![substitution("\u{FFFD}\u{FFFD}", "_")]);
+    }
+}
+
+mod trim {
+    use notox::{clean_str, parse_args, trim_separators, NotoxArgs};
+
+    fn trim_options() -> NotoxArgs {
+        NotoxArgs {
+            trim: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_trim_separators() {
+        let names = [
+            ("___file___", "file"),
+            ("file_.txt", "file.txt"),
+            ("file._txt", "file.txt"),
+            ("__file_.txt_", "file.txt"),
+            ("a_._b.tar_._gz", "a.b.tar.gz"),
+            ("my_file.txt", "my_file.txt"),
+            ("_", "_"),
+            ("__.txt", "_.txt"),
+            ("", ""),
+        ];
+        for (name, expected) in names {
+            assert_eq!(trim_separators(name), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_trim_dotfiles() {
+        let names = [
+            (".hidden_", ".hidden"),
+            ("._hidden_", ".hidden"),
+            ("._", "._"),
+            (".bashrc", ".bashrc"),
+            ("._config_.toml", ".config.toml"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(trim_separators(name), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_trim_clean_name() {
+        let options = trim_options();
+        let names = [
+            ("(draft) report!.txt", "draft_report.txt"),
+            ("Été (1).mp3", "Ete_1.mp3"),
+            ("  notes  .md", "notes.md"),
+            (".#config#", ".config"),
+            ("!!!.txt", "_.txt"),
+        ];
+        for (name, expected) in names {
+            assert_eq!(clean_str(name, &options), expected, "{}", name);
+        }
+        assert_eq!(
+            clean_str("(draft) report!.txt", &NotoxArgs::default()),
+            "_draft_report_.txt"
+        );
+    }
+
+    #[test]
+    fn test_parse_args_trim() {
+        let vec_args = ["notox".to_string(), "--trim".to_string(), ".".to_string()];
+        let res = parse_args(&vec_args).unwrap();
+        assert!(res.0.trim);
+    }
 }
@@ -0,0 +1,3171 @@
+mod common;
+
+mod apply_changes {
+    use std::path::PathBuf;
+
+    use notox::{apply_changes, PathChange};
+
+    use crate::common::quiet_options;
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_apply_deserialized_plan() {
+        use notox::Notox;
+        use std::collections::HashSet;
+
+        let dir = PathBuf::from("test apply plan");
+        std::fs::create_dir_all(dir.join("sub dir")).unwrap();
+        std::fs::File::create(dir.join("a b.txt")).unwrap();
+        std::fs::File::create(dir.join("sub dir/c d.txt")).unwrap();
+        std::fs::File::create(dir.join("ok.txt")).unwrap();
+
+        let plan = Notox::new(&quiet_options(true)).run(&HashSet::from([dir.clone()]));
+        let plan_json = serde_json::to_string(&plan).unwrap();
+        let plan: Vec<PathChange> = serde_json::from_str(&plan_json).unwrap();
+
+        // applying in dry-run only proposes the renames again
+        let res = apply_changes(&plan, &quiet_options(true));
+        assert_eq!(res.len(), 4);
+        assert!(res.iter().all(PathChange::is_dry_run_rename));
+        assert!(dir.exists());
+
+        let res = apply_changes(&plan, &quiet_options(false));
+        let new_dir = PathBuf::from("test_apply_plan");
+        assert_eq!(
+            res[0],
+            PathChange::Changed {
+                path: dir.clone(),
+                modified: new_dir.clone(),
+            }
+        );
+        assert_eq!(res.len(), 4);
+        assert!(res
+            .iter()
+            .all(|one_change| matches!(one_change, PathChange::Changed { .. })));
+        assert!(new_dir.join("a_b.txt").exists());
+        assert!(new_dir.join("sub_dir/c_d.txt").exists());
+        assert!(new_dir.join("ok.txt").exists());
+
+        std::fs::remove_dir_all(&new_dir).unwrap();
+    }
+
+    #[test]
+    fn test_apply_outdated_plan() {
+        let plan = vec![
+            PathChange::WouldChange {
+                path: PathBuf::from("test apply missing.txt"),
+                modified: PathBuf::from("test_apply_missing.txt"),
+            },
+            PathChange::Unchanged {
+                path: PathBuf::from("Cargo.toml"),
+            },
+        ];
+        assert_eq!(
+            apply_changes(&plan, &quiet_options(false)),
+            vec![PathChange::Error {
+                path: PathBuf::from("test apply missing.txt"),
+                error: "source not found, the plan is outdated".to_string(),
+            }]
+        );
+        assert!(!PathBuf::from("test_apply_missing.txt").exists());
+    }
+
+    #[test]
+    fn test_apply_existing_target() {
+        let source = PathBuf::from("test apply exists.txt");
+        let target = PathBuf::from("test_apply_exists.txt");
+        std::fs::File::create(&source).unwrap();
+        std::fs::File::create(&target).unwrap();
+
+        let plan = vec![PathChange::Changed {
+            path: source.clone(),
+            modified: target.clone(),
+        }];
+        assert_eq!(
+            apply_changes(&plan, &quiet_options(true)),
+            vec![PathChange::WouldFail {
+                path: source.clone(),
+                modified: target.clone(),
+                reason: "target already exists".to_string(),
+            }]
+        );
+
+        std::fs::remove_file(&source).unwrap();
+        std::fs::remove_file(&target).unwrap();
+    }
+}
+
+mod backup {
+    use std::{collections::HashSet, fs, path::PathBuf};
+
+    use notox::{backup_path, Notox, NotoxArgs, PathChange};
+
+    use crate::common::quiet_options;
+
+    fn options(backup_dir: &str) -> NotoxArgs {
+        NotoxArgs {
+            backup_dir: Some(PathBuf::from(backup_dir)),
+            ..quiet_options(false)
+        }
+    }
+
+    #[test]
+    fn test_backup_path() {
+        let backup_dir = PathBuf::from("copies");
+        assert_eq!(
+            backup_path(&backup_dir, &PathBuf::from("photos/a b.jpg")),
+            PathBuf::from("copies/photos/a b.jpg")
+        );
+        // the copy stays inside the backup directory
+        assert_eq!(
+            backup_path(&backup_dir, &PathBuf::from("/home/../x/./a b.jpg")),
+            PathBuf::from("copies/home/x/a b.jpg")
+        );
+    }
+
+    #[test]
+    fn test_backup_before_rename() {
+        let dir = PathBuf::from("test_backup_before_rename");
+        let backup_dir = "test_backup_before_rename_copies";
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_dir_all(backup_dir);
+        fs::create_dir_all(dir.join("sub dir")).unwrap();
+        fs::write(dir.join("sub dir").join("a b.txt"), "data").unwrap();
+
+        let res = Notox::new(&options(backup_dir)).run(&HashSet::from([dir.clone()]));
+        assert!(
+            res.iter().all(|one_change| !one_change.is_error()),
+            "{:?}",
+            res
+        );
+        assert!(dir.join("sub_dir").join("a_b.txt").exists());
+        // the file is copied under its parent already renamed, the directories are not copied
+        let copy = PathBuf::from(backup_dir)
+            .join(&dir)
+            .join("sub_dir")
+            .join("a b.txt");
+        assert_eq!(fs::read_to_string(copy).unwrap(), "data");
+        assert!(!PathBuf::from(backup_dir)
+            .join(&dir)
+            .join("sub dir")
+            .exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(backup_dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_keeps_previous_copy() {
+        let dir = PathBuf::from("test_backup_keeps_previous_copy");
+        let backup_dir = "test_backup_keeps_previous_copy_copies";
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_dir_all(backup_dir);
+        fs::create_dir_all(&dir).unwrap();
+        let copies = PathBuf::from(backup_dir).join(&dir);
+
+        // the same name is cleaned by two runs, then by a third one
+        for (run, suffix) in ["first", "second", "third"].iter().zip(["", "_1", "_2"]) {
+            let _ = fs::remove_file(dir.join("a_b.txt"));
+            fs::write(dir.join("a b.txt"), run).unwrap();
+            let res = Notox::new(&options(backup_dir)).run(&HashSet::from([dir.clone()]));
+            assert!(
+                res.iter().all(|one_change| !one_change.is_error()),
+                "{:?}",
+                res
+            );
+            let copy = copies.join(format!("a b{}.txt", suffix));
+            assert_eq!(fs::read_to_string(copy).unwrap(), *run);
+        }
+        assert_eq!(fs::read_to_string(copies.join("a b.txt")).unwrap(), "first");
+
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(backup_dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_failure_aborts_rename() {
+        let source = PathBuf::from("test backup failure.txt");
+        // a file cannot hold the copies
+        let backup_dir = "test_backup_failure_not_a_dir";
+        fs::write(&source, "").unwrap();
+        fs::write(backup_dir, "").unwrap();
+
+        let res = Notox::new(&options(backup_dir)).run(&HashSet::from([source.clone()]));
+        match res.as_slice() {
+            [PathChange::ErrorBackup { path, backup, .. }] => {
+                assert_eq!(path, &source);
+                assert_eq!(backup, &PathBuf::from(backup_dir).join(&source));
+            }
+            _ => panic!("Unexpected result {:?}", res),
+        }
+        assert!(source.exists());
+
+        fs::remove_file(&source).unwrap();
+        fs::remove_file(backup_dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_refused_target_not_copied() {
+        let dir = PathBuf::from("test_backup_refused_target");
+        let backup_dir = "test_backup_refused_target_copies";
+        let _ = fs::remove_dir_all(&dir);
+        let _ = fs::remove_dir_all(backup_dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a b.txt"), "source").unwrap();
+        fs::write(dir.join("a_b.txt"), "target").unwrap();
+
+        // the rename is refused by each run, no copy is left behind
+        for _ in 0..2 {
+            let res = Notox::new(&options(backup_dir)).run(&HashSet::from([dir.clone()]));
+            assert_eq!(
+                res.iter()
+                    .filter(|one_change| matches!(one_change, PathChange::ErrorRename { .. }))
+                    .count(),
+                1,
+                "{:?}",
+                res
+            );
+        }
+        assert!(dir.join("a b.txt").exists());
+        assert!(!PathBuf::from(backup_dir).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_dir_inside_cleaned_path() {
+        use assert_cmd::prelude::*;
+        use std::process::Command;
+
+        let dir = PathBuf::from("test_backup_dir_inside");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        // the backup directory is found by the second run, its copies keep their names
+        for run in ["first", "second"] {
+            let _ = fs::remove_file(dir.join("a_b.txt"));
+            fs::write(dir.join("a b.txt"), run).unwrap();
+            let mut cmd = Command::cargo_bin("notox").unwrap();
+            cmd.current_dir(&dir)
+                .args([".", "-d", "-q", "--backup", "bk"]);
+            cmd.assert().success();
+        }
+        assert_eq!(fs::read_to_string(dir.join("a_b.txt")).unwrap(), "second");
+        assert_eq!(
+            fs::read_to_string(dir.join("bk").join("a b.txt")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("bk").join("a b_1.txt")).unwrap(),
+            "second"
+        );
+        assert!(!dir.join("bk").join("a_b.txt").exists());
+        assert!(!dir.join("bk").join("bk").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_backup_error_serde() {
+        let error_backup = PathChange::ErrorBackup {
+            path: PathBuf::from("a b"),
+            backup: PathBuf::from("copies/a b"),
+            error: "Not a directory (os error 20)".to_string(),
+        };
+        let json = serde_json::to_string(&error_backup).unwrap();
+        assert_eq!(
+            json,
+            r#"{"path":"a b","modified":null,"error":"Not a directory (os error 20)","status":"error-backup","backup":"copies/a b"}"#
+        );
+        let deserialized: PathChange = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, error_backup);
+    }
+}
+
+mod callback {
+    use std::{collections::HashSet, fs, path::PathBuf};
+
+    use notox::{Notox, PathChange, Summary};
+
+    use crate::common::quiet_options;
+
+    #[test]
+    fn test_run_with_callback() {
+        let dir = PathBuf::from("test_run_with_callback");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub dir")).unwrap();
+        fs::write(dir.join("a b.txt"), "").unwrap();
+        fs::write(dir.join("clean.txt"), "").unwrap();
+        fs::write(dir.join("sub dir").join("c d.txt"), "").unwrap();
+
+        let notox_inst = Notox::new(&quiet_options(true));
+        let expected = notox_inst.run(&HashSet::from([dir.clone()]));
+
+        let mut seen = Vec::new();
+        let mut errors = 0;
+        let summary = notox_inst.run_with_callback(&HashSet::from([dir.clone()]), |one_change| {
+            if one_change.is_error() {
+                errors += 1;
+            }
+            seen.push(one_change.clone());
+        });
+        assert_eq!(summary, Summary::from_results(&expected));
+        assert_eq!(errors, 0);
+        assert_eq!(
+            seen.iter().collect::<HashSet<_>>(),
+            expected.iter().collect::<HashSet<_>>()
+        );
+        // a directory is passed before its entries
+        let position = |path: PathBuf| seen.iter().position(|one| one.path() == path).unwrap();
+        assert!(position(dir.clone()) < position(dir.join("sub dir")));
+        assert!(position(dir.join("sub dir")) < position(dir.join("sub dir/c d.txt")));
+        assert!(seen.contains(&PathChange::WouldChange {
+            path: dir.join("a b.txt"),
+            modified: dir.join("a_b.txt"),
+        }));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+mod canonicalize {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{Notox, NotoxArgs, Output, PathChange};
+
+    use crate::common::quiet_options;
+
+    #[test]
+    fn test_canonicalize_input_dedup() {
+        let file_name = PathBuf::from("test canonicalize.txt");
+        std::fs::File::create(&file_name).unwrap();
+        let paths_to_check = HashSet::from([
+            file_name.clone(),
+            PathBuf::from("./test canonicalize.txt"),
+            PathBuf::from("src/../test canonicalize.txt"),
+        ]);
+
+        let options = quiet_options(true);
+        let res = Notox::new(&options).run(&paths_to_check);
+        assert_eq!(res.len(), 3);
+
+        let options = NotoxArgs {
+            canonicalize_input: true,
+            ..options
+        };
+        let res = Notox::new(&options).run(&paths_to_check);
+        assert_eq!(res.len(), 1);
+        let canonical_path = std::fs::canonicalize(&file_name).unwrap();
+        match &res[0] {
+            PathChange::WouldChange { path, modified } => {
+                assert_eq!(path, &canonical_path);
+                assert_eq!(
+                    modified,
+                    &canonical_path.with_file_name("test_canonicalize.txt")
+                );
+            }
+            _ => panic!("Expected WouldChange"),
+        }
+        std::fs::remove_file(&file_name).unwrap();
+    }
+
+    #[test]
+    fn test_canonicalize_input_error() {
+        let options = NotoxArgs {
+            output: Output::Quiet,
+            canonicalize_input: true,
+            ..Default::default()
+        };
+        let missing = PathBuf::from("test canonicalize missing.txt");
+        let res = Notox::new(&options).run(&HashSet::from([missing.clone()]));
+        assert_eq!(res.len(), 1);
+        match &res[0] {
+            PathChange::Error { path, error } => {
+                assert_eq!(path, &missing);
+                assert!(error.starts_with("Cannot canonicalize path"));
+            }
+            _ => panic!("Expected Error"),
+        }
+    }
+}
+
+mod case_collision {
+    use std::{collections::HashSet, fs, path::PathBuf};
+
+    use notox::{ClobberPolicy, NotoxArgs, PathChange};
+
+    use crate::common::quiet_options;
+
+    #[test]
+    fn test_case_collision_with_existing_name() {
+        let dir = PathBuf::from("test_case_collision_existing");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("Cafe.txt"), "").unwrap();
+        fs::write(dir.join("café.txt"), "").unwrap();
+        let res = notox::notox(&quiet_options(true), &HashSet::from([dir.clone()]));
+        // the names are distinct on every filesystem, the collision does not depend on the host
+        assert!(
+            res.contains(&PathChange::WouldFail {
+                path: dir.join("café.txt"),
+                modified: dir.join("cafe.txt"),
+                reason: "name differs only by case from Cafe.txt".to_string(),
+            }),
+            "{:?}",
+            res
+        );
+        let res = notox::notox(&quiet_options(false), &HashSet::from([dir.clone()]));
+        assert!(
+            res.contains(&PathChange::ErrorRename {
+                path: dir.join("café.txt"),
+                modified: dir.join("cafe.txt"),
+                error: "name differs only by case from Cafe.txt".to_string(),
+            }),
+            "{:?}",
+            res
+        );
+        assert!(dir.join("café.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_case_collision_between_targets() {
+        let dir = PathBuf::from("test_case_collision_targets");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("Ète.txt"), "").unwrap();
+        fs::write(dir.join("éte.txt"), "").unwrap();
+        let res = notox::notox(&quiet_options(false), &HashSet::from([dir.clone()]));
+        // the first original name takes the target
+        assert!(
+            res.contains(&PathChange::Changed {
+                path: dir.join("Ète.txt"),
+                modified: dir.join("Ete.txt"),
+            }),
+            "{:?}",
+            res
+        );
+        assert!(
+            res.contains(&PathChange::ErrorRename {
+                path: dir.join("éte.txt"),
+                modified: dir.join("ete.txt"),
+                error: "name differs only by case from Ete.txt".to_string(),
+            }),
+            "{:?}",
+            res
+        );
+        assert!(dir.join("Ete.txt").exists());
+        assert!(dir.join("éte.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_lowercase_same_name_keeps_content() {
+        let dir = PathBuf::from("test_case_collision_lowercase");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("FILE.txt"), "upper").unwrap();
+        fs::write(dir.join("file.txt"), "lower").unwrap();
+        let options = NotoxArgs {
+            lowercase: true,
+            ..quiet_options(false)
+        };
+        let res = notox::notox(&options, &HashSet::from([dir.clone()]));
+        assert!(
+            res.contains(&PathChange::ErrorRename {
+                path: dir.join("FILE.txt"),
+                modified: dir.join("file.txt"),
+                error: "target already exists".to_string(),
+            }),
+            "{:?}",
+            res
+        );
+        assert_eq!(fs::read_to_string(dir.join("FILE.txt")).unwrap(), "upper");
+        assert_eq!(fs::read_to_string(dir.join("file.txt")).unwrap(), "lower");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_lowercase_same_target_keeps_content() {
+        let dir = PathBuf::from("test_case_collision_same_target");
+        // the earlier target is kept even when the existing targets can be replaced
+        for clobber_policy in [ClobberPolicy::Refuse, ClobberPolicy::Overwrite] {
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir(&dir).unwrap();
+            fs::write(dir.join("A B.txt"), "first").unwrap();
+            fs::write(dir.join("a b.txt"), "second").unwrap();
+            let options = NotoxArgs {
+                lowercase: true,
+                clobber_policy,
+                ..quiet_options(false)
+            };
+            let res = notox::notox(&options, &HashSet::from([dir.clone()]));
+            assert!(
+                res.contains(&PathChange::ErrorRename {
+                    path: dir.join("a b.txt"),
+                    modified: dir.join("a_b.txt"),
+                    error: "name already taken".to_string(),
+                }),
+                "{:?}",
+                res
+            );
+            assert_eq!(fs::read_to_string(dir.join("a_b.txt")).unwrap(), "first");
+            assert_eq!(fs::read_to_string(dir.join("a b.txt")).unwrap(), "second");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_no_case_collision() {
+        let dir = PathBuf::from("test_no_case_collision");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("Cafe.txt"), "").unwrap();
+        fs::write(dir.join("café au lait.txt"), "").unwrap();
+        let res = notox::notox(&quiet_options(true), &HashSet::from([dir.clone()]));
+        assert!(
+            res.iter().all(|one_change| !one_change.is_error()),
+            "{:?}",
+            res
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+mod collect_errors {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{CategoryTally, Notox, PathChange, Summary};
+
+    use crate::common::quiet_options;
+
+    #[test]
+    fn test_collect_errors_all_success() {
+        let source = PathBuf::from("test collect ok.txt");
+        let target = PathBuf::from("test_collect_ok.txt");
+        std::fs::File::create(&source).unwrap();
+
+        let summary = Notox::new(&quiet_options(false))
+            .run_and_collect_errors(&HashSet::from([
+                source.clone(),
+                PathBuf::from("Cargo.toml"),
+            ]))
+            .unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                checked: 2,
+                changed: 1,
+                unchanged: 1,
+                errors: 0,
+                would_change: 0,
+                would_fail: 0,
+                trashed: 0,
+                categories: CategoryTally {
+                    whitespace: 1,
+                    ..Default::default()
+                },
+            }
+        );
+        assert!(target.exists());
+
+        std::fs::remove_file(&target).unwrap();
+    }
+
+    #[test]
+    fn test_collect_errors_dry_run_is_not_an_error() {
+        let summary = Notox::new(&quiet_options(true))
+            .run_and_collect_errors(&HashSet::from([PathBuf::from("test collect dry.txt")]))
+            .unwrap();
+        assert_eq!(summary.would_change, 1);
+        assert_eq!(summary.errors, 0);
+    }
+
+    #[test]
+    fn test_collect_errors_mixed() {
+        let source = PathBuf::from("test collect mixed.txt");
+        let target = PathBuf::from("test_collect_mixed.txt");
+        let missing = PathBuf::from("test collect missing.txt");
+        std::fs::File::create(&source).unwrap();
+
+        let errors = Notox::new(&quiet_options(false))
+            .run_and_collect_errors(&HashSet::from([source.clone(), missing.clone()]))
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            PathChange::ErrorRename { path, .. } => assert_eq!(path, &missing),
+            _ => panic!("Unexpected result {:?}", errors[0]),
+        }
+        // the other renames are still applied
+        assert!(target.exists());
+
+        std::fs::remove_file(&target).unwrap();
+    }
+}
+
+mod decision {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{Decision, Notox, PathChange};
+
+    use crate::common::{create_tree, quiet_options};
+
+    const FILES: &[&str] = &["a b.txt", "c d.txt", "e f/g h.txt"];
+
+    #[test]
+    fn test_run_with_decision_skip() {
+        let dir = PathBuf::from("test_decision_skip");
+        create_tree(&dir, FILES);
+
+        let mut asked = 0;
+        let res = Notox::new(&quiet_options(false)).run_with_decision(
+            &HashSet::from([dir.clone()]),
+            |one_change| {
+                asked += 1;
+                match one_change {
+                    PathChange::WouldChange { path, .. } if path.ends_with("a b.txt") => {
+                        Decision::Skip
+                    }
+                    _ => Decision::Apply,
+                }
+            },
+        );
+        assert_eq!(asked, 4);
+        assert_eq!(res.len(), 5);
+        assert!(res.contains(&PathChange::Unchanged {
+            path: dir.join("a b.txt")
+        }));
+        assert!(res.contains(&PathChange::Changed {
+            path: dir.join("e f").join("g h.txt"),
+            modified: dir.join("e f").join("g_h.txt"),
+        }));
+        assert!(dir.join("a b.txt").exists());
+        assert!(dir.join("c_d.txt").exists());
+        assert!(dir.join("e_f").join("g_h.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_with_decision_abort() {
+        let dir = PathBuf::from("test_decision_abort");
+        create_tree(&dir, FILES);
+
+        let mut asked = 0;
+        let res = Notox::new(&quiet_options(false)).run_with_decision(
+            &HashSet::from([dir.clone()]),
+            |_| {
+                asked += 1;
+                Decision::Abort
+            },
+        );
+        assert_eq!(asked, 1);
+        assert!(res
+            .iter()
+            .all(|one_change| matches!(one_change, PathChange::Unchanged { .. })));
+        assert!(dir.join("a b.txt").exists());
+        assert!(dir.join("e f").join("g h.txt").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+mod deep_tree {
+    use std::{collections::HashSet, fs, path::PathBuf};
+
+    use notox::PathChange;
+
+    use crate::common::quiet_options;
+
+    /// Number of nested directories, the paths stay under the 4096 bytes of `PATH_MAX`
+    const DEPTH: usize = 2000;
+
+    #[test]
+    fn test_deep_tree() {
+        let dir = PathBuf::from("test_deep_tree");
+        let mut deepest = dir.clone();
+        fs::create_dir(&deepest).unwrap();
+        for _ in 0..DEPTH {
+            deepest.push("a");
+            fs::create_dir(&deepest).unwrap();
+        }
+        fs::write(deepest.join("my file.txt"), "").unwrap();
+        let options = quiet_options(false);
+        let res = notox::notox(&options, &HashSet::from([dir.clone()]));
+        // the directories and the file
+        assert_eq!(res.len(), DEPTH + 2);
+        assert_eq!(
+            res.last(),
+            Some(&PathChange::Changed {
+                path: deepest.join("my file.txt"),
+                modified: deepest.join("my_file.txt"),
+            })
+        );
+        assert!(deepest.join("my_file.txt").exists());
+        // `remove_dir_all` keeps a descriptor open per level
+        fs::remove_file(deepest.join("my_file.txt")).unwrap();
+        while deepest != dir {
+            fs::remove_dir(&deepest).unwrap();
+            deepest.pop();
+        }
+        fs::remove_dir(&dir).unwrap();
+    }
+}
+
+mod depth {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{Notox, NotoxArgs, PathChange, STREAM_BUFFER};
+
+    use crate::common::quiet_options;
+
+    #[test]
+    fn test_run_with_depth() {
+        let dir = PathBuf::from("test_depth");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        let nested = dir.join("first dir").join("second_dir");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::File::create(dir.join("top.txt")).unwrap();
+        std::fs::File::create(nested.join("deep file.txt")).unwrap();
+        let single = PathBuf::from("test depth single.txt");
+        std::fs::File::create(&single).unwrap();
+
+        let options = quiet_options(true);
+        let notox_inst = Notox::new(&options);
+        let res: Vec<(usize, PathChange)> = notox_inst
+            .run_with_depth(&HashSet::from([dir.clone(), single.clone()]))
+            .collect();
+        let mut depths: Vec<(usize, PathBuf)> = res
+            .iter()
+            .map(|(depth, one_change)| (*depth, one_change.path().to_path_buf()))
+            .collect();
+        depths.sort();
+        assert_eq!(
+            depths,
+            vec![
+                (0, single.clone()),
+                (0, dir.clone()),
+                (1, dir.join("first dir")),
+                (1, dir.join("top.txt")),
+                (2, nested.clone()),
+                (3, nested.join("deep file.txt")),
+            ]
+        );
+        // a directory comes before its entries
+        let position = |path: &PathBuf| {
+            res.iter()
+                .position(|(_, one_change)| one_change.path() == path)
+                .unwrap()
+        };
+        assert!(position(&dir) < position(&dir.join("first dir")));
+        assert!(position(&dir.join("first dir")) < position(&nested));
+        assert!(position(&nested) < position(&nested.join("deep file.txt")));
+        // same results without the depth
+        let without_depth: Vec<PathChange> =
+            res.into_iter().map(|(_, one_change)| one_change).collect();
+        let mut run_res = notox_inst.run(&HashSet::from([dir.clone(), single.clone()]));
+        let mut without_depth_sorted = without_depth.clone();
+        run_res.sort_by(|a, b| a.path().cmp(b.path()));
+        without_depth_sorted.sort_by(|a, b| a.path().cmp(b.path()));
+        assert_eq!(run_res, without_depth_sorted);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&single).unwrap();
+    }
+
+    #[test]
+    fn test_run_with_depth_dropped() {
+        let dir = PathBuf::from("test_depth_dropped");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        let total = 3 * STREAM_BUFFER;
+        for idx in 0..total {
+            std::fs::File::create(dir.join(format!("file {}.txt", idx))).unwrap();
+        }
+
+        let options = NotoxArgs {
+            threads: Some(2),
+            ..quiet_options(false)
+        };
+        let mut iter = Notox::new(&options).run_with_depth(&HashSet::from([dir.clone()]));
+        assert!(iter.next().is_some());
+        drop(iter);
+        // the walk is stopped once the buffer and the entries already started are done
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        let renamed = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter(|entry| {
+                !entry
+                    .as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .contains(' ')
+            })
+            .count();
+        assert!(renamed <= STREAM_BUFFER + 4, "{} renamed", renamed);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+mod exclude {
+    use std::{
+        collections::HashSet,
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use notox::{is_excluded, parse_args, ExitCode, NotoxArgs, PathChange};
+
+    use crate::common::quiet_options;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|pattern| pattern.to_string()).collect()
+    }
+
+    #[test]
+    fn test_is_excluded() {
+        let exclude = patterns(&["node_modules", ".git", "*.lock", "docs/*.md"]);
+        let excluded = [
+            "node_modules",
+            "project/node_modules",
+            "project/.git",
+            "Cargo.lock",
+            "project/yarn.lock",
+            "project/docs/read me.md",
+        ];
+        for path in excluded {
+            assert!(is_excluded(Path::new(path), &exclude), "{}", path);
+        }
+        let kept = [
+            "project/my node_modules",
+            "project/.gitignore",
+            "lock file.txt",
+            "project/read me.md",
+            "project/docs/sub/read me.md",
+        ];
+        for path in kept {
+            assert!(!is_excluded(Path::new(path), &exclude), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_exclude_skips_entries() {
+        let dir = PathBuf::from("test_exclude_skips_entries");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("node_modules").join("some package")).unwrap();
+        fs::write(dir.join("yarn lock.lock"), "").unwrap();
+        fs::write(dir.join("my file.txt"), "").unwrap();
+        let options = NotoxArgs {
+            exclude: patterns(&["node_modules", "*.lock"]),
+            ..quiet_options(true)
+        };
+        let mut res = notox::notox(&options, &HashSet::from([dir.clone()]));
+        res.sort_by(|a, b| a.path().cmp(b.path()));
+        assert_eq!(
+            res,
+            vec![
+                PathChange::Unchanged { path: dir.clone() },
+                PathChange::WouldChange {
+                    path: dir.join("my file.txt"),
+                    modified: dir.join("my_file.txt"),
+                },
+                PathChange::Skipped {
+                    path: dir.join("node_modules"),
+                    reason: "excluded".to_string(),
+                },
+                PathChange::Skipped {
+                    path: dir.join("yarn lock.lock"),
+                    reason: "excluded".to_string(),
+                },
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_exclude() {
+        let vec_args = [
+            "notox".to_string(),
+            "--exclude".to_string(),
+            "node_modules".to_string(),
+            "--exclude".to_string(),
+            "*.lock".to_string(),
+            ".".to_string(),
+        ];
+        let res = parse_args(&vec_args).unwrap();
+        assert_eq!(res.0.exclude, patterns(&["node_modules", "*.lock"]));
+        let vec_args = ["notox".to_string(), "--exclude".to_string()];
+        assert_eq!(parse_args(&vec_args).err(), Some(ExitCode::Usage));
+    }
+}
+
+mod glob {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{parse_args, ExitCode, Notox, PathChange};
+
+    #[test]
+    fn test_glob_roots() {
+        let root = PathBuf::from("test_glob");
+        for package in ["pkg a", "pkg_b", "pkg c"] {
+            std::fs::create_dir_all(root.join("packages").join(package)).unwrap();
+        }
+        std::fs::create_dir_all(root.join("packages/pkg a/src/sub dir")).unwrap();
+        std::fs::create_dir_all(root.join("packages/pkg_b/src")).unwrap();
+        std::fs::create_dir_all(root.join("packages/.hidden/src")).unwrap();
+        std::fs::File::create(root.join("packages/pkg a/src/a b.rs")).unwrap();
+        std::fs::File::create(root.join("packages/pkg a/src/sub dir/c d.rs")).unwrap();
+        std::fs::File::create(root.join("packages/pkg_b/src/e f.rs")).unwrap();
+        std::fs::File::create(root.join("packages/.hidden/src/g h.rs")).unwrap();
+        std::fs::File::create(root.join("packages/pkg c/not src.rs")).unwrap();
+
+        let (notox_args, paths) = parse_args(&[
+            "notox".to_string(),
+            "-q".to_string(),
+            "-d".to_string(),
+            "test_glob/packages/*/src".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            paths,
+            HashSet::from([
+                root.join("packages/pkg a/src/a b.rs"),
+                root.join("packages/pkg a/src/sub dir"),
+                root.join("packages/pkg_b/src/e f.rs"),
+            ])
+        );
+
+        let res = Notox::new(&notox_args).run(&paths);
+        assert_eq!(res.len(), 4);
+        assert!(res
+            .iter()
+            .all(|one_change| matches!(one_change, PathChange::Changed { .. })));
+        // the matching directories and the components of their paths are not renamed
+        assert!(root.join("packages/pkg a/src/a_b.rs").exists());
+        assert!(root.join("packages/pkg a/src/sub_dir/c_d.rs").exists());
+        assert!(root.join("packages/pkg_b/src/e_f.rs").exists());
+        assert!(root.join("packages/.hidden/src/g h.rs").exists());
+        assert!(root.join("packages/pkg c/not src.rs").exists());
+
+        // the wildcards match part of a name
+        let (_, paths) = parse_args(&[
+            "notox".to_string(),
+            "test_glob/packages/pkg?b/sr*".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            paths,
+            HashSet::from([root.join("packages/pkg_b/src/e_f.rs")])
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_glob_without_match() {
+        let root = PathBuf::from("test_glob_without_match");
+        std::fs::create_dir_all(root.join("packages/pkg a/src")).unwrap();
+        // the current directory is not cleaned instead
+        assert_eq!(
+            parse_args(&[
+                "notox".to_string(),
+                "-d".to_string(),
+                "test_glob_without_match/packages/*/lib".to_string(),
+            ])
+            .err(),
+            Some(ExitCode::Usage)
+        );
+        let (_, paths) = parse_args(&[
+            "notox".to_string(),
+            "test_glob_without_match/packages/*/src".to_string(),
+        ])
+        .unwrap();
+        assert!(paths.is_empty(), "{:?}", paths);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
+
+mod hidden {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use notox::{parse_args, NotoxArgs, PathChange};
+
+    use crate::common::{create_tree, quiet_options, run_dir};
+
+    fn run(dir: &Path, clean_hidden: bool) -> Vec<PathChange> {
+        let options = NotoxArgs {
+            clean_hidden,
+            ..quiet_options(false)
+        };
+        run_dir(dir, &options)
+    }
+
+    const FILES: &[&str] = &[".config/my app/settings file.toml", "my file.txt"];
+
+    #[test]
+    fn test_hidden_cleaned_by_default() {
+        let dir = PathBuf::from("test_hidden_cleaned_by_default");
+        create_tree(&dir, FILES);
+        run(&dir, true);
+        assert!(dir
+            .join(".config")
+            .join("my_app")
+            .join("settings_file.toml")
+            .exists());
+        assert!(dir.join("my_file.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_hidden_skipped() {
+        let dir = PathBuf::from("test_hidden_skipped");
+        create_tree(&dir, FILES);
+        let res = run(&dir, false);
+        assert!(res.contains(&PathChange::Skipped {
+            path: dir.join(".config"),
+            reason: "hidden".to_string(),
+        }));
+        assert!(dir
+            .join(".config")
+            .join("my app")
+            .join("settings file.toml")
+            .exists());
+        assert!(dir.join("my_file.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_hidden_given_path_cleaned() {
+        let dir = PathBuf::from("test_hidden_given_path_cleaned");
+        create_tree(&dir, FILES);
+        let config = dir.join(".config");
+        run(&config, false);
+        assert!(config.join("my_app").join("settings_file.toml").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_hidden() {
+        assert!(
+            parse_args(&["notox".to_string(), ".".to_string()])
+                .unwrap()
+                .0
+                .clean_hidden
+        );
+        assert!(
+            !parse_args(&["notox".to_string(), "--skip-hidden".to_string()])
+                .unwrap()
+                .0
+                .clean_hidden
+        );
+        assert!(
+            parse_args(&[
+                "notox".to_string(),
+                "--skip-hidden".to_string(),
+                "--include-hidden".to_string()
+            ])
+            .unwrap()
+            .0
+            .clean_hidden
+        );
+    }
+}
+
+mod journal {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{read_journal, ExitCode, PathChange};
+
+    fn resume_args(journal: &str, paths: &[&PathBuf]) -> Vec<String> {
+        let mut vec_args = vec![
+            "notox".to_string(),
+            "-d".to_string(),
+            "-q".to_string(),
+            "--resume".to_string(),
+            journal.to_string(),
+        ];
+        vec_args.extend(paths.iter().map(|path| path.to_string_lossy().to_string()));
+        vec_args
+    }
+
+    #[test]
+    fn test_resume_after_interruption() {
+        let journal = PathBuf::from("test_journal.bin");
+        let first = PathBuf::from("test journal first.txt");
+        let second = PathBuf::from("test journal second.txt");
+        let _ = std::fs::remove_file(&journal);
+        std::fs::File::create(&first).unwrap();
+        std::fs::File::create(&second).unwrap();
+
+        // interrupted run, only the first file was renamed
+        let (options, paths) =
+            notox::parse_args(&resume_args("test_journal.bin", &[&first])).unwrap();
+        assert!(options.journal_done.is_empty());
+        let res = notox::notox(&options, &paths);
+        assert_eq!(
+            res,
+            vec![PathChange::Changed {
+                path: first.clone(),
+                modified: PathBuf::from("test_journal_first.txt"),
+            }]
+        );
+        assert_eq!(
+            read_journal(&journal).unwrap(),
+            HashSet::from([first.clone()])
+        );
+
+        // resumed run, the first file is not renamed again
+        std::fs::File::create(&first).unwrap();
+        let (options, paths) =
+            notox::parse_args(&resume_args("test_journal.bin", &[&first, &second])).unwrap();
+        assert_eq!(options.journal_done, HashSet::from([first.clone()]));
+        let res = notox::notox(&options, &paths);
+        assert_eq!(
+            res,
+            vec![PathChange::Changed {
+                path: second.clone(),
+                modified: PathBuf::from("test_journal_second.txt"),
+            }]
+        );
+        assert!(first.exists());
+        assert_eq!(
+            read_journal(&journal).unwrap(),
+            HashSet::from([first.clone(), second.clone()])
+        );
+
+        std::fs::remove_file(&first).unwrap();
+        std::fs::remove_file("test_journal_first.txt").unwrap();
+        std::fs::remove_file("test_journal_second.txt").unwrap();
+        std::fs::remove_file(&journal).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_resume() {
+        let vec_args = ["notox".to_string(), "--resume".to_string()];
+        assert_eq!(notox::parse_args(&vec_args).err().unwrap(), ExitCode::Usage);
+    }
+}
+
+mod max_depth {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use notox::{parse_args, ExitCode, NotoxArgs, PathChange};
+
+    use crate::common::{create_tree, quiet_options, run_dir};
+
+    fn run(dir: &Path, max_depth: usize) -> Vec<PathChange> {
+        let options = NotoxArgs {
+            max_depth: Some(max_depth),
+            ..quiet_options(false)
+        };
+        run_dir(dir, &options)
+    }
+
+    const FILES: &[&str] = &["level 1/file 2.txt", "level 1/level 2/file 3.txt"];
+
+    #[test]
+    fn test_max_depth_zero() {
+        let dir = PathBuf::from("test max depth zero");
+        create_tree(&dir, FILES);
+        let res = run(&dir, 0);
+        let cleaned = PathBuf::from("test_max_depth_zero");
+        assert_eq!(
+            res,
+            vec![PathChange::Changed {
+                path: dir.clone(),
+                modified: cleaned.clone(),
+            }]
+        );
+        assert!(cleaned.join("level 1").exists());
+        fs::remove_dir_all(&cleaned).unwrap();
+    }
+
+    #[test]
+    fn test_max_depth_one() {
+        let dir = PathBuf::from("test_max_depth_one");
+        create_tree(&dir, FILES);
+        let res = run(&dir, 1);
+        // the directory at the maximum depth is renamed, not its entries
+        assert_eq!(res.len(), 2, "{:?}", res);
+        assert!(res.contains(&PathChange::Changed {
+            path: dir.join("level 1"),
+            modified: dir.join("level_1"),
+        }));
+        assert!(dir.join("level_1").join("file 2.txt").exists());
+        assert!(dir.join("level_1").join("level 2").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_depth_two() {
+        let dir = PathBuf::from("test_max_depth_two");
+        create_tree(&dir, FILES);
+        let res = run(&dir, 2);
+        assert_eq!(res.len(), 4, "{:?}", res);
+        assert!(dir.join("level_1").join("file_2.txt").exists());
+        assert!(dir
+            .join("level_1")
+            .join("level_2")
+            .join("file 3.txt")
+            .exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_max_depth() {
+        let vec_args = [
+            "notox".to_string(),
+            "--max-depth".to_string(),
+            "0".to_string(),
+            ".".to_string(),
+        ];
+        assert_eq!(parse_args(&vec_args).unwrap().0.max_depth, Some(0));
+        let vec_args = [
+            "notox".to_string(),
+            "--max-depth".to_string(),
+            "-1".to_string(),
+        ];
+        assert_eq!(parse_args(&vec_args).err(), Some(ExitCode::Usage));
+    }
+}
+
+mod max_name_bytes {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{parse_args, ExitCode, Notox, NotoxArgs, PathChange};
+
+    use crate::common::quiet_options;
+
+    fn options(dry_run: bool) -> NotoxArgs {
+        NotoxArgs {
+            reject_over_len: Some(10),
+            ..quiet_options(dry_run)
+        }
+    }
+
+    #[test]
+    fn test_reject_name_just_over_limit() {
+        // "test_max1.t" is 11 bytes
+        let source = PathBuf::from("test max1.t");
+        let target = PathBuf::from("test_max1.t");
+        std::fs::File::create(&source).unwrap();
+
+        let res = Notox::new(&options(true)).run(&HashSet::from([source.clone()]));
+        let reason = "name too long (11 bytes, max 10)".to_string();
+        assert_eq!(
+            res,
+            vec![PathChange::WouldFail {
+                path: source.clone(),
+                modified: target.clone(),
+                reason: reason.clone(),
+            }]
+        );
+
+        let res = Notox::new(&options(false)).run(&HashSet::from([source.clone()]));
+        assert_eq!(
+            res,
+            vec![PathChange::ErrorRename {
+                path: source.clone(),
+                modified: target.clone(),
+                error: reason,
+            }]
+        );
+        // the file is left untouched
+        assert!(source.exists());
+        assert!(!target.exists());
+
+        std::fs::remove_file(&source).unwrap();
+    }
+
+    #[test]
+    fn test_name_at_limit_is_renamed() {
+        let source = PathBuf::from("tmax 2.txt");
+        let target = PathBuf::from("tmax_2.txt");
+        std::fs::File::create(&source).unwrap();
+
+        let res = Notox::new(&options(false)).run(&HashSet::from([source.clone()]));
+        assert_eq!(
+            res,
+            vec![PathChange::Changed {
+                path: source.clone(),
+                modified: target.clone(),
+            }]
+        );
+
+        std::fs::remove_file(&target).unwrap();
+    }
+
+    #[test]
+    fn test_max_name_bytes_args() {
+        let args = |value: &str| {
+            vec![
+                "notox".to_string(),
+                "--max-name-bytes".to_string(),
+                value.to_string(),
+                "Cargo.toml".to_string(),
+            ]
+        };
+        let (notox_args, _) = parse_args(&args("100")).unwrap();
+        assert_eq!(notox_args.reject_over_len, Some(100));
+        assert_eq!(parse_args(&args("many")), Err(ExitCode::Usage));
+    }
+}
+
+mod max_renames {
+    use std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+    };
+
+    use notox::{parse_args, ExitCode, Notox, NotoxArgs, PathChange, Summary};
+
+    use crate::common::quiet_options;
+
+    fn run_with_budget(dir: &Path, dry_run: bool, max_renames: usize) -> Vec<PathChange> {
+        let options = NotoxArgs {
+            rename_budget: Some(max_renames),
+            no_rename_dirs: vec![dir.as_os_str().to_os_string()],
+            ..quiet_options(dry_run)
+        };
+        Notox::new(&options).run(&HashSet::from([dir.to_path_buf()]))
+    }
+
+    fn create_files(dir: &Path) {
+        std::fs::create_dir_all(dir).unwrap();
+        for idx in 0..5 {
+            std::fs::File::create(dir.join(format!("file {}.txt", idx))).unwrap();
+        }
+        std::fs::File::create(dir.join("clean.txt")).unwrap();
+    }
+
+    #[test]
+    fn test_max_renames() {
+        let dir = PathBuf::from("test_max_renames");
+        create_files(&dir);
+
+        let res = run_with_budget(&dir, false, 2);
+        let summary = Summary::from_results(&res);
+        assert_eq!(summary.changed, 2);
+        // the directory, clean.txt and the 3 skipped files
+        assert_eq!(summary.unchanged, 5);
+        let skipped = res
+            .iter()
+            .filter(|one_change| {
+                matches!(one_change, PathChange::Skipped { reason, .. } if reason == "rename limit reached")
+            })
+            .count();
+        assert_eq!(skipped, 3);
+        let renamed = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with("file_")
+            })
+            .count();
+        assert_eq!(renamed, 2);
+
+        // each run has its own budget
+        let res = run_with_budget(&dir, false, 2);
+        assert_eq!(Summary::from_results(&res).changed, 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_renames_dry_run() {
+        let dir = PathBuf::from("test_max_renames_dry_run");
+        create_files(&dir);
+
+        let res = run_with_budget(&dir, true, 4);
+        let summary = Summary::from_results(&res);
+        assert_eq!(summary.would_change, 4);
+        assert_eq!(summary.unchanged, 3);
+        assert!(dir.join("file 0.txt").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_renames_args() {
+        let args = |value: &str| {
+            vec![
+                "notox".to_string(),
+                "--max-renames".to_string(),
+                value.to_string(),
+                "Cargo.toml".to_string(),
+            ]
+        };
+        let (notox_args, _) = parse_args(&args("10")).unwrap();
+        assert_eq!(notox_args.rename_budget, Some(10));
+        assert_eq!(parse_args(&args("-1")), Err(ExitCode::Usage));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_skipped_serde() {
+        let skipped = PathChange::Skipped {
+            path: PathBuf::from("a b"),
+            reason: "rename limit reached".to_string(),
+        };
+        let json = serde_json::to_string(&skipped).unwrap();
+        assert_eq!(
+            json,
+            r#"{"path":"a b","modified":null,"error":null,"status":"skipped","reason":"rename limit reached"}"#
+        );
+        assert_eq!(serde_json::from_str::<PathChange>(&json).unwrap(), skipped);
+    }
+}
+
+mod nested_inputs {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{Notox, NotoxArgs, PathChange};
+
+    use crate::common::quiet_options;
+
+    #[test]
+    fn test_parent_and_children_inputs() {
+        let options = quiet_options(false);
+        for round in 0..5 {
+            let parent = PathBuf::from(format!("test nested parent {}", round));
+            let cleaned_parent = PathBuf::from(format!("test_nested_parent_{}", round));
+            std::fs::create_dir(&parent).unwrap();
+            let mut paths_to_check = HashSet::from([parent.clone()]);
+            for idx in 0..20 {
+                let child = parent.join(format!("child {}.txt", idx));
+                std::fs::File::create(&child).unwrap();
+                paths_to_check.insert(child);
+            }
+            let sub_dir = parent.join("sub dir");
+            std::fs::create_dir(&sub_dir).unwrap();
+            std::fs::File::create(sub_dir.join("grand child.txt")).unwrap();
+            paths_to_check.insert(sub_dir.clone());
+            paths_to_check.insert(sub_dir.join("grand child.txt"));
+
+            let res = Notox::new(&options).run(&paths_to_check);
+            assert_eq!(res.len(), 23);
+            assert!(res
+                .iter()
+                .all(|one_change| matches!(one_change, PathChange::Changed { .. })));
+            for idx in 0..20 {
+                assert!(cleaned_parent.join(format!("child_{}.txt", idx)).exists());
+            }
+            assert!(cleaned_parent
+                .join("sub_dir")
+                .join("grand_child.txt")
+                .exists());
+            std::fs::remove_dir_all(&cleaned_parent).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_nested_inputs_mixed_spellings() {
+        let parent = PathBuf::from("test nested spellings");
+        let cleaned_parent = PathBuf::from("test_nested_spellings");
+        let _ = std::fs::remove_dir_all(&parent);
+        let _ = std::fs::remove_dir_all(&cleaned_parent);
+        std::fs::create_dir_all(parent.join("inner")).unwrap();
+        for name in ["child a.txt", "child b.txt", "child c.txt"] {
+            std::fs::File::create(parent.join(name)).unwrap();
+        }
+        let paths_to_check = HashSet::from([
+            PathBuf::from(".").join(&parent),
+            parent.join("child a.txt"),
+            std::path::absolute(parent.join("child b.txt")).unwrap(),
+            parent.join("inner").join("..").join("child c.txt"),
+        ]);
+
+        let res = Notox::new(&quiet_options(false)).run(&paths_to_check);
+        // the parent, its three children and the unchanged `inner`
+        assert_eq!(res.len(), 5, "{:?}", res);
+        assert!(
+            res.iter().all(|one_change| matches!(
+                one_change,
+                PathChange::Changed { .. } | PathChange::Unchanged { .. }
+            )),
+            "{:?}",
+            res
+        );
+        for name in ["child_a.txt", "child_b.txt", "child_c.txt"] {
+            assert!(cleaned_parent.join(name).exists());
+        }
+        std::fs::remove_dir_all(&cleaned_parent).unwrap();
+    }
+
+    #[test]
+    fn test_nested_inputs_not_reached() {
+        let root = PathBuf::from("test_nested_not_reached");
+        let _ = std::fs::remove_dir_all(&root);
+        let options = |max_depth, exclude: &[&str], clean_hidden| NotoxArgs {
+            max_depth,
+            exclude: exclude.iter().map(|pattern| pattern.to_string()).collect(),
+            clean_hidden,
+            ..quiet_options(false)
+        };
+        for (options, inner_dir) in [
+            (options(Some(0), &[], true), "s"),
+            (options(None, &["s"], true), "s"),
+            (options(None, &[], false), ".h"),
+        ] {
+            // the parent is renamed after the explicit input
+            let parent = root.join("d d");
+            std::fs::create_dir_all(parent.join(inner_dir)).unwrap();
+            let file_path = parent.join(inner_dir).join("x y");
+            std::fs::File::create(&file_path).unwrap();
+            let res = Notox::new(&options).run(&HashSet::from([parent.clone(), file_path.clone()]));
+            assert!(
+                res.contains(&PathChange::Changed {
+                    path: file_path.clone(),
+                    modified: parent.join(inner_dir).join("x_y"),
+                }),
+                "{:?}",
+                res
+            );
+            assert!(root.join("d_d").join(inner_dir).join("x_y").exists());
+            std::fs::remove_dir_all(&root).unwrap();
+        }
+
+        // the explicit input reached by the walk is cleaned once
+        let parent = root.join("d");
+        std::fs::create_dir_all(parent.join("s")).unwrap();
+        let file_path = parent.join("s").join("x y");
+        std::fs::File::create(&file_path).unwrap();
+        let res = Notox::new(&options(Some(2), &[], true))
+            .run(&HashSet::from([parent.clone(), file_path.clone()]));
+        assert_eq!(res.len(), 3, "{:?}", res);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
+
+mod on_collision {
+    use std::{
+        collections::HashSet,
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use notox::{parse_args, ClobberPolicy, CollisionPolicy, ExitCode, NotoxArgs, PathChange};
+
+    use crate::common::{names_in, quiet_options, run_dir};
+
+    fn create_dir(dir: &Path, names: &[&str]) {
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir(dir).unwrap();
+        for name in names {
+            fs::write(dir.join(name), name).unwrap();
+        }
+    }
+
+    fn run(dir: &Path, dry_run: bool, policy: CollisionPolicy) -> Vec<PathChange> {
+        let options = NotoxArgs {
+            on_collision: Some(policy),
+            ..quiet_options(dry_run)
+        };
+        run_dir(dir, &options)
+    }
+
+    #[test]
+    fn test_on_collision_suffix() {
+        let dir = PathBuf::from("test_on_collision_suffix");
+        create_dir(&dir, &["café.txt", "cafe.txt", "cafè.txt", "cafe_1.txt"]);
+        let res = run(&dir, false, CollisionPolicy::Suffix);
+        assert!(
+            res.iter().all(|one_change| !one_change.is_error()),
+            "{:?}",
+            res
+        );
+        assert_eq!(
+            names_in(&dir),
+            HashSet::from([
+                "cafe.txt".to_string(),
+                "cafe_1.txt".to_string(),
+                "cafe_2.txt".to_string(),
+                "cafe_3.txt".to_string(),
+            ])
+        );
+        // the suffixes follow the order of the original names, è (U+00E8) is before é (U+00E9)
+        assert_eq!(
+            fs::read_to_string(dir.join("cafe.txt")).unwrap(),
+            "cafe.txt"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("cafe_1.txt")).unwrap(),
+            "cafe_1.txt"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("cafe_2.txt")).unwrap(),
+            "cafè.txt"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("cafe_3.txt")).unwrap(),
+            "café.txt"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_on_collision_suffix_max_length() {
+        let dir = PathBuf::from("test_on_collision_suffix_max_length");
+        // both names are truncated to the same name, longer than the one of the directory
+        create_dir(
+            &dir,
+            &[
+                "my long file name for the report one.txt",
+                "my long file name for the report two.txt",
+            ],
+        );
+        let options = NotoxArgs {
+            on_collision: Some(CollisionPolicy::Suffix),
+            max_length: Some(36),
+            ..quiet_options(false)
+        };
+        let res = notox::notox(&options, &HashSet::from([dir.clone()]));
+        assert!(
+            res.iter().all(|one_change| !one_change.is_error()),
+            "{:?}",
+            res
+        );
+        // the suffix is kept, the stem is truncated again
+        assert_eq!(
+            names_in(&dir),
+            HashSet::from([
+                "my_long_file_name_for_the_report.txt".to_string(),
+                "my_long_file_name_for_the_repo_1.txt".to_string(),
+            ])
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_on_collision_suffix_stable() {
+        let dir = PathBuf::from("test_on_collision_suffix_stable");
+        let names = [
+            "b é.txt", "b e.txt", "b è.txt", "b_e.txt", "noext é", "noext è",
+        ];
+        let mut first_plan = None;
+        for _ in 0..5 {
+            create_dir(&dir, &names);
+            let mut plan: Vec<(PathBuf, PathBuf)> = run(&dir, true, CollisionPolicy::Suffix)
+                .into_iter()
+                .filter_map(|one_change| match one_change {
+                    PathChange::WouldChange { path, modified } => Some((path, modified)),
+                    _ => None,
+                })
+                .collect();
+            plan.sort();
+            assert_eq!(plan.len(), 5, "{:?}", plan);
+            match &first_plan {
+                None => first_plan = Some(plan),
+                Some(first_plan) => assert_eq!(first_plan, &plan),
+            }
+        }
+        let first_plan = first_plan.unwrap();
+        assert!(first_plan.contains(&(dir.join("noext è"), dir.join("noext_e"))));
+        assert!(first_plan.contains(&(dir.join("noext é"), dir.join("noext_e_1"))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_on_collision_error() {
+        let dir = PathBuf::from("test_on_collision_error");
+        create_dir(&dir, &["café.txt", "cafe.txt", "thé.txt"]);
+        let res = run(&dir, true, CollisionPolicy::Error);
+        assert!(res.contains(&PathChange::WouldFail {
+            path: dir.join("café.txt"),
+            modified: dir.join("cafe.txt"),
+            reason: "name already taken".to_string(),
+        }));
+        let res = run(&dir, false, CollisionPolicy::Error);
+        assert!(res.contains(&PathChange::ErrorRename {
+            path: dir.join("café.txt"),
+            modified: dir.join("cafe.txt"),
+            error: "name already taken".to_string(),
+        }));
+        assert!(res.contains(&PathChange::Changed {
+            path: dir.join("thé.txt"),
+            modified: dir.join("the.txt"),
+        }));
+        assert_eq!(
+            fs::read_to_string(dir.join("cafe.txt")).unwrap(),
+            "cafe.txt"
+        );
+        assert!(dir.join("café.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_on_collision_skip() {
+        let dir = PathBuf::from("test_on_collision_skip");
+        create_dir(&dir, &["café.txt", "cafè.txt"]);
+        let res = run(&dir, false, CollisionPolicy::Skip);
+        assert!(res.contains(&PathChange::Changed {
+            path: dir.join("cafè.txt"),
+            modified: dir.join("cafe.txt"),
+        }));
+        assert!(res.contains(&PathChange::Skipped {
+            path: dir.join("café.txt"),
+            reason: "name already taken".to_string(),
+        }));
+        assert!(res.iter().all(|one_change| !one_change.is_error()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_on_collision_single_file() {
+        let dir = PathBuf::from("test_on_collision_single_file");
+        create_dir(&dir, &["café.txt", "cafe.txt"]);
+        let res = run(&dir.join("café.txt"), false, CollisionPolicy::Suffix);
+        assert_eq!(
+            res,
+            vec![PathChange::Changed {
+                path: dir.join("café.txt"),
+                modified: dir.join("cafe_1.txt"),
+            }]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_no_policy_keeps_existing_target() {
+        let dir = PathBuf::from("test_no_policy_keeps_existing_target");
+        create_dir(&dir, &["x y.txt", "x_y.txt"]);
+        let options = quiet_options(false);
+        for path in [dir.clone(), dir.join("x y.txt")] {
+            let res = notox::notox(&options, &HashSet::from([path]));
+            assert!(
+                res.contains(&PathChange::ErrorRename {
+                    path: dir.join("x y.txt"),
+                    modified: dir.join("x_y.txt"),
+                    error: "target already exists".to_string(),
+                }),
+                "{:?}",
+                res
+            );
+            assert_eq!(fs::read_to_string(dir.join("x_y.txt")).unwrap(), "x_y.txt");
+            assert_eq!(fs::read_to_string(dir.join("x y.txt")).unwrap(), "x y.txt");
+        }
+
+        // overwriting is only done when asked
+        let options = NotoxArgs {
+            clobber_policy: ClobberPolicy::Overwrite,
+            ..quiet_options(false)
+        };
+        let res = notox::notox(&options, &HashSet::from([dir.join("x y.txt")]));
+        assert_eq!(
+            res,
+            vec![PathChange::Changed {
+                path: dir.join("x y.txt"),
+                modified: dir.join("x_y.txt"),
+            }]
+        );
+        assert_eq!(fs::read_to_string(dir.join("x_y.txt")).unwrap(), "x y.txt");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_on_collision() {
+        let vec_args = [
+            "notox".to_string(),
+            "--on-collision".to_string(),
+            "suffix".to_string(),
+            ".".to_string(),
+        ];
+        let res = parse_args(&vec_args).unwrap();
+        assert_eq!(res.0.on_collision, Some(CollisionPolicy::Suffix));
+        let vec_args = [
+            "notox".to_string(),
+            "--on-collision".to_string(),
+            "rename".to_string(),
+        ];
+        assert_eq!(parse_args(&vec_args).err(), Some(ExitCode::Usage));
+
+        assert_eq!(NotoxArgs::default().clobber_policy, ClobberPolicy::Refuse);
+        for (value, policy) in [
+            ("refuse", ClobberPolicy::Refuse),
+            ("overwrite", ClobberPolicy::Overwrite),
+        ] {
+            let vec_args = ["notox", "--clobber", value, "."].map(String::from);
+            assert_eq!(parse_args(&vec_args).unwrap().0.clobber_policy, policy);
+        }
+    }
+}
+
+mod opaque_ext {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{NotoxArgs, PathChange};
+
+    use crate::common::quiet_options;
+
+    #[test]
+    fn test_opaque_app_directory() {
+        let dir = PathBuf::from("test_opaque_ext");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        let app = dir.join("My App.app");
+        std::fs::create_dir_all(app.join("Contents dir")).unwrap();
+        std::fs::File::create(app.join("Contents dir").join("dirty name.txt")).unwrap();
+        std::fs::File::create(dir.join("dirty top.txt")).unwrap();
+
+        let vec_args = [
+            "notox".to_string(),
+            dir.to_string_lossy().to_string(),
+            "--opaque-ext".to_string(),
+            "APP, .bundle".to_string(),
+            "-d".to_string(),
+            "-q".to_string(),
+        ];
+        let (options, paths) = notox::parse_args(&vec_args).unwrap();
+        assert_eq!(
+            options.treat_as_file_exts,
+            vec!["APP".to_string(), "bundle".to_string()]
+        );
+        let res = notox::notox(&options, &paths);
+        assert_eq!(res.len(), 3);
+        assert!(res.contains(&PathChange::Changed {
+            path: app.clone(),
+            modified: dir.join("My_App.app"),
+        }));
+        // the content of the bundle is left alone
+        let renamed_app = dir.join("My_App.app");
+        assert!(renamed_app
+            .join("Contents dir")
+            .join("dirty name.txt")
+            .exists());
+        assert!(dir.join("dirty_top.txt").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_opaque_input_directory() {
+        let app = PathBuf::from("test_opaque_input.bundle");
+        if app.exists() {
+            std::fs::remove_dir_all(&app).unwrap();
+        }
+        std::fs::create_dir_all(&app).unwrap();
+        std::fs::File::create(app.join("dirty name.txt")).unwrap();
+
+        let options = NotoxArgs {
+            treat_as_file_exts: vec!["bundle".to_string()],
+            ..quiet_options(true)
+        };
+        let res = notox::notox(&options, &HashSet::from([app.clone()]));
+        assert_eq!(res, vec![PathChange::Unchanged { path: app.clone() }]);
+
+        let options = NotoxArgs {
+            treat_as_file_exts: Vec::new(),
+            ..options
+        };
+        let res = notox::notox(&options, &HashSet::from([app.clone()]));
+        assert_eq!(res.len(), 2);
+
+        std::fs::remove_dir_all(&app).unwrap();
+    }
+}
+
+mod ordering {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{sort_for_apply, sort_for_undo, PathChange};
+
+    use crate::common::quiet_options;
+
+    fn unchanged(path: &str) -> PathChange {
+        PathChange::Unchanged {
+            path: PathBuf::from(path),
+        }
+    }
+
+    #[test]
+    fn test_directory_before_entries() {
+        let dir = PathBuf::from("test_ordering");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        for sub_dir in ["a dir", "b dir", "c dir"] {
+            std::fs::create_dir_all(dir.join(sub_dir).join("sub dir")).unwrap();
+            std::fs::File::create(dir.join(sub_dir).join("sub dir").join("file 1.txt")).unwrap();
+            std::fs::File::create(dir.join(sub_dir).join("file 2.txt")).unwrap();
+        }
+
+        let options = quiet_options(true);
+        for _ in 0..10 {
+            let res = notox::notox(&options, &HashSet::from([dir.clone()]));
+            assert_eq!(res.len(), 13);
+            for (idx, one_change) in res.iter().enumerate() {
+                if let Some(parent) = one_change.path().parent() {
+                    if let Some(parent_idx) = res.iter().position(|other| other.path() == parent) {
+                        assert!(parent_idx < idx, "{:?}", res);
+                    }
+                }
+            }
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_deterministic_order() {
+        let dir = PathBuf::from("test_deterministic_order");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        for sub_dir in ["c dir", "a dir", "b dir"] {
+            std::fs::create_dir_all(dir.join(sub_dir)).unwrap();
+            for file in ["z.txt", "m n.txt", "a.txt"] {
+                std::fs::File::create(dir.join(sub_dir).join(file)).unwrap();
+            }
+        }
+        std::fs::File::create(dir.join("top file.txt")).unwrap();
+
+        let options = quiet_options(true);
+        let paths = HashSet::from([
+            dir.join("c dir"),
+            dir.join("top file.txt"),
+            dir.join("a dir"),
+            dir.join("b dir"),
+        ]);
+        let first = notox::notox(&options, &paths);
+        let mut expected = Vec::new();
+        for sub_dir in ["a dir", "b dir", "c dir"] {
+            expected.push(dir.join(sub_dir));
+            for file in ["a.txt", "m n.txt", "z.txt"] {
+                expected.push(dir.join(sub_dir).join(file));
+            }
+        }
+        expected.push(dir.join("top file.txt"));
+        assert_eq!(
+            first.iter().map(PathChange::path).collect::<Vec<_>>(),
+            expected.iter().collect::<Vec<_>>()
+        );
+        for _ in 0..10 {
+            assert_eq!(notox::notox(&options, &paths), first);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sort_for_apply_and_undo() {
+        let mut results = vec![
+            unchanged("a/b/c.txt"),
+            unchanged("a/b"),
+            unchanged("d.txt"),
+            unchanged("a"),
+            unchanged("a/e.txt"),
+        ];
+        sort_for_apply(&mut results);
+        assert_eq!(
+            results,
+            vec![
+                unchanged("d.txt"),
+                unchanged("a"),
+                unchanged("a/b"),
+                unchanged("a/e.txt"),
+                unchanged("a/b/c.txt"),
+            ]
+        );
+        sort_for_undo(&mut results);
+        assert_eq!(
+            results,
+            vec![
+                unchanged("a/b/c.txt"),
+                unchanged("a/b"),
+                unchanged("a/e.txt"),
+                unchanged("d.txt"),
+                unchanged("a"),
+            ]
+        );
+    }
+}
+
+mod plan {
+    use std::{collections::HashSet, fs, path::PathBuf};
+
+    use notox::{Notox, PathChange};
+
+    use crate::common::quiet_options;
+
+    #[test]
+    fn test_plan_and_apply() {
+        let dir = PathBuf::from("test_plan_and_apply");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub dir")).unwrap();
+        fs::write(dir.join("a b.txt"), "").unwrap();
+        fs::write(dir.join("sub dir").join("c d.txt"), "").unwrap();
+        fs::write(dir.join("ok.txt"), "").unwrap();
+
+        // the plan is a dry-run even when the renames are enabled
+        let notox_inst = Notox::new(&quiet_options(false));
+        let plan = notox_inst.plan(&HashSet::from([dir.clone()]));
+        assert_eq!(plan.iter().filter(|one| one.is_dry_run_rename()).count(), 3);
+        assert!(dir.join("a b.txt").exists());
+        assert!(dir.join("sub dir").exists());
+
+        // only the approved renames are applied, without walking the tree again
+        let approved: Vec<PathChange> = plan
+            .into_iter()
+            .filter(|one| one.path() != dir.join("a b.txt"))
+            .collect();
+        let res = notox_inst.apply(&approved);
+        assert_eq!(res.len(), 2);
+        assert!(res
+            .iter()
+            .all(|one| matches!(one, PathChange::Changed { .. })));
+        assert!(dir.join("a b.txt").exists());
+        assert!(dir.join("sub_dir").join("c_d.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_apply_ignores_dry_run() {
+        let dir = PathBuf::from("test_apply_ignores_dry_run");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("e f.txt"), "").unwrap();
+
+        let notox_inst = Notox::new(&quiet_options(true));
+        let plan = notox_inst.plan(&HashSet::from([dir.clone()]));
+        let res = notox_inst.apply(&plan);
+        assert_eq!(
+            res,
+            vec![PathChange::Changed {
+                path: dir.join("e f.txt"),
+                modified: dir.join("e_f.txt"),
+            }]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod stream {
+    use std::{collections::HashSet, fs, path::PathBuf};
+
+    use notox::{Notox, NotoxArgs, PathChange, STREAM_BUFFER};
+    use tokio_stream::StreamExt;
+
+    use crate::common::quiet_options;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_run_stream() {
+        let dir = PathBuf::from("test_run_stream");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub dir")).unwrap();
+        fs::write(dir.join("a b.txt"), "").unwrap();
+        fs::write(dir.join("sub dir").join("c d.txt"), "").unwrap();
+
+        let notox_inst = Notox::new(&quiet_options(false));
+        let paths = HashSet::from([dir.clone()]);
+        let seen: Vec<PathChange> = runtime().block_on(async {
+            let stream = notox_inst.run_stream(&paths).await;
+            stream.collect().await
+        });
+        assert!(seen.contains(&PathChange::Changed {
+            path: dir.join("sub dir"),
+            modified: dir.join("sub_dir"),
+        }));
+        assert!(seen.contains(&PathChange::Changed {
+            path: dir.join("sub_dir").join("c d.txt"),
+            modified: dir.join("sub_dir").join("c_d.txt"),
+        }));
+        // a directory is yielded before its entries
+        let position = |path: PathBuf| seen.iter().position(|one| one.path() == path).unwrap();
+        assert!(position(dir.join("sub dir")) < position(dir.join("sub_dir").join("c d.txt")));
+        assert!(dir.join("sub_dir").join("c_d.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_stream_more_than_buffer() {
+        let dir = PathBuf::from("test_run_stream_buffer");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        for idx in 0..STREAM_BUFFER + 10 {
+            fs::write(dir.join(format!("file {}.txt", idx)), "").unwrap();
+        }
+        let notox_inst = Notox::new(&quiet_options(true));
+        let paths = HashSet::from([dir.clone()]);
+        let count = runtime().block_on(async {
+            // the walk waits for the stream to be polled
+            let mut stream = Box::pin(notox_inst.run_stream(&paths).await);
+            let mut count = 0;
+            while let Some(one_change) = stream.next().await {
+                assert!(!one_change.is_error(), "{:?}", one_change);
+                count += 1;
+            }
+            count
+        });
+        assert_eq!(count, STREAM_BUFFER + 11);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_stream_dropped() {
+        let dir = PathBuf::from("test_run_stream_dropped");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        for idx in 0..3 * STREAM_BUFFER {
+            fs::write(dir.join(format!("file {}.txt", idx)), "").unwrap();
+        }
+        let notox_inst = Notox::new(&NotoxArgs {
+            threads: Some(2),
+            ..quiet_options(false)
+        });
+        let paths = HashSet::from([dir.clone()]);
+        runtime().block_on(async {
+            let mut stream = Box::pin(notox_inst.run_stream(&paths).await);
+            assert!(stream.next().await.is_some());
+        });
+        // the walk is stopped once the buffer and the entries already started are done
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        let renamed = fs::read_dir(&dir)
+            .unwrap()
+            .filter(|entry| {
+                !entry
+                    .as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .contains(' ')
+            })
+            .count();
+        assert!(renamed <= STREAM_BUFFER + 4, "{} renamed", renamed);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(unix)]
+mod symlinks {
+    use std::{
+        fs,
+        os::unix::fs::symlink,
+        path::{Path, PathBuf},
+    };
+
+    use notox::{parse_args, NotoxArgs, PathChange, Summary};
+
+    use crate::common::{quiet_options, run_dir};
+
+    fn run(dir: &Path, follow_symlinks: bool) -> Vec<PathChange> {
+        let options = NotoxArgs {
+            follow_symlinks,
+            ..quiet_options(false)
+        };
+        run_dir(dir, &options)
+    }
+
+    /// `dir/link dir` points to `outside` which contains `inner file.txt`
+    fn create_tree(dir: &Path, outside: &Path) {
+        let _ = fs::remove_dir_all(dir);
+        let _ = fs::remove_dir_all(outside);
+        fs::create_dir(dir).unwrap();
+        fs::create_dir(outside).unwrap();
+        fs::write(outside.join("inner file.txt"), "").unwrap();
+        symlink(fs::canonicalize(outside).unwrap(), dir.join("link dir")).unwrap();
+    }
+
+    #[test]
+    fn test_symlink_not_followed() {
+        let dir = PathBuf::from("test_symlink_not_followed");
+        let outside = PathBuf::from("test_symlink_not_followed_outside");
+        create_tree(&dir, &outside);
+        let res = run(&dir, false);
+        assert!(res.contains(&PathChange::Symlink {
+            path: dir.join("link dir"),
+            target: fs::canonicalize(&outside).unwrap(),
+        }));
+        assert!(res.contains(&PathChange::Changed {
+            path: dir.join("link dir"),
+            modified: dir.join("link_dir"),
+        }));
+        assert!(dir
+            .join("link_dir")
+            .symlink_metadata()
+            .unwrap()
+            .is_symlink());
+        assert!(outside.join("inner file.txt").exists());
+        // the link is counted once
+        let summary = Summary::from_results(&res);
+        assert_eq!(summary.checked, 2);
+        assert_eq!(summary.changed, 1);
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn test_symlink_followed() {
+        let dir = PathBuf::from("test_symlink_followed");
+        let outside = PathBuf::from("test_symlink_followed_outside");
+        create_tree(&dir, &outside);
+        let res = run(&dir, true);
+        assert!(res.contains(&PathChange::Changed {
+            path: dir.join("link_dir").join("inner file.txt"),
+            modified: dir.join("link_dir").join("inner_file.txt"),
+        }));
+        assert!(outside.join("inner_file.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn test_symlink_cycle() {
+        let dir = PathBuf::from("test_symlink_cycle");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("a file.txt"), "").unwrap();
+        symlink("..", dir.join("sub").join("loop")).unwrap();
+        let res = run(&dir, true);
+        let renamed = res
+            .iter()
+            .filter(|one_change| matches!(one_change, PathChange::Changed { .. }))
+            .count();
+        assert_eq!(renamed, 1, "{:?}", res);
+        assert!(
+            res.iter().all(|one_change| !one_change.is_error()),
+            "{:?}",
+            res
+        );
+        assert!(dir.join("sub").join("a_file.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_symlink_serde() {
+        let symlink = PathChange::Symlink {
+            path: PathBuf::from("link"),
+            target: PathBuf::from("../target"),
+        };
+        let json = serde_json::to_string(&symlink).unwrap();
+        assert_eq!(
+            json,
+            r#"{"path":"link","modified":null,"error":null,"status":"symlink","target":"../target"}"#
+        );
+        let deserialized: PathChange = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, symlink);
+    }
+
+    #[test]
+    fn test_parse_args_follow_symlinks() {
+        let vec_args = [
+            "notox".to_string(),
+            "--follow-symlinks".to_string(),
+            ".".to_string(),
+        ];
+        assert!(parse_args(&vec_args).unwrap().0.follow_symlinks);
+    }
+}
+
+mod targets {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use notox::{parse_args, CleanTargets, NotoxArgs};
+
+    use crate::common::{create_tree, quiet_options, run_dir};
+
+    fn run(dir: &Path, targets: CleanTargets) {
+        let options = NotoxArgs {
+            targets,
+            ..quiet_options(false)
+        };
+        run_dir(dir, &options);
+    }
+
+    const FILES: &[&str] = &["my dir/my file.txt", "other file.txt"];
+
+    #[test]
+    fn test_targets_files_only() {
+        let dir = PathBuf::from("test_targets_files_only");
+        create_tree(&dir, FILES);
+        run(
+            &dir,
+            CleanTargets {
+                files: true,
+                dirs: false,
+            },
+        );
+        assert!(dir.join("my dir").join("my_file.txt").exists());
+        assert!(dir.join("other_file.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_targets_dirs_only() {
+        let dir = PathBuf::from("test_targets_dirs_only");
+        create_tree(&dir, FILES);
+        run(
+            &dir,
+            CleanTargets {
+                files: false,
+                dirs: true,
+            },
+        );
+        assert!(dir.join("my_dir").join("my file.txt").exists());
+        assert!(dir.join("other file.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_targets() {
+        let args = |flag: &str| vec!["notox".to_string(), flag.to_string()];
+        assert_eq!(
+            parse_args(&args("--files-only")).unwrap().0.targets,
+            CleanTargets {
+                files: true,
+                dirs: false,
+            }
+        );
+        assert_eq!(
+            parse_args(&args("--dirs-only")).unwrap().0.targets,
+            CleanTargets {
+                files: false,
+                dirs: true,
+            }
+        );
+        assert_eq!(
+            parse_args(&args(".")).unwrap().0.targets,
+            CleanTargets::default()
+        );
+    }
+}
+
+mod threads {
+    use std::{collections::HashSet, fs, path::PathBuf};
+
+    use notox::{parse_args, ExitCode, Notox, NotoxArgs};
+
+    use crate::common::quiet_options;
+
+    fn threads_options(threads: Option<usize>) -> NotoxArgs {
+        NotoxArgs {
+            threads,
+            ..quiet_options(true)
+        }
+    }
+
+    #[test]
+    fn test_threads_same_results() {
+        let dir = PathBuf::from("test_threads_same_results");
+        let _ = fs::remove_dir_all(&dir);
+        for sub in ["one dir", "two dir"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+            fs::write(dir.join(sub).join("a b.txt"), "").unwrap();
+        }
+        let paths = HashSet::from([dir.clone()]);
+        let expected: HashSet<_> = Notox::new(&threads_options(None))
+            .run(&paths)
+            .into_iter()
+            .collect();
+        for threads in [1, 2] {
+            let res: HashSet<_> = Notox::new(&threads_options(Some(threads)))
+                .run(&paths)
+                .into_iter()
+                .collect();
+            assert_eq!(res, expected, "{}", threads);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_threads_pool_size() {
+        let dir = PathBuf::from("test_threads_pool_size");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub dir")).unwrap();
+        let mut pool_sizes = HashSet::new();
+        Notox::new(&threads_options(Some(2))).run_with_callback(
+            &HashSet::from([dir.clone()]),
+            |_| {
+                pool_sizes.insert(rayon::current_num_threads());
+            },
+        );
+        assert_eq!(pool_sizes, HashSet::from([2]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_threads() {
+        let vec_args = [
+            "notox".to_string(),
+            "--threads".to_string(),
+            "2".to_string(),
+            ".".to_string(),
+        ];
+        assert_eq!(parse_args(&vec_args).unwrap().0.threads, Some(2));
+        for invalid in ["0", "two"] {
+            let vec_args = [
+                "notox".to_string(),
+                "--threads".to_string(),
+                invalid.to_string(),
+            ];
+            assert_eq!(
+                parse_args(&vec_args).err(),
+                Some(ExitCode::Usage),
+                "{}",
+                invalid
+            );
+        }
+        let vec_args = ["notox".to_string(), "--threads".to_string()];
+        assert_eq!(parse_args(&vec_args).err(), Some(ExitCode::Usage));
+    }
+
+    #[test]
+    fn test_chunk_size_same_results() {
+        let dir = PathBuf::from("test_chunk_size_same_results");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub dir")).unwrap();
+        let mut paths = HashSet::from([dir.join("sub dir")]);
+        for idx in 0..20 {
+            let file_path = dir.join(format!("file {}.txt", idx));
+            fs::write(&file_path, "").unwrap();
+            paths.insert(file_path);
+        }
+        let expected = Notox::new(&threads_options(Some(2))).run(&paths);
+        for chunk_size in [1, 3, 64] {
+            let options = NotoxArgs {
+                chunk_size: Some(chunk_size),
+                ..threads_options(Some(2))
+            };
+            // the paths are sorted, the order does not depend on the chunks
+            assert_eq!(Notox::new(&options).run(&paths), expected, "{}", chunk_size);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_chunk_size() {
+        let vec_args = ["notox", "--chunk-size", "64", "."].map(String::from);
+        assert_eq!(parse_args(&vec_args).unwrap().0.chunk_size, Some(64));
+        for invalid in ["0", "many"] {
+            let vec_args = ["notox", "--chunk-size", invalid].map(String::from);
+            assert_eq!(
+                parse_args(&vec_args).err(),
+                Some(ExitCode::Usage),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
+
+mod time_window {
+    use std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+        time::{Duration, SystemTime},
+    };
+
+    use notox::{parse_args, ExitCode, Notox, PathChange};
+
+    const DAY: Duration = Duration::from_secs(86400);
+
+    fn create_with_mtime(path: &Path, age: Duration) {
+        let file = std::fs::File::create(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    fn run(dir: &Path, option: &str, value: &str) -> Vec<PathChange> {
+        let (notox_args, _) = parse_args(&[
+            "notox".to_string(),
+            "-q".to_string(),
+            option.to_string(),
+            value.to_string(),
+            dir.to_string_lossy().to_string(),
+        ])
+        .unwrap();
+        let mut res = Notox::new(&notox_args).run(&HashSet::from([dir.to_path_buf()]));
+        res.sort_by(|first, second| first.path().cmp(second.path()));
+        res
+    }
+
+    fn skipped(path: PathBuf) -> PathChange {
+        PathChange::Skipped {
+            path,
+            reason: "modified outside of the time window".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_newer_and_older_than() {
+        let dir = PathBuf::from("test_time_window");
+        // the directory is old, its entries are still checked
+        std::fs::create_dir_all(dir.join("old dir")).unwrap();
+        create_with_mtime(&dir.join("old dir/recent file.txt"), DAY);
+        create_with_mtime(&dir.join("recent file.txt"), DAY);
+        create_with_mtime(&dir.join("old file.txt"), 400 * DAY);
+        std::fs::File::open(dir.join("old dir"))
+            .unwrap()
+            .set_modified(SystemTime::now() - 400 * DAY)
+            .unwrap();
+
+        let res = run(&dir, "--newer-than", "7d");
+        assert_eq!(
+            res,
+            vec![
+                PathChange::Unchanged { path: dir.clone() },
+                skipped(dir.join("old dir")),
+                PathChange::WouldChange {
+                    path: dir.join("old dir/recent file.txt"),
+                    modified: dir.join("old dir/recent_file.txt"),
+                },
+                skipped(dir.join("old file.txt")),
+                PathChange::WouldChange {
+                    path: dir.join("recent file.txt"),
+                    modified: dir.join("recent_file.txt"),
+                },
+            ]
+        );
+
+        let res = run(&dir, "--older-than", "1y");
+        assert_eq!(
+            res,
+            vec![
+                PathChange::Unchanged { path: dir.clone() },
+                PathChange::WouldChange {
+                    path: dir.join("old dir"),
+                    modified: dir.join("old_dir"),
+                },
+                skipped(dir.join("old dir/recent file.txt")),
+                PathChange::WouldChange {
+                    path: dir.join("old file.txt"),
+                    modified: dir.join("old_file.txt"),
+                },
+                skipped(dir.join("recent file.txt")),
+            ]
+        );
+
+        // all of them are newer than this date
+        let res = run(&dir, "--newer-than", "2000-01-01");
+        assert!(res
+            .iter()
+            .all(|one_change| !matches!(one_change, PathChange::Skipped { .. })));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_time_window_args() {
+        let args = |option: &str, value: &str| {
+            vec![
+                "notox".to_string(),
+                option.to_string(),
+                value.to_string(),
+                "Cargo.toml".to_string(),
+            ]
+        };
+        let (notox_args, _) = parse_args(&args("--older-than", "2024-03-01")).unwrap();
+        assert_eq!(
+            notox_args.older_than,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1709251200))
+        );
+        let (notox_args, _) = parse_args(&args("--newer-than", "2w")).unwrap();
+        let limit = notox_args.newer_than.unwrap();
+        let age = SystemTime::now().duration_since(limit).unwrap();
+        assert!(age >= 14 * DAY && age < 14 * DAY + Duration::from_secs(60));
+        for value in ["7", "7x", "d", "2024-13-01", "2024-1-1"] {
+            assert_eq!(
+                parse_args(&args("--newer-than", value)),
+                Err(ExitCode::Usage),
+                "{}",
+                value
+            );
+        }
+    }
+}
+
+#[cfg(feature = "trash")]
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "macos"))]
+mod trash {
+    use std::{collections::HashSet, path::PathBuf, process::Command};
+
+    use assert_cmd::prelude::*;
+    use notox::{ClobberPolicy, ExitCode, Notox, NotoxArgs, PathChange};
+    use predicates::prelude::*;
+
+    use crate::common::quiet_options;
+
+    fn options(dry_run: bool) -> NotoxArgs {
+        NotoxArgs {
+            clobber_policy: ClobberPolicy::Trash,
+            ..quiet_options(dry_run)
+        }
+    }
+
+    #[test]
+    #[cfg_attr(
+        not(target_os = "linux"),
+        ignore = "the target would be sent to the trash of the user"
+    )]
+    fn test_trash_existing_target() {
+        // the trash of the test, on the same filesystem as the target,
+        // given to the binary only to not change the environment of the other tests
+        let data_home = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("test_trash_data_home");
+        let _ = std::fs::remove_dir_all(&data_home);
+        std::fs::create_dir_all(&data_home).unwrap();
+        let run = |extra: &[&str]| {
+            let mut cmd = Command::cargo_bin("notox").unwrap();
+            cmd.env("XDG_DATA_HOME", &data_home)
+                .args(["test trash target.txt", "--clobber", "trash"])
+                .args(extra);
+            cmd.assert().success()
+        };
+
+        let source = PathBuf::from("test trash target.txt");
+        let target = PathBuf::from("test_trash_target.txt");
+        std::fs::write(&source, "new").unwrap();
+        std::fs::write(&target, "old").unwrap();
+
+        // the existing target is not a failure with the trash
+        run(&[]).stdout(predicate::str::contains(
+            "test trash target.txt -> test_trash_target.txt : dry-run",
+        ));
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "old");
+
+        run(&["-d"])
+            .stdout(predicate::str::contains("test_trash_target.txt -> trash"))
+            .stdout(predicate::str::contains(
+                "test trash target.txt -> test_trash_target.txt\n",
+            ))
+            .stdout(predicate::str::contains(
+                "1 file checked: 1 changed, 0 unchanged, 0 errors, 1 trashed",
+            ));
+        assert!(!source.exists());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+        #[cfg(target_os = "linux")]
+        assert_eq!(
+            std::fs::read_to_string(data_home.join("Trash/files/test_trash_target.txt")).unwrap(),
+            "old"
+        );
+
+        std::fs::remove_file(&target).unwrap();
+        std::fs::remove_dir_all(&data_home).unwrap();
+    }
+
+    #[test]
+    fn test_trash_without_target() {
+        let source = PathBuf::from("test trash alone.txt");
+        let target = PathBuf::from("test_trash_alone.txt");
+        std::fs::File::create(&source).unwrap();
+
+        let res = Notox::new(&options(false)).run(&HashSet::from([source.clone()]));
+        assert_eq!(
+            res,
+            vec![PathChange::Changed {
+                path: source.clone(),
+                modified: target.clone(),
+            }]
+        );
+
+        std::fs::remove_file(&target).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_clobber() {
+        let vec_args = [
+            "notox".to_string(),
+            "README.md".to_string(),
+            "--clobber".to_string(),
+            "trash".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).unwrap();
+        assert_eq!(options.clobber_policy, ClobberPolicy::Trash);
+
+        let vec_args = [
+            "notox".to_string(),
+            "--clobber".to_string(),
+            "backup".to_string(),
+        ];
+        assert_eq!(notox::parse_args(&vec_args).err().unwrap(), ExitCode::Usage);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_trashed_serde() {
+        let trashed = PathChange::Trashed {
+            path: PathBuf::from("a_b"),
+        };
+        let json = serde_json::to_string(&trashed).unwrap();
+        assert_eq!(
+            json,
+            r#"{"path":"a_b","modified":null,"error":null,"status":"trashed"}"#
+        );
+        let back: PathChange = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, trashed);
+    }
+}
+
+mod try_run {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{Notox, NotoxArgs, NotoxError, PathChange};
+
+    use crate::common::quiet_options;
+
+    fn options(stop_at_first_error: bool) -> NotoxArgs {
+        NotoxArgs {
+            // one thread cleans the sorted paths in order
+            threads: Some(1),
+            stop_at_first_error,
+            ..quiet_options(false)
+        }
+    }
+
+    #[test]
+    fn test_try_run_success() {
+        let source = PathBuf::from("test try run ok.txt");
+        let target = PathBuf::from("test_try_run_ok.txt");
+        std::fs::File::create(&source).unwrap();
+
+        let results = Notox::new(&options(false))
+            .try_run(&HashSet::from([source.clone()]))
+            .unwrap();
+        assert_eq!(
+            results,
+            vec![PathChange::Changed {
+                path: source,
+                modified: target.clone(),
+            }]
+        );
+
+        std::fs::remove_file(&target).unwrap();
+    }
+
+    #[test]
+    fn test_try_run_all_errors() {
+        let first_missing = PathBuf::from("test try run all a.txt");
+        let source = PathBuf::from("test try run all b.txt");
+        let target = PathBuf::from("test_try_run_all_b.txt");
+        let second_missing = PathBuf::from("test try run all c.txt");
+        std::fs::File::create(&source).unwrap();
+
+        let error = Notox::new(&options(false))
+            .try_run(&HashSet::from([
+                first_missing.clone(),
+                source.clone(),
+                second_missing.clone(),
+            ]))
+            .unwrap_err();
+        match &error {
+            NotoxError::Multiple(errors) => {
+                assert_eq!(errors.len(), 2);
+                assert!(
+                    matches!(&errors[0], NotoxError::Rename { path, .. } if path == &first_missing)
+                );
+                assert!(
+                    matches!(&errors[1], NotoxError::Rename { path, .. } if path == &second_missing)
+                );
+            }
+            _ => panic!("Unexpected error {:?}", error),
+        }
+        assert!(error.to_string().starts_with("2 errors\n"));
+        // the other renames are still applied
+        assert!(target.exists());
+
+        std::fs::remove_file(&target).unwrap();
+    }
+
+    #[test]
+    fn test_try_run_stop_at_first_error() {
+        let missing = PathBuf::from("test try run stop a.txt");
+        let source = PathBuf::from("test try run stop b.txt");
+        std::fs::File::create(&source).unwrap();
+
+        let error = Notox::new(&options(true))
+            .try_run(&HashSet::from([missing.clone(), source.clone()]))
+            .unwrap_err();
+        assert!(matches!(&error, NotoxError::Rename { path, .. } if path == &missing));
+        // the path after the error is not cleaned
+        assert!(source.exists());
+
+        std::fs::remove_file(&source).unwrap();
+    }
+
+    #[test]
+    fn test_notox_error_from_change() {
+        let unchanged = PathChange::Unchanged {
+            path: PathBuf::from("clean.txt"),
+        };
+        assert_eq!(NotoxError::from_change(&unchanged), None);
+        let error = PathChange::Error {
+            path: PathBuf::from("dir"),
+            error: "Error while reading directory".to_string(),
+        };
+        let notox_error = NotoxError::from_change(&error).unwrap();
+        assert_eq!(
+            notox_error.to_string(),
+            "dir : Error while reading directory"
+        );
+        let _: &dyn std::error::Error = &notox_error;
+    }
+}
+
+#[cfg(feature = "serde")]
+mod undo {
+    use std::{collections::HashSet, fs, path::PathBuf};
+
+    use notox::{parse_args, undo, ExitCode, PathChange};
+
+    use crate::common::{create_tree, quiet_options};
+
+    const FILES: &[&str] = &["sub dir/file one.txt"];
+
+    #[test]
+    fn test_undo_run() {
+        let dir = PathBuf::from("test_undo_run");
+        create_tree(&dir, FILES);
+        let changes = notox::notox(&quiet_options(false), &HashSet::from([dir.clone()]));
+        assert!(dir.join("sub_dir").join("file_one.txt").exists());
+
+        let res = undo(&changes, &quiet_options(false));
+        assert_eq!(res.len(), 2);
+        assert!(res
+            .iter()
+            .all(|one_change| matches!(one_change, PathChange::Changed { .. })));
+        assert!(dir.join("sub dir").join("file one.txt").exists());
+        assert!(!dir.join("sub_dir").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_undo_dry_run() {
+        let dir = PathBuf::from("test_undo_dry_run");
+        create_tree(&dir, FILES);
+        let changes = notox::notox(&quiet_options(false), &HashSet::from([dir.clone()]));
+
+        let res = undo(&changes, &quiet_options(true));
+        assert_eq!(res.len(), 2);
+        assert!(res.contains(&PathChange::WouldChange {
+            path: dir.join("sub_dir"),
+            modified: dir.join("sub dir"),
+        }));
+        assert!(dir.join("sub_dir").join("file_one.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_undo_errors() {
+        let dir = PathBuf::from("test_undo_errors");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("taken.txt"), "").unwrap();
+        fs::write(dir.join("taken_again.txt"), "").unwrap();
+        let changes = vec![
+            PathChange::Changed {
+                path: dir.join("taken.txt"),
+                modified: dir.join("taken_again.txt"),
+            },
+            PathChange::Changed {
+                path: dir.join("gone file.txt"),
+                modified: dir.join("gone_file.txt"),
+            },
+            PathChange::Unchanged {
+                path: dir.join("taken.txt"),
+            },
+        ];
+
+        let res = undo(&changes, &quiet_options(false));
+        assert_eq!(
+            res,
+            vec![
+                PathChange::Error {
+                    path: dir.join("gone_file.txt"),
+                    error: "modified path not found, cannot undo".to_string(),
+                },
+                PathChange::ErrorRename {
+                    path: dir.join("taken_again.txt"),
+                    modified: dir.join("taken.txt"),
+                    error: "original path already exists".to_string(),
+                },
+            ]
+        );
+        assert!(dir.join("taken_again.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_undo() {
+        let file = PathBuf::from("test_parse_args_undo.json");
+        fs::write(
+            &file,
+            r#"[{"path":"a b.txt","modified":"a_b.txt","status":"changed"}]"#,
+        )
+        .unwrap();
+        let vec_args = [
+            "notox".to_string(),
+            "--undo".to_string(),
+            file.display().to_string(),
+        ];
+        let res = parse_args(&vec_args).unwrap();
+        assert_eq!(
+            res.0.undo,
+            Some(vec![PathChange::Changed {
+                path: PathBuf::from("a b.txt"),
+                modified: PathBuf::from("a_b.txt"),
+            }])
+        );
+        fs::remove_file(&file).unwrap();
+
+        let vec_args = ["notox".to_string(), "--undo".to_string()];
+        assert_eq!(parse_args(&vec_args).err(), Some(ExitCode::Usage));
+        let vec_args = [
+            "notox".to_string(),
+            "--undo".to_string(),
+            "test_parse_args_undo_missing.json".to_string(),
+        ];
+        assert_eq!(parse_args(&vec_args).err(), Some(ExitCode::Usage));
+    }
+}
+
+#[cfg(feature = "watch")]
+mod watch {
+    use std::{
+        path::PathBuf,
+        time::{Duration, Instant},
+    };
+
+    use notox::{watch::WatchCleaner, PathChange};
+
+    use crate::common::quiet_options;
+
+    #[test]
+    fn test_watch_cleaner_synthetic_events() {
+        let dir = PathBuf::from("test_watch_cleaner");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir(&dir).unwrap();
+        let new_file = dir.join("new file.txt");
+        std::fs::File::create(&new_file).unwrap();
+
+        let options = quiet_options(false);
+        let debounce = Duration::from_millis(100);
+        let mut cleaner = WatchCleaner::new(&options, debounce);
+        let start = Instant::now();
+
+        cleaner.push_event(new_file.clone(), start);
+        assert!(cleaner.clean_ready(start).is_empty());
+        // a second event postpones the cleaning
+        cleaner.push_event(new_file.clone(), start + Duration::from_millis(50));
+        assert!(cleaner
+            .clean_ready(start + Duration::from_millis(120))
+            .is_empty());
+        let res = cleaner.clean_ready(start + Duration::from_millis(150));
+        assert_eq!(
+            res,
+            vec![PathChange::Changed {
+                path: new_file.clone(),
+                modified: dir.join("new_file.txt"),
+            }]
+        );
+
+        // the event of its own rename is ignored
+        cleaner.push_event(dir.join("new_file.txt"), start + Duration::from_millis(160));
+        assert!(cleaner
+            .clean_ready(start + Duration::from_secs(1))
+            .is_empty());
+
+        // a removed file is ignored
+        cleaner.push_event(dir.join("removed file.txt"), start);
+        assert!(cleaner
+            .clean_ready(start + Duration::from_secs(1))
+            .is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_cleaner_new_directory() {
+        let dir = PathBuf::from("test_watch_cleaner_dir");
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(dir.join("new dir")).unwrap();
+        std::fs::File::create(dir.join("new dir").join("a b.txt")).unwrap();
+
+        let options = quiet_options(false);
+        let debounce = Duration::from_millis(100);
+        let mut cleaner = WatchCleaner::new(&options, debounce);
+        let start = Instant::now();
+
+        // the directory is renamed before the event of its file is ready
+        cleaner.push_event(dir.join("new dir"), start);
+        cleaner.push_event(
+            dir.join("new dir").join("a b.txt"),
+            start + Duration::from_millis(50),
+        );
+        let res = cleaner.clean_ready(start + debounce);
+        assert!(
+            res.iter().all(|one_change| !one_change.is_error()),
+            "{:?}",
+            res
+        );
+        assert!(dir.join("new_dir").join("a_b.txt").exists(), "{:?}", res);
+        // the file is no longer at the path of its event
+        assert!(cleaner
+            .clean_ready(start + Duration::from_secs(1))
+            .is_empty());
+
+        // the events of its own renames inside the directory are ignored
+        cleaner.push_event(dir.join("new_dir").join("a_b.txt"), start + debounce);
+        assert!(cleaner
+            .clean_ready(start + Duration::from_secs(1))
+            .is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_args_watch() {
+        let vec_args = [
+            "notox".to_string(),
+            "--watch".to_string(),
+            "src".to_string(),
+        ];
+        let (options, _) = notox::parse_args(&vec_args).ok().unwrap();
+        assert_eq!(options.watch, Some(PathBuf::from("src")));
+    }
+}
+
+mod would_fail {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use notox::{ClobberPolicy, Notox, NotoxArgs, PathChange, Summary};
+
+    use crate::common::quiet_options;
+
+    fn dry_run_options() -> NotoxArgs {
+        quiet_options(true)
+    }
+
+    #[test]
+    fn test_dry_run_would_collide() {
+        let source = PathBuf::from("test would collide.txt");
+        let target = PathBuf::from("test_would_collide.txt");
+        std::fs::File::create(&source).unwrap();
+        std::fs::File::create(&target).unwrap();
+
+        let res = Notox::new(&dry_run_options()).run(&HashSet::from([source.clone()]));
+        assert_eq!(
+            res,
+            vec![PathChange::WouldFail {
+                path: source.clone(),
+                modified: target.clone(),
+                reason: "target already exists".to_string(),
+            }]
+        );
+        assert!(res[0].is_error());
+        // the failing rename is only counted as an error
+        let summary = Summary::from_results(&res);
+        assert_eq!(summary.would_change, 0);
+        assert_eq!(summary.would_fail, 1);
+        assert_eq!(
+            summary.to_string(),
+            "1 file checked: 0 changed, 0 unchanged, 1 error"
+        );
+
+        std::fs::remove_file(&source).unwrap();
+        std::fs::remove_file(&target).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_matches_rename() {
+        let source = PathBuf::from("test would match.txt");
+        let target = PathBuf::from("test_would_match.txt");
+        std::fs::write(&source, "source").unwrap();
+        std::fs::write(&target, "target").unwrap();
+
+        let dry_run = Notox::new(&dry_run_options()).run(&HashSet::from([source.clone()]));
+        let options = NotoxArgs {
+            dry_run: false,
+            ..dry_run_options()
+        };
+        let res = Notox::new(&options).run(&HashSet::from([source.clone()]));
+        match (&dry_run[0], &res[0]) {
+            (PathChange::WouldFail { reason, .. }, PathChange::ErrorRename { error, .. }) => {
+                assert_eq!(reason, error)
+            }
+            _ => panic!("Unexpected results {:?} {:?}", dry_run, res),
+        }
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "target");
+
+        // the dry-run reports the overwrite as a rename when it is asked
+        let options = NotoxArgs {
+            clobber_policy: ClobberPolicy::Overwrite,
+            ..dry_run_options()
+        };
+        let dry_run = Notox::new(&options).run(&HashSet::from([source.clone()]));
+        assert_eq!(
+            dry_run,
+            vec![PathChange::WouldChange {
+                path: source.clone(),
+                modified: target.clone(),
+            }]
+        );
+        let options = NotoxArgs {
+            dry_run: false,
+            ..options
+        };
+        let res = Notox::new(&options).run(&HashSet::from([source.clone()]));
+        assert_eq!(
+            res,
+            vec![PathChange::Changed {
+                path: source,
+                modified: target.clone(),
+            }]
+        );
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "source");
+        std::fs::remove_file(&target).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_would_be_too_long() {
+        let source = PathBuf::from("a b".repeat(100));
+        let res = Notox::new(&dry_run_options()).run(&HashSet::from([source.clone()]));
+        match &res[0] {
+            PathChange::WouldFail { path, reason, .. } => {
+                assert_eq!(path, &source);
+                assert_eq!(reason, "name too long (300 bytes, max 255)");
+            }
+            _ => panic!("Expected WouldFail"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_would_fail_serde() {
+        let would_fail = PathChange::WouldFail {
+            path: PathBuf::from("a b"),
+            modified: PathBuf::from("a_b"),
+            reason: "target already exists".to_string(),
+        };
+        let json = serde_json::to_string(&would_fail).unwrap();
+        assert_eq!(
+            json,
+            r#"{"path":"a b","modified":"a_b","error":"target already exists","status":"would-fail"}"#
+        );
+        let deserialized: PathChange = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, would_fail);
+    }
+}